@@ -57,10 +57,71 @@ pub enum BlendMode {
     Color,
     /// Luminosity blend mode.
     Luminosity,
+    /// A blend mode that isn’t known, keeping its key so that it can
+    /// be written back out. Composited as normal.
+    Unknown([u8; 4]),
 }
 
 impl BlendMode {
-    /// Returns the blend mode as a string slice.
+    /// Creates a blend mode from the key in a layer record, keeping the
+    /// bytes of keys that aren’t known, even if they aren’t valid UTF-8.
+    pub fn from_bytes(bytes: &[u8; 4]) -> Self {
+        std::str::from_utf8(bytes)
+            .ok()
+            .and_then(Self::from_key)
+            .unwrap_or(Self::Unknown(*bytes))
+    }
+
+    /// Returns the known blend mode for a key.
+    fn from_key(key: &str) -> Option<Self> {
+        let blend_mode = match key {
+            "pass" => Self::PassThrough,
+            "norm" => Self::Normal,
+            "diss" => Self::Dissolve,
+            "dark" => Self::Darken,
+            "mul " => Self::Multiply,
+            "idiv" => Self::ColorBurn,
+            "lbrn" => Self::LinearBurn,
+            "dkCl" => Self::DarkerColor,
+            "lite" => Self::Lighten,
+            "scrn" => Self::Screen,
+            "div " => Self::ColorDodge,
+            "lddg" => Self::LinearDodge,
+            "lgCl" => Self::LighterColor,
+            "over" => Self::Overlay,
+            "sLit" => Self::SoftLight,
+            "hLit" => Self::HardLight,
+            "vLit" => Self::VividLight,
+            "lLit" => Self::LinearLight,
+            "pLit" => Self::PinLight,
+            "hMix" => Self::HardMix,
+            "diff" => Self::Difference,
+            "smud" => Self::Exclusion,
+            "fsub" => Self::Subtract,
+            "fdiv" => Self::Divide,
+            "hue " => Self::Hue,
+            "sat " => Self::Saturation,
+            "colr" => Self::Color,
+            "lum " => Self::Luminosity,
+            _ => return None,
+        };
+        Some(blend_mode)
+    }
+
+    /// Returns the key written to a layer record.
+    pub fn key(&self) -> [u8; 4] {
+        match self {
+            Self::Unknown(bytes) => *bytes,
+            _ => {
+                let mut key = [0; 4];
+                key.copy_from_slice(self.as_str().as_bytes());
+                key
+            }
+        }
+    }
+
+    /// Returns the blend mode as a string slice. Unknown keys that aren’t
+    /// valid UTF-8 are given as normal.
     pub fn as_str(&self) -> &str {
         match self {
             Self::PassThrough => "pass",
@@ -91,43 +152,16 @@ impl BlendMode {
             Self::Saturation => "sat ",
             Self::Color => "colr",
             Self::Luminosity => "lum ",
+            Self::Unknown(bytes) => std::str::from_utf8(bytes).unwrap_or("norm"),
         }
     }
 }
 
 impl From<&str> for BlendMode {
+    /// Creates a blend mode from its key, falling back to normal for
+    /// keys that aren’t known.
     fn from(value: &str) -> Self {
-        match value {
-            "pass" => Self::PassThrough,
-            "norm" => Self::Normal,
-            "diss" => Self::Dissolve,
-            "dark" => Self::Darken,
-            "mul " => Self::Multiply,
-            "idiv" => Self::ColorBurn,
-            "lbrn" => Self::LinearBurn,
-            "dkCl" => Self::DarkerColor,
-            "lite" => Self::Lighten,
-            "scrn" => Self::Screen,
-            "div " => Self::ColorDodge,
-            "lddg" => Self::LinearDodge,
-            "lgCl" => Self::LighterColor,
-            "over" => Self::Overlay,
-            "sLit" => Self::SoftLight,
-            "hLit" => Self::HardLight,
-            "vLit" => Self::VividLight,
-            "lLit" => Self::LinearLight,
-            "pLit" => Self::PinLight,
-            "hMix" => Self::HardMix,
-            "diff" => Self::Difference,
-            "smud" => Self::Exclusion,
-            "fsub" => Self::Subtract,
-            "fdiv" => Self::Divide,
-            "hue " => Self::Hue,
-            "sat " => Self::Saturation,
-            "colr" => Self::Color,
-            "lum " => Self::Luminosity,
-            _ => Self::Normal,
-        }
+        Self::from_key(value).unwrap_or(Self::Normal)
     }
 }
 
@@ -161,9 +195,11 @@ impl From<graphics::BlendMode> for BlendMode {
     }
 }
 
-impl From<BlendMode> for graphics::BlendMode {
-    fn from(value: BlendMode) -> Self {
-        match value {
+impl BlendMode {
+    /// Returns the matching blend mode in the graphics crate, or nothing
+    /// if it has no equivalent.
+    pub fn graphics_blend_mode(&self) -> Option<graphics::BlendMode> {
+        let blend_mode = match self {
             BlendMode::PassThrough => graphics::BlendMode::PassThrough,
             BlendMode::Normal => graphics::BlendMode::Normal,
             BlendMode::Darken => graphics::BlendMode::Darken,
@@ -172,6 +208,7 @@ impl From<BlendMode> for graphics::BlendMode {
             BlendMode::Lighten => graphics::BlendMode::Lighten,
             BlendMode::Screen => graphics::BlendMode::Screen,
             BlendMode::ColorDodge => graphics::BlendMode::ColorDodge,
+            BlendMode::LinearDodge => graphics::BlendMode::Addition,
             BlendMode::Overlay => graphics::BlendMode::Overlay,
             BlendMode::SoftLight => graphics::BlendMode::SoftLight,
             BlendMode::HardLight => graphics::BlendMode::HardLight,
@@ -183,17 +220,100 @@ impl From<BlendMode> for graphics::BlendMode {
             BlendMode::Saturation => graphics::BlendMode::Saturation,
             BlendMode::Color => graphics::BlendMode::Color,
             BlendMode::Luminosity => graphics::BlendMode::Luminosity,
-            // These blend modes aren’t compatible —
-            // what’s the best way to handle that?
-            BlendMode::Dissolve => graphics::BlendMode::Normal,
-            BlendMode::LinearBurn => graphics::BlendMode::Normal,
-            BlendMode::DarkerColor => graphics::BlendMode::Normal,
-            BlendMode::LinearDodge => graphics::BlendMode::Normal,
-            BlendMode::LighterColor => graphics::BlendMode::Normal,
-            BlendMode::VividLight => graphics::BlendMode::Normal,
-            BlendMode::LinearLight => graphics::BlendMode::Normal,
-            BlendMode::PinLight => graphics::BlendMode::Normal,
-            BlendMode::HardMix => graphics::BlendMode::Normal,
+            BlendMode::Dissolve
+            | BlendMode::LinearBurn
+            | BlendMode::DarkerColor
+            | BlendMode::LighterColor
+            | BlendMode::VividLight
+            | BlendMode::LinearLight
+            | BlendMode::PinLight
+            | BlendMode::HardMix
+            | BlendMode::Unknown(_) => return None,
+        };
+        Some(blend_mode)
+    }
+
+    /// Returns the enumerated value that descriptors use for the blend
+    /// mode, which differs from the key in a layer record.
+    pub(crate) fn descriptor_value(&self) -> &str {
+        match self {
+            Self::PassThrough => "passThrough",
+            Self::Normal => "Nrml",
+            Self::Dissolve => "Dslv",
+            Self::Darken => "Drkn",
+            Self::Multiply => "Mltp",
+            Self::ColorBurn => "CBrn",
+            Self::LinearBurn => "linearBurn",
+            Self::DarkerColor => "darkerColor",
+            Self::Lighten => "Lghn",
+            Self::Screen => "Scrn",
+            Self::ColorDodge => "CDdg",
+            Self::LinearDodge => "linearDodge",
+            Self::LighterColor => "lighterColor",
+            Self::Overlay => "Ovrl",
+            Self::SoftLight => "SftL",
+            Self::HardLight => "HrdL",
+            Self::VividLight => "vividLight",
+            Self::LinearLight => "linearLight",
+            Self::PinLight => "pinLight",
+            Self::HardMix => "hardMix",
+            Self::Difference => "Dfrn",
+            Self::Exclusion => "Xclu",
+            Self::Subtract => "blendSubtraction",
+            Self::Divide => "blendDivide",
+            Self::Hue => "H   ",
+            Self::Saturation => "Strt",
+            Self::Color => "Clr ",
+            Self::Luminosity => "Lmns",
+            Self::Unknown(_) => "Nrml",
         }
     }
+
+    /// Returns whether the blend mode has an equivalent in the graphics
+    /// crate. Converting any other blend mode gives normal instead.
+    pub fn is_graphics_compatible(&self) -> bool {
+        self.graphics_blend_mode().is_some()
+    }
+}
+
+impl From<BlendMode> for graphics::BlendMode {
+    /// Converts the blend mode, falling back to normal for blend modes
+    /// that have no equivalent. See `is_graphics_compatible`.
+    fn from(value: BlendMode) -> Self {
+        value
+            .graphics_blend_mode()
+            .unwrap_or(graphics::BlendMode::Normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_bytes() {
+        assert_eq!(BlendMode::from_bytes(b"mul "), BlendMode::Multiply);
+
+        let bytes = [0xff, 0xfe, 0x00, 0x41];
+        let blend_mode = BlendMode::from_bytes(&bytes);
+        assert_eq!(blend_mode, BlendMode::Unknown(bytes));
+        assert_eq!(blend_mode.key(), bytes);
+        assert_eq!(blend_mode.as_str(), "norm");
+        assert_eq!(BlendMode::Screen.key(), *b"scrn");
+    }
+
+    #[test]
+    fn is_graphics_compatible() {
+        assert!(BlendMode::Multiply.is_graphics_compatible());
+        assert!(!BlendMode::VividLight.is_graphics_compatible());
+
+        assert_eq!(
+            graphics::BlendMode::from(BlendMode::VividLight),
+            graphics::BlendMode::Normal
+        );
+        assert_eq!(
+            BlendMode::LinearDodge.graphics_blend_mode(),
+            Some(graphics::BlendMode::Addition)
+        );
+    }
 }
@@ -0,0 +1,252 @@
+use std::fmt;
+use std::sync::Arc;
+
+use graphics::Size;
+
+use crate::color_channel::{ColorChannelType, CompressedDataResult};
+use crate::error::WriteError;
+use crate::image_compression::ImageCompression;
+use crate::{rle, zip};
+
+/// A source of channel data for a layer that provides its rows on demand,
+/// so that the whole layer image never has to be held in memory. This is
+/// useful for layers made up of tiles.
+pub trait ChannelSource: Send + Sync {
+    /// Returns the types of the channels provided, in the order
+    /// they’re written. Convention is to put the alpha channel first.
+    fn channel_types(&self) -> Vec<ColorChannelType>;
+
+    /// Returns a row of data for the channel at an index. The row
+    /// must be as long as the layer is wide.
+    fn row(&self, channel_index: usize, y_position: u32) -> Vec<u8>;
+}
+
+/// The channel source for a layer, which can be shared between copies
+/// of the layer.
+#[derive(Clone)]
+pub struct SourcedChannels {
+    /// The source of the channel data.
+    pub source: Arc<dyn ChannelSource>,
+}
+
+// MARK: Creation
+
+impl SourcedChannels {
+    /// Creates new sourced channels.
+    pub fn new(source: impl ChannelSource + 'static) -> Self {
+        Self {
+            source: Arc::new(source),
+        }
+    }
+}
+
+// MARK: Encoding
+
+impl SourcedChannels {
+    /// Returns the compressed data for a channel, pulling the rows from
+    /// the source one at a time. Nothing is cached, so that only one
+    /// compressed channel is held in memory at once.
+    pub(crate) fn compressed_data(
+        &self,
+        channel_index: usize,
+        size: Size<u32>,
+        compression: &ImageCompression,
+    ) -> anyhow::Result<CompressedDataResult> {
+        let row = |y_position: u32| {
+            let row = self.source.row(channel_index, y_position);
+            if row.len() != size.width as usize {
+                anyhow::bail!(WriteError::InvalidImage);
+            }
+            Ok(row)
+        };
+
+        let raw_data = || -> anyhow::Result<CompressedDataResult> {
+            let mut data = Vec::new();
+            for y_position in 0..size.height {
+                data.append(&mut row(y_position)?);
+            }
+            Ok(CompressedDataResult {
+                data,
+                compression: ImageCompression::RawData,
+            })
+        };
+
+        if matches!(
+            compression,
+            ImageCompression::ZipWithoutPrediction | ImageCompression::ZipWithPrediction
+        ) {
+            let raw_data = raw_data()?;
+            return Ok(CompressedDataResult {
+                data: zip::encoded(
+                    &raw_data.data,
+                    size.width as usize,
+                    compression.uses_prediction(),
+                )?,
+                compression: compression.clone(),
+            });
+        }
+
+        // Matches the channels made from an image, which are too
+        // small to compress.
+        if compression == &ImageCompression::RawData
+            || size.width as usize * size.height as usize <= 2
+        {
+            return raw_data();
+        }
+
+        let mut line_lengths = Vec::new();
+        let mut encoded_data = Vec::new();
+        for y_position in 0..size.height {
+            let mut encoded_row = rle::encoded(&row(y_position)?);
+            let Ok(line_length) = u16::try_from(encoded_row.len()) else {
+                // Rows too long to record their encoded length are stored uncompressed.
+                return raw_data();
+            };
+            line_lengths.extend_from_slice(&line_length.to_be_bytes());
+            encoded_data.append(&mut encoded_row);
+        }
+
+        line_lengths.append(&mut encoded_data);
+        Ok(CompressedDataResult {
+            data: line_lengths,
+            compression: ImageCompression::Rle,
+        })
+    }
+}
+
+impl fmt::Debug for SourcedChannels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SourcedChannels")
+            .field("channel_types", &self.source.channel_types())
+            .finish()
+    }
+}
+
+impl PartialEq for SourcedChannels {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.source, &other.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graphics::{Color, Image, Rect, Size};
+
+    use crate::error::Error;
+    use crate::layer::Layer;
+
+    use super::*;
+
+    /// A source made of tiles placed side by side.
+    struct TiledSource {
+        tiles: Vec<Image>,
+    }
+
+    impl ChannelSource for TiledSource {
+        fn channel_types(&self) -> Vec<ColorChannelType> {
+            vec![
+                ColorChannelType::Alpha,
+                ColorChannelType::Red,
+                ColorChannelType::Green,
+                ColorChannelType::Blue,
+            ]
+        }
+
+        fn row(&self, channel_index: usize, y_position: u32) -> Vec<u8> {
+            let component_offset = [3, 0, 1, 2][channel_index];
+            let mut row = Vec::new();
+            for tile in self.tiles.iter() {
+                let row_start = (y_position * tile.bytes_per_row) as usize;
+                for x_position in 0..tile.size.width as usize {
+                    row.push(tile.data[row_start + x_position * 4 + component_offset]);
+                }
+            }
+            row
+        }
+    }
+
+    #[test]
+    fn tiled_source() {
+        let tile_size = Size {
+            width: 2,
+            height: 2,
+        };
+        let tiles = vec![
+            Image::color(&Color::from_rgba_u32(0x24a4ee80), tile_size),
+            Image::color(&Color::from_rgb_u32(0xff0000), tile_size),
+        ];
+
+        // The same pixels as a single image.
+        let mut image = Image::empty(Size {
+            width: 4,
+            height: 2,
+        });
+        for y_position in 0..2 {
+            for (index, tile) in tiles.iter().enumerate() {
+                let source_start = (y_position * tile.bytes_per_row) as usize;
+                let target_start = (y_position * image.bytes_per_row) as usize + index * 8;
+                image.data[target_start..target_start + 8]
+                    .copy_from_slice(&tile.data[source_start..source_start + 8]);
+            }
+        }
+
+        let bounds = Rect::new(0, 0, 4, 2);
+        let mut image_layer = Layer::new(bounds);
+        image_layer.image = Some(image);
+        let mut sourced_layer = Layer::from_channel_source(bounds, TiledSource { tiles });
+
+        assert_eq!(
+            sourced_layer.layer_record_data().unwrap(),
+            image_layer.layer_record_data().unwrap()
+        );
+        assert_eq!(
+            sourced_layer.encoded_image().unwrap(),
+            image_layer.encoded_image().unwrap()
+        );
+    }
+
+    #[test]
+    fn short_row() {
+        let source = TiledSource {
+            tiles: vec![Image::color(
+                &Color::RED,
+                Size {
+                    width: 2,
+                    height: 2,
+                },
+            )],
+        };
+        let mut layer = Layer::from_channel_source(Rect::new(0, 0, 3, 2), source);
+
+        let error = layer.encoded_image().unwrap_err();
+        assert!(matches!(error, Error::Write(WriteError::InvalidImage)));
+    }
+
+    #[test]
+    fn large_source() {
+        let source = TiledSource {
+            tiles: vec![Image::color(
+                &Color::RED,
+                Size {
+                    width: 2,
+                    height: 2,
+                },
+            )],
+        };
+        let channels = SourcedChannels::new(source);
+        // The number of pixels doesn’t fit in 32 bits, so the short first
+        // row is only reached if the size is worked out without overflowing.
+        let size = Size {
+            width: 70_000,
+            height: 70_000,
+        };
+
+        let Err(error) = channels.compressed_data(0, size, &ImageCompression::Rle) else {
+            panic!("The short row wasn’t reported.");
+        };
+        assert!(matches!(
+            error.downcast_ref::<WriteError>(),
+            Some(WriteError::InvalidImage)
+        ));
+    }
+}
@@ -3,7 +3,8 @@ mod channel_type;
 pub use channel_type::ColorChannelType;
 use file_stream::write::FileStreamWriter;
 
-use crate::{data, error::WriteError, image_compression::ImageCompression, rle};
+use crate::error::{self, Error, WriteError};
+use crate::{data, image_compression::ImageCompression, rle, zip};
 
 /// A colour channel holds the data for one channel of
 /// colours for an image.
@@ -41,6 +42,23 @@ impl ColorChannel {
             compressed_data: None,
         }
     }
+
+    /// Creates a colour channel from 16-bit samples, stored big endian as
+    /// Photoshop expects. Each row of data is then twice the width of the
+    /// image in bytes, which is what the RLE line lengths count, as PackBits
+    /// works on bytes rather than samples.
+    pub fn from_u16_samples(color_type: ColorChannelType, samples: &[u16]) -> Self {
+        let data: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| sample.to_be_bytes())
+            .collect();
+        Self {
+            color_type,
+            data_length: data.len(),
+            data,
+            compressed_data: None,
+        }
+    }
 }
 
 // MARK: Encoding
@@ -51,7 +69,7 @@ impl ColorChannel {
     pub fn compressed_data<'a>(
         &'a mut self,
         image_height: u32,
-    ) -> anyhow::Result<CompressedDataResult> {
+    ) -> error::Result<CompressedDataResult> {
         if self.data.len() <= 2 {
             let data = self.data.clone();
             return Ok(CompressedDataResult {
@@ -65,7 +83,17 @@ impl ColorChannel {
             });
         }
 
-        let compressed_data = self.rle_encoded_data(image_height)?;
+        let compressed_data = match self.rle_encoded_data(image_height) {
+            Ok(compressed_data) => compressed_data,
+            // Rows too long to record their encoded length are stored uncompressed.
+            Err(error) if is_rle_row_overflow(&error) => {
+                return Ok(CompressedDataResult {
+                    data: self.data.clone(),
+                    compression: ImageCompression::RawData,
+                });
+            }
+            Err(error) => return Err(error),
+        };
         self.compressed_data = Some(compressed_data.clone());
         Ok(CompressedDataResult {
             data: compressed_data,
@@ -73,9 +101,33 @@ impl ColorChannel {
         })
     }
 
+    /// Returns the data using a compression method. Raw data is returned
+    /// as it is, ZIP data is compressed each time, and RLE uses the
+    /// cached `compressed_data`.
+    pub(crate) fn data_with_compression(
+        &mut self,
+        image_height: u32,
+        compression: &ImageCompression,
+    ) -> anyhow::Result<CompressedDataResult> {
+        let data = match compression {
+            ImageCompression::Rle => return Ok(self.compressed_data(image_height)?),
+            ImageCompression::RawData => self.data.clone(),
+            ImageCompression::ZipWithoutPrediction | ImageCompression::ZipWithPrediction => {
+                let width = self.data.len() / image_height.max(1) as usize;
+                zip::encoded(&self.data, width, compression.uses_prediction())?
+            }
+        };
+        Ok(CompressedDataResult {
+            data,
+            compression: compression.clone(),
+        })
+    }
+
     /// Returns the channel data encoded with line lengths
     /// for the RLE compression.
-    fn rle_encoded_data(&self, image_height: u32) -> anyhow::Result<Vec<u8>> {
+    fn rle_encoded_data(&self, image_height: u32) -> error::Result<Vec<u8>> {
+        #[cfg(test)]
+        RLE_ENCODED_DATA_COUNT.with(|count| count.set(count.get() + 1));
         let result = self.rle_encoded_components(image_height)?;
         let mut output = result.line_lengths.clone();
         output.extend(&result.data);
@@ -83,29 +135,20 @@ impl ColorChannel {
     }
 
     /// Returns the line lengths and image data for the RLE compression of the channel.
-    pub fn rle_encoded_components(&self, image_height: u32) -> anyhow::Result<RleComponents> {
+    pub fn rle_encoded_components(&self, image_height: u32) -> error::Result<RleComponents> {
         if image_height == 0 {
-            anyhow::bail!(WriteError::InvalidImage)
+            return Err(WriteError::InvalidImage.into());
         }
 
-        let bytes_per_row = self.data.len() as u32 / image_height;
-        let mut line_lengths = Vec::new();
+        let mut line_lengths_stream = FileStreamWriter::new();
         let mut encoded_data = Vec::new();
 
-        for y in 0..image_height {
-            let start = (y * bytes_per_row) as usize;
-            let end = start + bytes_per_row as usize;
-            let row_data = &self.data[start..end];
-            let mut encoded_row = rle::encoded(row_data);
-            line_lengths.push(encoded_row.len());
+        for row in self.rle_rows(image_height) {
+            let (line_length, mut encoded_row) = row?;
+            line_lengths_stream.write_be(&line_length)?;
             encoded_data.append(&mut encoded_row);
         }
 
-        let mut line_lengths_stream = FileStreamWriter::new();
-        for line_length in line_lengths {
-            line_lengths_stream.write_be(&(line_length as u16))?;
-        }
-
         let result = RleComponents {
             line_lengths: line_lengths_stream.data().to_vec(),
             data: encoded_data,
@@ -114,7 +157,50 @@ impl ColorChannel {
     }
 }
 
+// MARK: Streaming
+
+impl ColorChannel {
+    /// Returns an iterator that RLE encodes the channel one row at a time,
+    /// yielding the encoded length of each row along with its data. This
+    /// avoids holding the whole encoded channel in memory. Rows longer
+    /// than `u16::MAX` once encoded can’t be stored with RLE compression,
+    /// and are returned as `WriteError::RleRowOverflow`.
+    pub fn rle_rows(
+        &self,
+        image_height: u32,
+    ) -> impl Iterator<Item = error::Result<(u16, Vec<u8>)>> + '_ {
+        let bytes_per_row = if image_height == 0 {
+            0
+        } else {
+            self.data.len() / image_height as usize
+        };
+
+        (0..image_height as usize).map(move |y| {
+            let start = y * bytes_per_row;
+            let end = start + bytes_per_row;
+            let encoded_row = rle::encoded(&self.data[start..end]);
+            let line_length =
+                u16::try_from(encoded_row.len()).map_err(|_| WriteError::RleRowOverflow)?;
+            Ok((line_length, encoded_row))
+        })
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of times channel data has been RLE encoded on this thread.
+    pub(crate) static RLE_ENCODED_DATA_COUNT: std::cell::Cell<usize> =
+        const { std::cell::Cell::new(0) };
+}
+
+/// Returns whether an error is caused by an RLE encoded row being
+/// too long to store its length.
+pub(crate) fn is_rle_row_overflow(error: &Error) -> bool {
+    matches!(error, Error::Write(WriteError::RleRowOverflow))
+}
+
 /// Represents the components of RLE encoded data.
+#[derive(Debug)]
 pub struct RleComponents {
     /// The line lengths for the data.
     pub line_lengths: Vec<u8>,
@@ -176,6 +262,81 @@ mod tests {
         assert_eq!(data[9], 0x20);
     }
 
+    #[test]
+    fn encoded_data_16_bit() {
+        // Three pixels wide and two rows high, so each row is six bytes.
+        let mut channel = ColorChannel::from_u16_samples(
+            ColorChannelType::Red,
+            &[0x1234, 0x1234, 0x1234, 0xffff, 0xffff, 0xffff],
+        );
+        assert_eq!(channel.data_length, 12);
+
+        let result = channel.compressed_data(2).unwrap();
+        assert_eq!(result.compression, ImageCompression::Rle);
+
+        let expected_data = [
+            0x00, 0x07, // First line length
+            0x00, 0x02, // Second line length
+            0x05, 0x12, 0x34, 0x12, 0x34, 0x12, 0x34, // First line data
+            0xfb, 0xff, // Second line data, repeating across the samples
+        ];
+        assert_eq!(result.data, expected_data);
+    }
+
+    #[test]
+    fn rle_rows() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("tests/resources/clouds-raw-red.data");
+        let raw_data = std::fs::read(&path).unwrap();
+
+        let mut channel = ColorChannel::new(ColorChannelType::Red, raw_data.len());
+        channel.data = raw_data;
+
+        let mut line_lengths = Vec::new();
+        let mut data = Vec::new();
+        for row in channel.rle_rows(4) {
+            let (line_length, mut row) = row.unwrap();
+            assert_eq!(line_length as usize, row.len());
+            line_lengths.extend(line_length.to_be_bytes());
+            data.append(&mut row);
+        }
+
+        let components = channel.rle_encoded_components(4).unwrap();
+        assert_eq!(line_lengths, components.line_lengths);
+        assert_eq!(data, components.data);
+    }
+
+    #[test]
+    fn rle_row_overflow() {
+        // A single incompressible row, which is longer than the
+        // original data once encoded.
+        let width = 66_000;
+        let mut channel = ColorChannel::new(ColorChannelType::Red, width);
+        channel.data = (0..width).map(|index| (index % 251) as u8).collect();
+
+        let error = channel.rle_rows(1).next().unwrap().unwrap_err();
+        assert!(is_rle_row_overflow(&error));
+        let error = channel.rle_encoded_components(1).unwrap_err();
+        assert!(is_rle_row_overflow(&error));
+
+        let result = channel.compressed_data(1).unwrap();
+        assert_eq!(result.compression, ImageCompression::RawData);
+        assert_eq!(result.data, channel.data);
+    }
+
+    #[test]
+    fn cached_compressed_data() {
+        let mut channel = ColorChannel::new(ColorChannelType::Red, 4);
+        channel.data = vec![0xfb, 0xe5, 0x42, 0x20];
+
+        let count = RLE_ENCODED_DATA_COUNT.with(|count| count.get());
+        let first_result = channel.compressed_data(2).unwrap();
+        let second_result = channel.compressed_data(2).unwrap();
+
+        assert_eq!(first_result.data, second_result.data);
+        assert_eq!(RLE_ENCODED_DATA_COUNT.with(|count| count.get()), count + 1);
+    }
+
     #[test]
     fn large_encoded_data() {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -198,9 +359,9 @@ mod tests {
 
         // Line lengths
         assert_eq!(data[0..=1], [0x00, 0x22]);
-        assert_eq!(data[2..=3], [0x00, 0x31]);
-        assert_eq!(data[4..=5], [0x00, 0x2D]);
-        assert_eq!(data[6..=7], [0x00, 0x32]);
+        assert_eq!(data[2..=3], [0x00, 0x2F]);
+        assert_eq!(data[4..=5], [0x00, 0x2C]);
+        assert_eq!(data[6..=7], [0x00, 0x30]);
 
         // Top row
         // (-(-5)+1) = 6 bytes of 0x73
@@ -281,94 +442,92 @@ mod tests {
         assert_eq!(data[44], 0xF6);
         assert_eq!(data[45], 0x42);
 
-        // (1)+1 = 2 bytes of discrete data
-        assert_eq!(data[46], 0x01);
+        // (-(-1)+1) = 2 bytes of 0x24, which is a run because it follows one
+        assert_eq!(data[46], 0xFF);
         assert_eq!(data[47], 0x24);
-        assert_eq!(data[48], 0x24);
 
         // (-(-2)+1) = 3 bytes of 0x42
-        assert_eq!(data[49], 0xFE);
-        assert_eq!(data[50], 0x42);
+        assert_eq!(data[48], 0xFE);
+        assert_eq!(data[49], 0x42);
 
         // (-(-2)+1) = 3 bytes of 0x24
-        assert_eq!(data[51], 0xFE);
-        assert_eq!(data[52], 0x24);
+        assert_eq!(data[50], 0xFE);
+        assert_eq!(data[51], 0x24);
 
         // (-(-3)+1) = 4 bytes of 0x42
-        assert_eq!(data[53], 0xFD);
-        assert_eq!(data[54], 0x42);
+        assert_eq!(data[52], 0xFD);
+        assert_eq!(data[53], 0x42);
 
         // (-(-5)+1) = 6 bytes of 0x24
-        assert_eq!(data[55], 0xFB);
-        assert_eq!(data[56], 0x24);
+        assert_eq!(data[54], 0xFB);
+        assert_eq!(data[55], 0x24);
 
         // (-(-4)+1) = 5 bytes of 0x42
-        assert_eq!(data[57], 0xFC);
-        assert_eq!(data[58], 0x42);
+        assert_eq!(data[56], 0xFC);
+        assert_eq!(data[57], 0x42);
 
         // (-(-3)+1) = 4 bytes of 0x24
-        assert_eq!(data[59], 0xFD);
-        assert_eq!(data[60], 0x24);
+        assert_eq!(data[58], 0xFD);
+        assert_eq!(data[59], 0x24);
 
-        // (1)+1 = 2 bytes of discrete data
-        assert_eq!(data[61], 0x01);
-        assert_eq!(data[62], 0x42);
-        assert_eq!(data[63], 0x42);
+        // (-(-1)+1) = 2 bytes of 0x42
+        assert_eq!(data[60], 0xFF);
+        assert_eq!(data[61], 0x42);
 
         // (-(-2)+1) = 3 bytes of 0x24
-        assert_eq!(data[64], 0xFE);
-        assert_eq!(data[65], 0x24);
+        assert_eq!(data[62], 0xFE);
+        assert_eq!(data[63], 0x24);
 
         // (-(-5)+1) = 6 bytes of 0x42
-        assert_eq!(data[66], 0xFB);
-        assert_eq!(data[67], 0x42);
+        assert_eq!(data[64], 0xFB);
+        assert_eq!(data[65], 0x42);
 
         // Long repeat of 315 coming up…
         // (-(-127)+1) = 128 bytes of 0x24
-        assert_eq!(data[68], 0x81);
-        assert_eq!(data[69], 0x24);
+        assert_eq!(data[66], 0x81);
+        assert_eq!(data[67], 0x24);
 
         // (-(-127)+1) = 128 bytes of 0x24
-        assert_eq!(data[70], 0x81);
-        assert_eq!(data[71], 0x24);
+        assert_eq!(data[68], 0x81);
+        assert_eq!(data[69], 0x24);
 
         // (-(-58)+1) = 59 bytes of 0x24
-        assert_eq!(data[72], 0xC6);
-        assert_eq!(data[73], 0x24);
+        assert_eq!(data[70], 0xC6);
+        assert_eq!(data[71], 0x24);
 
         // (-(-4)+1) = 5 bytes of 0xFC
-        assert_eq!(data[74], 0xFC);
-        assert_eq!(data[75], 0xFC);
+        assert_eq!(data[72], 0xFC);
+        assert_eq!(data[73], 0xFC);
 
         // Long repeat of 129 coming up…
         // (-(-127)+1) = 128 bytes of 0x24
-        assert_eq!(data[76], 0x81);
-        assert_eq!(data[77], 0x24);
+        assert_eq!(data[74], 0x81);
+        assert_eq!(data[75], 0x24);
 
         // (1)+1 = 2 bytes of discrete data
-        assert_eq!(data[78], 0x01);
-        assert_eq!(data[79], 0x24);
-        assert_eq!(data[80], 0x42);
+        assert_eq!(data[76], 0x01);
+        assert_eq!(data[77], 0x24);
+        assert_eq!(data[78], 0x42);
 
         // (-(-6)+1) = 7 bytes of 0x24
-        assert_eq!(data[81], 0xFA);
-        assert_eq!(data[82], 0x24);
+        assert_eq!(data[79], 0xFA);
+        assert_eq!(data[80], 0x24);
 
         // (-(-3)+1) = 4 bytes of 0x42
-        assert_eq!(data[83], 0xFD);
-        assert_eq!(data[84], 0x42);
+        assert_eq!(data[81], 0xFD);
+        assert_eq!(data[82], 0x42);
 
         // (-(-13)+1) = 14 bytes of 0x24
-        assert_eq!(data[85], 0xF3);
-        assert_eq!(data[86], 0x24);
+        assert_eq!(data[83], 0xF3);
+        assert_eq!(data[84], 0x24);
 
         // (-(-9)+1) = 10 bytes of 0x73
-        assert_eq!(data[87], 0xF7);
-        assert_eq!(data[88], 0x73);
+        assert_eq!(data[85], 0xF7);
+        assert_eq!(data[86], 0x73);
 
         // (0)+1 = 1 byte of discrete data
-        assert_eq!(data[89], 0x00);
-        assert_eq!(data[90], 0x42);
+        assert_eq!(data[87], 0x00);
+        assert_eq!(data[88], 0x42);
 
         // Not comparing the third and fourth rows in detail.
 
@@ -1,5 +1,9 @@
-/// The different types of colour channel.
-#[derive(Debug, Clone, PartialEq)]
+use std::cmp::Ordering;
+use std::fmt;
+
+/// The different types of colour channel. These are ordered the way
+/// Photoshop stores them, with the alpha channel first.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ColorChannelType {
     /// The red channel.
     Red,
@@ -45,4 +49,80 @@ impl ColorChannelType {
             ColorChannelType::Unknown => 9999,
         }
     }
+
+    /// Returns the position of the channel type in the order
+    /// Photoshop stores channels.
+    fn storage_index(&self) -> u8 {
+        match self {
+            ColorChannelType::Alpha => 0,
+            ColorChannelType::Red => 1,
+            ColorChannelType::Green => 2,
+            ColorChannelType::Blue => 3,
+            ColorChannelType::UserSuppliedLayerMask => 4,
+            ColorChannelType::RealUserSuppliedLayerMask => 5,
+            ColorChannelType::Unknown => 6,
+        }
+    }
+}
+
+impl Ord for ColorChannelType {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.storage_index().cmp(&other.storage_index())
+    }
+}
+
+impl PartialOrd for ColorChannelType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for ColorChannelType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorChannelType::Red => "Red",
+            ColorChannelType::Green => "Green",
+            ColorChannelType::Blue => "Blue",
+            ColorChannelType::Alpha => "Alpha",
+            ColorChannelType::UserSuppliedLayerMask => "User Supplied Layer Mask",
+            ColorChannelType::RealUserSuppliedLayerMask => "Real User Supplied Layer Mask",
+            ColorChannelType::Unknown => "Unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_order() {
+        let mut channel_types = vec![
+            ColorChannelType::Blue,
+            ColorChannelType::UserSuppliedLayerMask,
+            ColorChannelType::Red,
+            ColorChannelType::Alpha,
+            ColorChannelType::Green,
+        ];
+
+        channel_types.sort();
+
+        assert_eq!(
+            channel_types,
+            vec![
+                ColorChannelType::Alpha,
+                ColorChannelType::Red,
+                ColorChannelType::Green,
+                ColorChannelType::Blue,
+                ColorChannelType::UserSuppliedLayerMask,
+            ]
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(ColorChannelType::Red.to_string(), "Red");
+        assert_eq!(ColorChannelType::Alpha.to_string(), "Alpha");
+    }
 }
@@ -35,6 +35,20 @@ impl ColorMode {
         }
     }
 
+    /// Returns the number of colour channels the mode naturally has,
+    /// without any alpha channels. Multichannel documents have no
+    /// natural count, as it depends on their channels.
+    pub fn number_of_channels(&self) -> Option<u16> {
+        match self {
+            ColorMode::Bitmap | ColorMode::Grayscale | ColorMode::Indexed | ColorMode::Duotone => {
+                Some(1)
+            }
+            ColorMode::Rgb | ColorMode::Lab => Some(3),
+            ColorMode::Cmyk => Some(4),
+            ColorMode::Multichannel => None,
+        }
+    }
+
     /// Returns the raw value for the colour mode.
     pub fn raw_value(&self) -> i16 {
         match self {
@@ -1,4 +1,9 @@
+use graphics::Color;
+
+use crate::image;
+
 /// Colour spaces used in a Photoshop colour swatch.
+#[derive(Debug, Clone, PartialEq)]
 pub enum ColorSpace {
     /// The RGB colour space.
     Rgb,
@@ -36,3 +41,285 @@ impl ColorSpace {
         }
     }
 }
+
+// MARK: Conversion
+
+impl ColorSpace {
+    /// Returns the four 16-bit colour components Photoshop uses to
+    /// store a colour in this colour space, in big endian order.
+    /// The alpha of the colour is ignored.
+    pub fn encode(&self, color: &Color) -> [u8; 8] {
+        let red = color.red as f64 / 255.0;
+        let green = color.green as f64 / 255.0;
+        let blue = color.blue as f64 / 255.0;
+
+        let components: [u16; 4] = match self {
+            ColorSpace::Rgb => [
+                color.red as u16 * 257,
+                color.green as u16 * 257,
+                color.blue as u16 * 257,
+                0,
+            ],
+            ColorSpace::Hsb => {
+                let (hue, saturation, brightness) = hsb_from_rgb(red, green, blue);
+                [
+                    component(hue / 360.0),
+                    component(saturation),
+                    component(brightness),
+                    0,
+                ]
+            }
+            ColorSpace::Cmyk => {
+                // Zero represents 100% ink.
+                let key = 1.0 - red.max(green).max(blue);
+                let ink = |value: f64| {
+                    if key >= 1.0 {
+                        0.0
+                    } else {
+                        (1.0 - value - key) / (1.0 - key)
+                    }
+                };
+                [
+                    component(1.0 - ink(red)),
+                    component(1.0 - ink(green)),
+                    component(1.0 - ink(blue)),
+                    component(1.0 - key),
+                ]
+            }
+            ColorSpace::Lab => {
+                let (lightness, a, b) = lab_from_rgb(red, green, blue);
+                [
+                    (lightness * 100.0).round() as u16,
+                    (a * 100.0).round() as i16 as u16,
+                    (b * 100.0).round() as i16 as u16,
+                    0,
+                ]
+            }
+            ColorSpace::Grayscale => {
+                // Stored as the amount of black ink, from 0 to 10,000.
+                let gray = image::luminance(color.red, color.green, color.blue);
+                let ink = (255 - gray) as f64 / 255.0;
+                [(ink * 10_000.0).round() as u16, 0, 0, 0]
+            }
+        };
+
+        let mut output = [0u8; 8];
+        for (index, component) in components.iter().enumerate() {
+            output[index * 2..index * 2 + 2].copy_from_slice(&component.to_be_bytes());
+        }
+        output
+    }
+
+    /// Returns the colour for the four 16-bit colour components
+    /// Photoshop uses to store a colour in this colour space.
+    pub fn decode(&self, data: &[u8; 8]) -> Color {
+        let components: [u16; 4] = [
+            u16::from_be_bytes([data[0], data[1]]),
+            u16::from_be_bytes([data[2], data[3]]),
+            u16::from_be_bytes([data[4], data[5]]),
+            u16::from_be_bytes([data[6], data[7]]),
+        ];
+        let fraction = |value: u16| value as f64 / u16::MAX as f64;
+
+        let (red, green, blue) = match self {
+            ColorSpace::Rgb => (
+                fraction(components[0]),
+                fraction(components[1]),
+                fraction(components[2]),
+            ),
+            ColorSpace::Hsb => rgb_from_hsb(
+                fraction(components[0]) * 360.0,
+                fraction(components[1]),
+                fraction(components[2]),
+            ),
+            ColorSpace::Cmyk => {
+                // Zero represents 100% ink, so the components are the inverse of the ink.
+                let key = 1.0 - fraction(components[3]);
+                let value = |component: u16| fraction(component) * (1.0 - key);
+                (
+                    value(components[0]),
+                    value(components[1]),
+                    value(components[2]),
+                )
+            }
+            ColorSpace::Lab => rgb_from_lab(
+                components[0] as f64 / 100.0,
+                components[1] as i16 as f64 / 100.0,
+                components[2] as i16 as f64 / 100.0,
+            ),
+            ColorSpace::Grayscale => {
+                let gray = 1.0 - components[0] as f64 / 10_000.0;
+                (gray, gray, gray)
+            }
+        };
+
+        Color {
+            red: byte(red),
+            green: byte(green),
+            blue: byte(blue),
+            alpha: u8::MAX,
+        }
+    }
+}
+
+/// Returns a 16-bit component for a value from 0 to 1.
+fn component(value: f64) -> u16 {
+    (value.clamp(0.0, 1.0) * u16::MAX as f64).round() as u16
+}
+
+/// Returns an 8-bit value for a value from 0 to 1.
+fn byte(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * u8::MAX as f64).round() as u8
+}
+
+/// Returns the hue (in degrees), saturation and brightness for an RGB colour.
+fn hsb_from_rgb(red: f64, green: f64, blue: f64) -> (f64, f64, f64) {
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == red {
+        60.0 * ((green - blue) / delta).rem_euclid(6.0)
+    } else if max == green {
+        60.0 * ((blue - red) / delta + 2.0)
+    } else {
+        60.0 * ((red - green) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    (hue, saturation, max)
+}
+
+/// Returns the RGB colour for a hue (in degrees), saturation and brightness.
+fn rgb_from_hsb(hue: f64, saturation: f64, brightness: f64) -> (f64, f64, f64) {
+    let chroma = brightness * saturation;
+    let sector = (hue / 60.0).rem_euclid(6.0);
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (red, green, blue) = match sector as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let offset = brightness - chroma;
+    (red + offset, green + offset, blue + offset)
+}
+
+/// The D65 reference white used for Lab conversions.
+const REFERENCE_WHITE: (f64, f64, f64) = (0.95047, 1.0, 1.08883);
+
+/// Returns the linear value for an sRGB component from 0 to 1.
+pub(crate) fn linear_from_srgb(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Returns the sRGB component for a linear value from 0 to 1.
+pub(crate) fn srgb_from_linear(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Returns the Lab values for an sRGB colour.
+pub(crate) fn lab_from_rgb(red: f64, green: f64, blue: f64) -> (f64, f64, f64) {
+    let (red, green, blue) = (
+        linear_from_srgb(red),
+        linear_from_srgb(green),
+        linear_from_srgb(blue),
+    );
+
+    let x = 0.4124 * red + 0.3576 * green + 0.1805 * blue;
+    let y = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+    let z = 0.0193 * red + 0.1192 * green + 0.9505 * blue;
+
+    let f = |value: f64| {
+        if value > 0.008856 {
+            value.cbrt()
+        } else {
+            7.787 * value + 16.0 / 116.0
+        }
+    };
+    let fx = f(x / REFERENCE_WHITE.0);
+    let fy = f(y / REFERENCE_WHITE.1);
+    let fz = f(z / REFERENCE_WHITE.2);
+
+    (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+/// Returns the sRGB colour for Lab values.
+pub(crate) fn rgb_from_lab(lightness: f64, a: f64, b: f64) -> (f64, f64, f64) {
+    let fy = (lightness + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+
+    let inverse = |value: f64| {
+        let cubed = value.powi(3);
+        if cubed > 0.008856 {
+            cubed
+        } else {
+            (value - 16.0 / 116.0) / 7.787
+        }
+    };
+    let x = inverse(fx) * REFERENCE_WHITE.0;
+    let y = inverse(fy) * REFERENCE_WHITE.1;
+    let z = inverse(fz) * REFERENCE_WHITE.2;
+
+    let red = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+    let green = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+    let blue = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+
+    (
+        srgb_from_linear(red),
+        srgb_from_linear(green),
+        srgb_from_linear(blue),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use graphics::Color;
+
+    use super::*;
+
+    #[test]
+    fn encode_rgb() {
+        let color = Color::from_rgb_u32(0x24a4ee);
+        let data = ColorSpace::Rgb.encode(&color);
+
+        assert_eq!(data, [0x24, 0x24, 0xa4, 0xa4, 0xee, 0xee, 0x00, 0x00]);
+        assert_eq!(ColorSpace::Rgb.decode(&data), color);
+    }
+
+    #[test]
+    fn round_trip() {
+        let color = Color::from_rgb_u32(0x24a4ee);
+        for color_space in [
+            ColorSpace::Rgb,
+            ColorSpace::Hsb,
+            ColorSpace::Cmyk,
+            ColorSpace::Lab,
+        ] {
+            let data = color_space.encode(&color);
+            assert_eq!(color_space.decode(&data), color, "{:?}", color_space);
+        }
+    }
+
+    #[test]
+    fn encode_grayscale() {
+        let data = ColorSpace::Grayscale.encode(&Color::BLACK);
+        assert_eq!(data[0..2], [0x27, 0x10]);
+
+        let data = ColorSpace::Grayscale.encode(&Color::WHITE);
+        assert_eq!(data[0..2], [0x00, 0x00]);
+    }
+}
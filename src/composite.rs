@@ -0,0 +1,612 @@
+use graphics::{Color, Image, Point, Rect};
+
+use crate::blend_mode::BlendMode;
+use crate::color_space;
+use crate::document::BlendColorSpace;
+use crate::image;
+use crate::layer::{Layer, LayerType};
+
+/// The amount added to the Lab a and b components to make them positive.
+const LAB_OFFSET: f64 = 128.0;
+
+/// The range of the Lab a and b components.
+const LAB_RANGE: f64 = 255.0;
+
+/// A colour with components from 0 to 1, without premultiplied alpha.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Pixel {
+    red: f64,
+    green: f64,
+    blue: f64,
+    alpha: f64,
+}
+
+// MARK: Compositing
+
+/// Composites the visible layers, from the bottom up, into an image
+/// covering the bounds, blending them in a colour space. Anything
+/// outside of the bounds is clipped.
+pub(crate) fn composited_image(
+    layers: &[Layer],
+    bounds: Rect<i32>,
+    color_space: BlendColorSpace,
+) -> Image {
+    let mut image = Image::empty(bounds.size.into());
+    for layer in layers.iter() {
+        composite_layer(&mut image, bounds, layer, color_space);
+    }
+    image
+}
+
+/// Returns the image composited onto an opaque background colour.
+pub(crate) fn flattened_image(image: &Image, background: &Color) -> Image {
+    let bounds = Rect {
+        origin: Point::zero(),
+        size: image.size.into(),
+    };
+    let background = Color {
+        alpha: u8::MAX,
+        ..*background
+    };
+    let mut output = Image::color(&background, image.size);
+    blend_image(
+        &mut output,
+        bounds,
+        image,
+        bounds,
+        &BlendMode::Normal,
+        u8::MAX,
+        BlendColorSpace::Srgb,
+    );
+    output
+}
+
+/// Composites a layer onto the backdrop, which covers the bounds.
+fn composite_layer(
+    backdrop: &mut Image,
+    bounds: Rect<i32>,
+    layer: &Layer,
+    color_space: BlendColorSpace,
+) {
+    if layer.is_hidden {
+        return;
+    }
+
+    match &layer.layer_type {
+        LayerType::Group(info) if layer.blend_mode == BlendMode::PassThrough => {
+            // The child layers are blended straight onto the backdrop,
+            // and the group’s opacity fades between the two.
+            let mut group_image = backdrop.clone();
+            for child in info.layers.iter() {
+                composite_layer(&mut group_image, bounds, child, color_space);
+            }
+            mix_image(backdrop, &group_image, layer.opacity);
+        }
+        LayerType::Group(info) => {
+            // The child layers are composited in isolation, then
+            // blended onto the backdrop as one.
+            let group_image = composited_image(&info.layers, bounds, color_space);
+            blend_image(
+                backdrop,
+                bounds,
+                &group_image,
+                bounds,
+                &layer.blend_mode,
+                layer.opacity,
+                color_space,
+            );
+        }
+        LayerType::Image => {
+            if let Some(image) = &layer.image {
+                blend_image(
+                    backdrop,
+                    bounds,
+                    image,
+                    layer.bounds,
+                    &layer.blend_mode,
+                    layer.opacity,
+                    color_space,
+                );
+            }
+        }
+        // Sourced layers only provide their channels when they’re written.
+        LayerType::Sourced(_) => {}
+    }
+}
+
+/// Blends the source image onto the backdrop where the two overlap.
+fn blend_image(
+    backdrop: &mut Image,
+    backdrop_bounds: Rect<i32>,
+    source: &Image,
+    source_bounds: Rect<i32>,
+    blend_mode: &BlendMode,
+    opacity: u8,
+    color_space: BlendColorSpace,
+) {
+    let opacity = opacity as f64 / 255.0;
+    let min_x = backdrop_bounds.min_x().max(source_bounds.min_x());
+    let min_y = backdrop_bounds.min_y().max(source_bounds.min_y());
+    let max_x = (backdrop_bounds.min_x() + backdrop.size.width as i32)
+        .min(source_bounds.min_x() + source.size.width as i32);
+    let max_y = (backdrop_bounds.min_y() + backdrop.size.height as i32)
+        .min(source_bounds.min_y() + source.size.height as i32);
+
+    for y_position in min_y..max_y {
+        for x_position in min_x..max_x {
+            let source_index = pixel_index(source, source_bounds, x_position, y_position);
+            let backdrop_index = pixel_index(backdrop, backdrop_bounds, x_position, y_position);
+
+            let mut source_pixel = read_pixel(source, source_index);
+            source_pixel.alpha *= opacity;
+            // Dissolve keeps or drops whole pixels, using the alpha as
+            // the chance of keeping each one.
+            if *blend_mode == BlendMode::Dissolve {
+                let is_kept = dissolve_threshold(x_position, y_position) < source_pixel.alpha;
+                source_pixel.alpha = if is_kept { 1.0 } else { 0.0 };
+            }
+            let backdrop_pixel = read_pixel(backdrop, backdrop_index);
+
+            let pixel = blend_pixel(backdrop_pixel, source_pixel, blend_mode, color_space);
+            write_pixel(backdrop, backdrop_index, pixel);
+        }
+    }
+}
+
+/// Mixes the backdrop towards the source image, which has the same size,
+/// by the opacity. The colours are mixed with premultiplied alpha, so
+/// that transparent pixels don’t darken the result.
+fn mix_image(backdrop: &mut Image, source: &Image, opacity: u8) {
+    let opacity = opacity as f64 / 255.0;
+    let bytes_per_pixel = image::bytes_per_pixel(backdrop);
+    for index in (0..backdrop.data.len()).step_by(bytes_per_pixel) {
+        let backdrop_pixel = read_pixel(backdrop, index);
+        let source_pixel = read_pixel(source, index);
+        let backdrop_weight = backdrop_pixel.alpha * (1.0 - opacity);
+        let source_weight = source_pixel.alpha * opacity;
+        let alpha = backdrop_weight + source_weight;
+        if alpha <= 0.0 {
+            write_pixel(backdrop, index, Pixel::default());
+            continue;
+        }
+        let mix = |backdrop: f64, source: f64| {
+            (backdrop * backdrop_weight + source * source_weight) / alpha
+        };
+        let pixel = Pixel {
+            red: mix(backdrop_pixel.red, source_pixel.red),
+            green: mix(backdrop_pixel.green, source_pixel.green),
+            blue: mix(backdrop_pixel.blue, source_pixel.blue),
+            alpha,
+        };
+        write_pixel(backdrop, index, pixel);
+    }
+}
+
+/// Returns a number from 0 to 1 that is the same every time for a
+/// position, but that looks random from one position to the next.
+fn dissolve_threshold(x_position: i32, y_position: i32) -> f64 {
+    let mut hash = (x_position as u32 as u64) << 32 | y_position as u32 as u64;
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^= hash >> 33;
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Returns the index of the pixel at a position, for an image
+/// placed at the bounds.
+fn pixel_index(image: &Image, bounds: Rect<i32>, x_position: i32, y_position: i32) -> usize {
+    let x_position = (x_position - bounds.min_x()) as usize;
+    let y_position = (y_position - bounds.min_y()) as usize;
+    y_position * image.bytes_per_row as usize + x_position * image::bytes_per_pixel(image)
+}
+
+/// Reads the pixel at an index, expanding grey to red, green and blue.
+fn read_pixel(image: &Image, index: usize) -> Pixel {
+    let components = image::rgba_components(image, index, image::bytes_per_pixel(image));
+    let component = |index: usize| components[index] as f64 / 255.0;
+    Pixel {
+        red: component(0),
+        green: component(1),
+        blue: component(2),
+        alpha: component(3),
+    }
+}
+
+/// Writes a pixel at an index, in RGBA order. The backdrop is always RGBA.
+fn write_pixel(image: &mut Image, index: usize, pixel: Pixel) {
+    let component = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    image.data[index] = component(pixel.red);
+    image.data[index + 1] = component(pixel.green);
+    image.data[index + 2] = component(pixel.blue);
+    image.data[index + 3] = component(pixel.alpha);
+}
+
+// MARK: Blending
+
+/// Blends a source pixel onto a backdrop pixel, as described in
+/// the W3C compositing specification. The colours are mixed in the
+/// colour space, and the result is composited in sRGB.
+fn blend_pixel(
+    backdrop: Pixel,
+    source: Pixel,
+    blend_mode: &BlendMode,
+    color_space: BlendColorSpace,
+) -> Pixel {
+    if source.alpha <= 0.0 {
+        return backdrop;
+    }
+
+    let backdrop_color = [backdrop.red, backdrop.green, backdrop.blue];
+    let source_color = [source.red, source.green, source.blue];
+    let mixed_color = srgb_components(
+        blended_color(
+            blend_components(backdrop_color, color_space),
+            blend_components(source_color, color_space),
+            blend_mode,
+        ),
+        color_space,
+    );
+
+    let alpha = source.alpha + backdrop.alpha * (1.0 - source.alpha);
+    let mut color = [0.0; 3];
+    for index in 0..3 {
+        // Where the backdrop is transparent, the source shows as it is.
+        let result =
+            (1.0 - backdrop.alpha) * source_color[index] + backdrop.alpha * mixed_color[index];
+        color[index] = (source.alpha * result
+            + backdrop.alpha * backdrop_color[index] * (1.0 - source.alpha))
+            / alpha;
+    }
+
+    Pixel {
+        red: color[0],
+        green: color[1],
+        blue: color[2],
+        alpha,
+    }
+}
+
+/// Returns the components of an sRGB colour in the colour space it’s
+/// blended in, each from 0 to 1.
+fn blend_components(color: [f64; 3], color_space: BlendColorSpace) -> [f64; 3] {
+    match color_space {
+        BlendColorSpace::Srgb => color,
+        BlendColorSpace::Linear => color.map(color_space::linear_from_srgb),
+        BlendColorSpace::Lab => {
+            let (lightness, a, b) = color_space::lab_from_rgb(color[0], color[1], color[2]);
+            [
+                lightness / 100.0,
+                (a + LAB_OFFSET) / LAB_RANGE,
+                (b + LAB_OFFSET) / LAB_RANGE,
+            ]
+        }
+    }
+}
+
+/// Returns the sRGB colour for components in the colour space it was
+/// blended in.
+fn srgb_components(components: [f64; 3], color_space: BlendColorSpace) -> [f64; 3] {
+    match color_space {
+        BlendColorSpace::Srgb => components,
+        BlendColorSpace::Linear => components.map(color_space::srgb_from_linear),
+        BlendColorSpace::Lab => {
+            let (red, green, blue) = color_space::rgb_from_lab(
+                components[0] * 100.0,
+                components[1] * LAB_RANGE - LAB_OFFSET,
+                components[2] * LAB_RANGE - LAB_OFFSET,
+            );
+            [red, green, blue]
+        }
+    }
+}
+
+/// Returns the source colour mixed with the backdrop using the blend mode.
+fn blended_color(backdrop: [f64; 3], source: [f64; 3], blend_mode: &BlendMode) -> [f64; 3] {
+    match blend_mode {
+        BlendMode::Hue => set_luminosity(
+            set_saturation(source, saturation(backdrop)),
+            luminosity(backdrop),
+        ),
+        BlendMode::Saturation => set_luminosity(
+            set_saturation(backdrop, saturation(source)),
+            luminosity(backdrop),
+        ),
+        BlendMode::Color => set_luminosity(source, luminosity(backdrop)),
+        BlendMode::Luminosity => set_luminosity(backdrop, luminosity(source)),
+        BlendMode::DarkerColor => {
+            if luminosity(source) < luminosity(backdrop) {
+                source
+            } else {
+                backdrop
+            }
+        }
+        BlendMode::LighterColor => {
+            if luminosity(source) > luminosity(backdrop) {
+                source
+            } else {
+                backdrop
+            }
+        }
+        _ => [0, 1, 2].map(|index| blended_component(backdrop[index], source[index], blend_mode)),
+    }
+}
+
+/// Returns a single colour component mixed using a separable blend mode.
+/// Blend modes that aren’t supported yet fall back to normal.
+fn blended_component(backdrop: f64, source: f64, blend_mode: &BlendMode) -> f64 {
+    let screen = |backdrop: f64, source: f64| backdrop + source - backdrop * source;
+    let hard_light = |backdrop: f64, source: f64| {
+        if source <= 0.5 {
+            backdrop * 2.0 * source
+        } else {
+            screen(backdrop, 2.0 * source - 1.0)
+        }
+    };
+    let color_dodge = |backdrop: f64, source: f64| {
+        if backdrop <= 0.0 {
+            0.0
+        } else if source >= 1.0 {
+            1.0
+        } else {
+            (backdrop / (1.0 - source)).min(1.0)
+        }
+    };
+    let color_burn = |backdrop: f64, source: f64| {
+        if backdrop >= 1.0 {
+            1.0
+        } else if source <= 0.0 {
+            0.0
+        } else {
+            1.0 - ((1.0 - backdrop) / source).min(1.0)
+        }
+    };
+
+    match blend_mode {
+        BlendMode::Multiply => backdrop * source,
+        BlendMode::Screen => screen(backdrop, source),
+        BlendMode::Overlay => hard_light(source, backdrop),
+        BlendMode::Darken => backdrop.min(source),
+        BlendMode::Lighten => backdrop.max(source),
+        BlendMode::ColorDodge => color_dodge(backdrop, source),
+        BlendMode::ColorBurn => color_burn(backdrop, source),
+        BlendMode::LinearBurn => (backdrop + source - 1.0).max(0.0),
+        BlendMode::LinearDodge => (backdrop + source).min(1.0),
+        BlendMode::HardLight => hard_light(backdrop, source),
+        BlendMode::VividLight => {
+            if source <= 0.5 {
+                color_burn(backdrop, 2.0 * source)
+            } else {
+                color_dodge(backdrop, 2.0 * source - 1.0)
+            }
+        }
+        BlendMode::LinearLight => (backdrop + 2.0 * source - 1.0).clamp(0.0, 1.0),
+        BlendMode::PinLight => {
+            if source <= 0.5 {
+                backdrop.min(2.0 * source)
+            } else {
+                backdrop.max(2.0 * source - 1.0)
+            }
+        }
+        BlendMode::HardMix => {
+            if backdrop + source >= 1.0 {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        BlendMode::SoftLight => {
+            if source <= 0.5 {
+                backdrop - (1.0 - 2.0 * source) * backdrop * (1.0 - backdrop)
+            } else {
+                let d = if backdrop <= 0.25 {
+                    ((16.0 * backdrop - 12.0) * backdrop + 4.0) * backdrop
+                } else {
+                    backdrop.sqrt()
+                };
+                backdrop + (2.0 * source - 1.0) * (d - backdrop)
+            }
+        }
+        BlendMode::Difference => (backdrop - source).abs(),
+        BlendMode::Exclusion => backdrop + source - 2.0 * backdrop * source,
+        BlendMode::Subtract => (backdrop - source).max(0.0),
+        BlendMode::Divide => {
+            if source <= 0.0 {
+                if backdrop <= 0.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            } else {
+                (backdrop / source).min(1.0)
+            }
+        }
+        _ => source,
+    }
+}
+
+/// Returns the luminosity of a colour.
+fn luminosity(color: [f64; 3]) -> f64 {
+    0.3 * color[0] + 0.59 * color[1] + 0.11 * color[2]
+}
+
+/// Returns the colour with its luminosity changed, clipped to the valid range.
+fn set_luminosity(color: [f64; 3], luminosity_value: f64) -> [f64; 3] {
+    let difference = luminosity_value - luminosity(color);
+    let color = color.map(|component| component + difference);
+
+    let luminosity_value = luminosity(color);
+    let minimum = color[0].min(color[1]).min(color[2]);
+    let maximum = color[0].max(color[1]).max(color[2]);
+    color.map(|component| {
+        let mut component = component;
+        if minimum < 0.0 {
+            component = luminosity_value
+                + (component - luminosity_value) * luminosity_value / (luminosity_value - minimum);
+        }
+        if maximum > 1.0 {
+            component = luminosity_value
+                + (component - luminosity_value) * (1.0 - luminosity_value)
+                    / (maximum - luminosity_value);
+        }
+        component
+    })
+}
+
+/// Returns the saturation of a colour.
+fn saturation(color: [f64; 3]) -> f64 {
+    color[0].max(color[1]).max(color[2]) - color[0].min(color[1]).min(color[2])
+}
+
+/// Returns the colour with its saturation changed.
+fn set_saturation(color: [f64; 3], saturation_value: f64) -> [f64; 3] {
+    let minimum = color[0].min(color[1]).min(color[2]);
+    let maximum = color[0].max(color[1]).max(color[2]);
+    if maximum <= minimum {
+        return [0.0; 3];
+    }
+    color.map(|component| (component - minimum) * saturation_value / (maximum - minimum))
+}
+
+#[cfg(test)]
+mod tests {
+    use graphics::{Color, Size};
+
+    use super::*;
+
+    #[test]
+    fn multiply() {
+        let size = Size {
+            width: 2,
+            height: 1,
+        };
+        let bounds = Rect::new(0, 0, 2, 1);
+
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.image = Some(Image::color(&Color::from_rgb_u32(0x80ff40), size));
+        let mut layer_1 = Layer::new(Rect::new(1, 0, 2, 1));
+        layer_1.image = Some(Image::color(&Color::from_rgb_u32(0x808080), size));
+        layer_1.blend_mode = BlendMode::Multiply;
+
+        let result = composited_image(&[layer_0, layer_1], bounds, BlendColorSpace::Srgb);
+
+        // The bottom layer on its own, then multiplied. The rest
+        // of the top layer is clipped.
+        assert_eq!(
+            result.data,
+            vec![0x80, 0xff, 0x40, 0xff, 0x40, 0x80, 0x20, 0xff]
+        );
+    }
+
+    #[test]
+    fn multiply_in_lab() {
+        let size = Size {
+            width: 1,
+            height: 1,
+        };
+        let bounds = Rect::new(0, 0, 1, 1);
+
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.image = Some(Image::color(&Color::from_rgb_u32(0x80ff40), size));
+        let mut layer_1 = Layer::new(bounds);
+        layer_1.image = Some(Image::color(&Color::from_rgb_u32(0x808080), size));
+        layer_1.blend_mode = BlendMode::Multiply;
+        let layers = [layer_0, layer_1];
+
+        let srgb_result = composited_image(&layers, bounds, BlendColorSpace::Srgb);
+        let lab_result = composited_image(&layers, bounds, BlendColorSpace::Lab);
+
+        assert_eq!(srgb_result.data, vec![0x40, 0x80, 0x20, 0xff]);
+        assert_ne!(lab_result.data, srgb_result.data);
+        assert_eq!(lab_result.data[3], 0xff);
+    }
+
+    #[test]
+    fn linear_burn() {
+        let size = Size {
+            width: 1,
+            height: 1,
+        };
+        let bounds = Rect::new(0, 0, 1, 1);
+
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.image = Some(Image::color(&Color::from_rgb_u32(0xc08040), size));
+        let mut layer_1 = Layer::new(bounds);
+        layer_1.image = Some(Image::color(&Color::from_rgb_u32(0x80c0ff), size));
+        layer_1.blend_mode = BlendMode::LinearBurn;
+
+        let result = composited_image(&[layer_0, layer_1], bounds, BlendColorSpace::Srgb);
+
+        // The sum of the components, less one.
+        assert_eq!(result.data, vec![0x41, 0x41, 0x40, 0xff]);
+    }
+
+    #[test]
+    fn dissolve() {
+        let size = Size {
+            width: 16,
+            height: 16,
+        };
+        let bounds = Rect::new(0, 0, 16, 16);
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(Image::color(&Color::RED, size));
+        layer.blend_mode = BlendMode::Dissolve;
+        layer.opacity = 0x80;
+
+        let result = composited_image(&[layer], bounds, BlendColorSpace::Srgb);
+
+        // Every pixel is either opaque or clear, with about half of each.
+        let alphas: Vec<u8> = result.data.chunks_exact(4).map(|pixel| pixel[3]).collect();
+        assert!(alphas.iter().all(|&alpha| alpha == 0x00 || alpha == 0xff));
+        let opaque_count = alphas.iter().filter(|&&alpha| alpha == 0xff).count();
+        assert!((96..160).contains(&opaque_count));
+    }
+
+    #[test]
+    fn isolated_group() {
+        let size = Size {
+            width: 1,
+            height: 1,
+        };
+        let bounds = Rect::new(0, 0, 1, 1);
+        let mut background = Layer::new(bounds);
+        background.image = Some(Image::color(&Color::WHITE, size));
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.image = Some(Image::color(&Color::RED, size));
+        let mut layer_1 = Layer::new(bounds);
+        layer_1.image = Some(Image::color(&Color::BLUE, size));
+        let mut group = Layer::group(vec![layer_0, layer_1], true);
+        group.opacity = 0x80;
+
+        let result = composited_image(&[background, group.clone()], bounds, BlendColorSpace::Srgb);
+
+        // Blue covers red within the group, and only then is the group
+        // blended at half opacity. Blending each child at half opacity
+        // would leave some of the red showing.
+        assert_eq!(result.data, vec![0x7f, 0x7f, 0xff, 0xff]);
+
+        group.blend_mode = BlendMode::PassThrough;
+        let result = composited_image(&[group], bounds, BlendColorSpace::Srgb);
+
+        // Without isolation, the children fade onto the empty backdrop,
+        // keeping their colour.
+        assert_eq!(result.data, vec![0x00, 0x00, 0xff, 0x80]);
+    }
+
+    #[test]
+    fn hidden_layer() {
+        let size = Size {
+            width: 1,
+            height: 1,
+        };
+        let bounds = Rect::new(0, 0, 1, 1);
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(Image::color(&Color::RED, size));
+        layer.is_hidden = true;
+
+        let result = composited_image(&[layer], bounds, BlendColorSpace::Srgb);
+
+        assert_eq!(result.data, vec![0x00, 0x00, 0x00, 0x00]);
+    }
+}
@@ -1,3 +1,11 @@
+use crate::error::WriteError;
+
+/// Returns a length as the 32-bit value written before a section,
+/// or an error if the length is too large to fit.
+pub(crate) fn section_length(length: usize) -> anyhow::Result<u32> {
+    u32::try_from(length).map_err(|_| WriteError::SectionTooLarge(length).into())
+}
+
 /// Pads the data to be a multiple of a number of bytes.
 pub(crate) fn pad(data: &mut Vec<u8>, number_of_bytes: usize) {
     while data.len() % number_of_bytes != 0 {
@@ -0,0 +1,166 @@
+use file_stream::write::FileStreamWriter;
+
+use crate::endian::WriteBigEndian;
+
+/// An action descriptor, the structure Photoshop uses for layer effects
+/// and other settings that are stored as a list of keyed values.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Descriptor {
+    /// The name of the class, which is usually empty.
+    pub name: String,
+    /// The class identifier.
+    pub class_id: String,
+    /// The keyed values, in the order they’re written.
+    pub items: Vec<(String, DescriptorValue)>,
+}
+
+/// A value stored in a descriptor.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum DescriptorValue {
+    /// A nested descriptor.
+    Descriptor(Descriptor),
+    /// A double precision number.
+    Double(f64),
+    /// A number with a unit, such as `#Ang`, `#Pxl` or `#Prc`.
+    UnitFloat(&'static [u8; 4], f64),
+    /// An enumerated value, made of a type identifier and a value.
+    Enumerated(String, String),
+    /// A boolean.
+    Boolean(bool),
+    /// A 32-bit integer.
+    Integer(i32),
+    /// A Unicode string.
+    Text(String),
+    /// A list of values.
+    List(Vec<DescriptorValue>),
+}
+
+impl Descriptor {
+    /// Creates a new descriptor with an empty name and no items.
+    pub fn new(class_id: &str) -> Self {
+        Self {
+            name: String::new(),
+            class_id: class_id.to_string(),
+            items: Vec::new(),
+        }
+    }
+
+    /// Adds a keyed value to the descriptor, returning the descriptor
+    /// so that items can be chained.
+    pub fn with(mut self, key: &str, value: DescriptorValue) -> Self {
+        self.items.push((key.to_string(), value));
+        self
+    }
+
+    /// Returns the data for the descriptor.
+    pub fn data(&self) -> anyhow::Result<Vec<u8>> {
+        let mut file_stream = FileStreamWriter::new();
+        self.write(&mut file_stream)?;
+        Ok(file_stream.data().to_vec())
+    }
+
+    /// Writes the descriptor to a file stream.
+    fn write(&self, file_stream: &mut FileStreamWriter) -> anyhow::Result<()> {
+        write_unicode_string(file_stream, &self.name)?;
+        write_identifier(file_stream, &self.class_id)?;
+        file_stream.write_u32(self.items.len() as u32)?;
+        for (key, value) in &self.items {
+            write_identifier(file_stream, key)?;
+            value.write(file_stream)?;
+        }
+        Ok(())
+    }
+}
+
+impl DescriptorValue {
+    /// Returns the OSType code that marks the type of the value.
+    fn type_code(&self) -> &[u8; 4] {
+        match self {
+            DescriptorValue::Descriptor(_) => b"Objc",
+            DescriptorValue::Double(_) => b"doub",
+            DescriptorValue::UnitFloat(_, _) => b"UntF",
+            DescriptorValue::Enumerated(_, _) => b"enum",
+            DescriptorValue::Boolean(_) => b"bool",
+            DescriptorValue::Integer(_) => b"long",
+            DescriptorValue::Text(_) => b"TEXT",
+            DescriptorValue::List(_) => b"VlLs",
+        }
+    }
+
+    /// Writes the type code followed by the value.
+    fn write(&self, file_stream: &mut FileStreamWriter) -> anyhow::Result<()> {
+        file_stream.write_bytes(self.type_code())?;
+        match self {
+            DescriptorValue::Descriptor(descriptor) => descriptor.write(file_stream)?,
+            DescriptorValue::Double(value) => file_stream.write_be(value)?,
+            DescriptorValue::UnitFloat(unit, value) => {
+                file_stream.write_bytes(*unit)?;
+                file_stream.write_be(value)?;
+            }
+            DescriptorValue::Enumerated(type_id, value) => {
+                write_identifier(file_stream, type_id)?;
+                write_identifier(file_stream, value)?;
+            }
+            DescriptorValue::Boolean(value) => file_stream.write_bytes(&[*value as u8])?,
+            DescriptorValue::Integer(value) => file_stream.write_be(value)?,
+            DescriptorValue::Text(value) => write_unicode_string(file_stream, value)?,
+            DescriptorValue::List(values) => {
+                file_stream.write_u32(values.len() as u32)?;
+                for value in values {
+                    value.write(file_stream)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a class or key identifier. Four character identifiers are
+/// written with a zero length, and anything else is written with its length.
+fn write_identifier(file_stream: &mut FileStreamWriter, identifier: &str) -> anyhow::Result<()> {
+    let length = if identifier.len() == 4 {
+        0
+    } else {
+        identifier.len() as u32
+    };
+    file_stream.write_u32(length)?;
+    file_stream.write_bytes(identifier.as_bytes())?;
+    Ok(())
+}
+
+/// Writes a Unicode string as the number of UTF-16 code units followed by
+/// the code units.
+fn write_unicode_string(file_stream: &mut FileStreamWriter, string: &str) -> anyhow::Result<()> {
+    file_stream.write_u32(string.encode_utf16().count() as u32)?;
+    for code_unit in string.encode_utf16() {
+        file_stream.write_u16(code_unit)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptor_data() {
+        let descriptor = Descriptor::new("null")
+            .with("Scl ", DescriptorValue::UnitFloat(b"#Prc", 100.0))
+            .with("masterFXSwitch", DescriptorValue::Boolean(true));
+
+        let expected_data = [
+            0x00, 0x00, 0x00, 0x00, // Empty name
+            0x00, 0x00, 0x00, 0x00, 0x6e, 0x75, 0x6c, 0x6c, // null
+            0x00, 0x00, 0x00, 0x02, // Number of items
+            0x00, 0x00, 0x00, 0x00, 0x53, 0x63, 0x6c, 0x20, // Scl
+            0x55, 0x6e, 0x74, 0x46, 0x23, 0x50, 0x72, 0x63, // UntF #Prc
+            0x40, 0x59, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 100.0
+            0x00, 0x00, 0x00, 0x0e, // Length of the key
+            0x6d, 0x61, 0x73, 0x74, 0x65, 0x72, 0x46, 0x58, 0x53, 0x77, 0x69, 0x74, 0x63,
+            0x68, // masterFXSwitch
+            0x62, 0x6f, 0x6f, 0x6c, 0x01, // bool true
+        ];
+
+        assert_eq!(descriptor.data().unwrap(), expected_data);
+    }
+}
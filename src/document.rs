@@ -1,31 +1,159 @@
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::mem;
 
 use file_stream::write::FileStreamWriter;
-use graphics::{Image, Point, Rect, Size};
+use graphics::{Color, Image, Point, Rect, Size};
 
+use crate::color_channel::{ColorChannel, ColorChannelType};
 use crate::color_mode::ColorMode;
+use crate::color_space::ColorSpace;
+use crate::composite;
+use crate::endian::WriteBigEndian;
+use crate::error::{self, WriteError};
+use crate::file_format::FileFormat;
 use crate::image_compression::ImageCompression;
 use crate::layer::Layer;
 use crate::layer_container::LayerContainer;
-use crate::{data, image, LayerType};
+use crate::reader::Reader;
+use crate::{data, image, string, LayerType};
 
+mod bezier_knot;
+mod blend_color_space;
 pub(crate) mod constants;
+mod document_summary;
+mod document_template;
+mod global_light;
+mod guide;
+mod guide_direction;
+pub(crate) mod image_resources;
+mod layer_comp;
+mod layer_info_alignment;
+mod layer_order;
+mod mask_overlay;
+mod mask_overlay_kind;
+mod named_channel;
+mod parse;
+mod path;
+mod print_scale_style;
+mod print_settings;
+mod raw_resource;
+mod resolution_info;
+mod resolution_unit;
+mod slice;
+mod srgb_profile;
+mod subpath;
+mod swatches;
+
+pub use bezier_knot::BezierKnot;
+pub use blend_color_space::BlendColorSpace;
+pub use document_summary::DocumentSummary;
+pub use document_template::DocumentTemplate;
+pub use global_light::GlobalLight;
+pub use guide::{Guide, DEFAULT_GUIDES_VERSION};
+pub use guide_direction::GuideDirection;
+pub use layer_comp::LayerComp;
+pub use layer_info_alignment::LayerInfoAlignment;
+pub use layer_order::LayerOrder;
+pub use mask_overlay::MaskOverlay;
+pub use mask_overlay_kind::MaskOverlayKind;
+pub use named_channel::NamedChannel;
+pub use path::Path;
+pub use print_scale_style::PrintScaleStyle;
+pub use print_settings::PrintSettings;
+pub use raw_resource::RawResource;
+pub use resolution_info::ResolutionInfo;
+pub use resolution_unit::ResolutionUnit;
+pub use slice::Slice;
+pub use subpath::Subpath;
 
 /// A Photoshop document.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Document {
     /// The number of channels in the image, including any alpha channels. Supported range is 1 to 56.
     pub number_of_channels: u16,
-    /// The size of the image in pixels. Supported range is 1 to 30,000 for width and height.
+    /// The size of the image in pixels. Supported range is 1 to 30,000 for width and height,
+    /// or up to 300,000 for large documents.
     pub size: Size<u32>,
     /// The number of bits per channel. Supported values are 1, 8, 16 and 32.
     pub bits_per_channel: u16,
     /// The colour mode of the file.
     pub color_mode: ColorMode,
-    /// The preview image for the whole document.
+    /// The resolution of the document and the unit it’s shown in.
+    pub resolution_info: ResolutionInfo,
+    /// The settings for printing the document. The print resources
+    /// are left out when there are none.
+    pub print_settings: Option<PrintSettings>,
+    /// The light shared by layer styles. The global light resources
+    /// are left out when there is none.
+    pub global_light: Option<GlobalLight>,
+    /// The width of a pixel divided by its height, for video frames with
+    /// non-square pixels. The resource is left out when there is none.
+    pub pixel_aspect_ratio: Option<f64>,
+    /// The ICC colour profile for the document, written as is.
+    pub color_profile: Option<Vec<u8>>,
+    /// Whether an sRGB colour profile is written when there is no
+    /// `color_profile`, so that readers don’t have to guess. Off by default.
+    pub embeds_srgb_profile: bool,
+    /// The preview image for the whole document, which must be the same
+    /// size as the document.
     pub preview_image: Option<Image>,
-    /// The document’s layers.
+    /// The colour the preview image is flattened onto, for importers that
+    /// want an opaque merged image. When set, the merged image is written
+    /// with three channels and no alpha, and the header counts three
+    /// channels. The layers keep their transparency.
+    pub opaque_background: Option<Color>,
+    /// The colour space layers are blended in when the crate composites
+    /// them, such as for `set_preview_from`. Defaults to sRGB. This
+    /// setting isn’t written to the file.
+    pub blend_color_space: BlendColorSpace,
+    /// The document’s layers, in the order given by `layer_order`.
     pub layers: Vec<Layer>,
+    /// The order of the layers, and of the child layers in each group.
+    /// Defaults to bottom to top, the order used in the file.
+    pub layer_order: LayerOrder,
+    /// The alignment the layer information is padded to. Defaults to
+    /// two bytes.
+    pub layer_info_alignment: LayerInfoAlignment,
+    /// The colour used to show layer masks, stored in the global layer
+    /// mask info. The global layer mask info is empty when there is none.
+    pub mask_overlay: Option<MaskOverlay>,
+    /// The index of the layer selected when the document is opened,
+    /// counting from the bottom layer record.
+    pub selected_layer: usize,
+    /// The independent channels of a multichannel document. These
+    /// are written instead of the preview image.
+    pub channels: Vec<NamedChannel>,
+    /// The layer comps, saved snapshots of the layers. Each layer’s
+    /// visibility in the comps is written with the layer, so the layers
+    /// need identifiers.
+    pub layer_comps: Vec<LayerComp>,
+    /// The slices used for web export. The slices resource is left out
+    /// when there are none.
+    pub slices: Vec<Slice>,
+    /// The work paths, such as cut paths, with up to 999 paths.
+    pub paths: Vec<Path>,
+    /// The name of the path used as the clipping path, if there is one.
+    pub clipping_path_name: Option<String>,
+    /// The colour table for indexed documents, with up to 256 colours.
+    pub palette: Option<Vec<Color>>,
+    /// The duotone specification for duotone documents. Photoshop treats
+    /// this as opaque data, so it is written as is.
+    pub duotone_specification: Option<Vec<u8>>,
+    /// The colour swatches for the document, each with a name and
+    /// the colour space it is stored in.
+    pub swatches: Vec<(String, Color, ColorSpace)>,
+    /// Image resources that aren’t otherwise understood, written
+    /// back out as they are.
+    pub extra_resources: Vec<RawResource>,
+    /// The name of the application that wrote the document, stored
+    /// in the version info. Defaults to the name of this crate.
+    pub writer_name: String,
+    /// The name of the application the document is meant to be read
+    /// by, stored in the version info. Defaults to the name of this crate.
+    pub reader_name: String,
 }
 
 // MARK: Creation
@@ -38,46 +166,502 @@ impl Document {
             size: Size::zero(),
             bits_per_channel: 1,
             color_mode: ColorMode::Bitmap,
+            resolution_info: ResolutionInfo::default(),
+            print_settings: None,
+            global_light: None,
+            pixel_aspect_ratio: None,
+            color_profile: None,
+            embeds_srgb_profile: false,
             preview_image: None,
+            opaque_background: None,
+            blend_color_space: BlendColorSpace::Srgb,
             layers: Vec::new(),
+            layer_order: LayerOrder::BottomToTop,
+            layer_info_alignment: LayerInfoAlignment::default(),
+            mask_overlay: None,
+            selected_layer: 0,
+            channels: Vec::new(),
+            layer_comps: Vec::new(),
+            slices: Vec::new(),
+            paths: Vec::new(),
+            clipping_path_name: None,
+            palette: None,
+            duotone_specification: None,
+            swatches: Vec::new(),
+            extra_resources: Vec::new(),
+            writer_name: env!("CARGO_PKG_NAME").to_string(),
+            reader_name: env!("CARGO_PKG_NAME").to_string(),
         }
     }
+
+    /// Creates an empty RGB document with 8 bits per channel and an
+    /// alpha channel, ready to have layers added and be exported.
+    pub fn rgb(size: Size<u32>) -> Self {
+        let mut document = Self::new();
+        document.size = size;
+        document.bits_per_channel = 8;
+        document.set_color_mode(ColorMode::Rgb);
+        document.number_of_channels = 4;
+        document
+    }
+
+    /// Creates an RGB document sized to an image, with a single layer
+    /// holding the image, which is also used as the preview image.
+    pub fn from_image(image: Image) -> Self {
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: image.size.into(),
+        };
+        let mut layer = Layer::new(bounds);
+        layer.name = Some("Layer 1".to_string());
+        layer.image = Some(image.clone());
+
+        let mut document = Self::rgb(image.size);
+        document.layers = vec![layer];
+        document.preview_image = Some(image);
+        document
+    }
+
+    /// Creates an RGB document sized to an atlas image, with a layer for
+    /// each named region of the image. Regions are clipped to the image.
+    /// The preview image is the composite of the layers.
+    pub fn from_atlas(image: Image, regions: &[(String, Rect<i32>)]) -> Self {
+        let image_bounds = Rect {
+            origin: Point::zero(),
+            size: image.size.into(),
+        };
+
+        let mut document = Self::rgb(image.size);
+        for (name, region) in regions {
+            let min_x = region.min_x().clamp(0, image_bounds.max_x());
+            let min_y = region.min_y().clamp(0, image_bounds.max_y());
+            let max_x = region.max_x().clamp(min_x, image_bounds.max_x());
+            let max_y = region.max_y().clamp(min_y, image_bounds.max_y());
+            let bounds = Rect::new(min_x, min_y, max_x - min_x, max_y - min_y);
+
+            let mut layer = Layer::new(bounds);
+            layer.name = Some(name.clone());
+            layer.image = Some(image::cropped_image(&image, bounds));
+            document.layers.push(layer);
+        }
+        document.preview_image = Some(composite::composited_image(
+            &document.layers,
+            image_bounds,
+            document.blend_color_space,
+        ));
+        document
+    }
+}
+
+// MARK: Updates
+
+impl Document {
+    /// Sets the colour mode, along with the number of channels the mode
+    /// naturally has. Set `number_of_channels` afterwards to override
+    /// the count, such as to add an alpha channel. The count is left
+    /// as it is for multichannel documents. Colour modes that are
+    /// written as RGB still write three or four channels to the file.
+    pub fn set_color_mode(&mut self, color_mode: ColorMode) {
+        if let Some(number_of_channels) = color_mode.number_of_channels() {
+            self.number_of_channels = number_of_channels;
+        }
+        self.color_mode = color_mode;
+    }
+
+    /// Removes the layers that are entirely transparent. Groups left
+    /// without any layers are removed too if `removes_empty_groups` is set.
+    pub fn prune_empty_layers(&mut self, removes_empty_groups: bool) {
+        prune_empty_layers(&mut self.layers, removes_empty_groups);
+        // The selected layer may no longer exist.
+        if self.selected_layer >= self.number_of_layers() {
+            self.selected_layer = 0;
+        }
+    }
+
+    /// Moves top-level layers into a new open group with a name. The
+    /// layers keep their order, and don’t need to be next to each other.
+    /// The group takes the place of the first of the layers.
+    pub fn group_layers(&mut self, indices: &[usize], name: &str) -> error::Result<()> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        let Some(&first_index) = indices.first() else {
+            return Ok(());
+        };
+        if let Some(&index) = indices.iter().find(|&&index| index >= self.layers.len()) {
+            return Err(WriteError::InvalidLayerIndex(index).into());
+        }
+
+        // Removing from the end keeps the remaining indices valid.
+        let mut child_layers: Vec<Layer> = indices
+            .iter()
+            .rev()
+            .map(|&index| self.layers.remove(index))
+            .collect();
+        child_layers.reverse();
+
+        let mut group = Layer::group(child_layers, true);
+        group.name = Some(name.to_string());
+        self.layers.insert(first_index, group);
+        Ok(())
+    }
+}
+
+// MARK: Rendering
+
+impl Document {
+    /// Returns the composited layers scaled down to fit within a size,
+    /// keeping the aspect ratio. Documents that already fit aren’t scaled.
+    pub fn thumbnail(&self, max: Size<u32>) -> error::Result<Image> {
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: self.size.into(),
+        };
+        let image = composite::composited_image(
+            &self.bottom_to_top_layers(),
+            bounds,
+            self.blend_color_space,
+        );
+
+        let scale = (max.width as f64 / self.size.width as f64)
+            .min(max.height as f64 / self.size.height as f64)
+            .min(1.0);
+        let size = Size {
+            width: ((self.size.width as f64 * scale).round() as u32).clamp(1, max.width.max(1)),
+            height: ((self.size.height as f64 * scale).round() as u32).clamp(1, max.height.max(1)),
+        };
+        Ok(image::scaled_image(&image, size))
+    }
+
+    /// Sets the preview image to the composite of the layers for which
+    /// the predicate returns `true`, leaving out layers such as guides or
+    /// reference images. Leaving out a group also leaves out all of its
+    /// child layers. Layers that extend beyond the canvas are clipped to
+    /// it, though their records keep their full bounds.
+    pub fn set_preview_from(&mut self, predicate: impl Fn(&Layer) -> bool) {
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: self.size.into(),
+        };
+        let layers = filtered_layers(&self.bottom_to_top_layers(), &predicate);
+        self.preview_image = Some(composite::composited_image(
+            &layers,
+            bounds,
+            self.blend_color_space,
+        ));
+    }
+}
+
+// MARK: Resources
+
+impl Document {
+    /// Returns the resolution of the document.
+    pub fn resolution(&self) -> ResolutionInfo {
+        self.resolution_info
+    }
+
+    /// Returns the ICC colour profile embedded in the document, if any.
+    pub fn icc_profile(&self) -> Option<&[u8]> {
+        self.color_profile.as_deref()
+    }
+
+    /// Returns the ruler guides, read from the grid and guides resource.
+    pub fn guides(&self) -> error::Result<Vec<Guide>> {
+        let Some(resource) = self.extra_resources.iter().find(|resource| {
+            resource.identifier == constants::resource_identifiers::GRID_AND_GUIDES
+        }) else {
+            return Ok(Vec::new());
+        };
+        Ok(guide::from_reader(&mut Reader::new(&resource.data))?)
+    }
+
+    /// Sets the ruler guides, replacing the grid and guides resource.
+    pub fn set_guides(&mut self, guides: &[Guide]) -> error::Result<()> {
+        self.set_guides_with_version(guides, DEFAULT_GUIDES_VERSION)
+    }
+
+    /// Sets the ruler guides, replacing the grid and guides resource,
+    /// which is written with a version. Photoshop writes version
+    /// `DEFAULT_GUIDES_VERSION`.
+    pub fn set_guides_with_version(&mut self, guides: &[Guide], version: u32) -> error::Result<()> {
+        let data = guide::data(guides, version)?;
+        self.extra_resources.retain(|resource| {
+            resource.identifier != constants::resource_identifiers::GRID_AND_GUIDES
+        });
+        self.extra_resources.push(RawResource::new(
+            constants::resource_identifiers::GRID_AND_GUIDES,
+            data,
+        ));
+        Ok(())
+    }
+}
+
+// MARK: Summary
+
+impl Document {
+    /// Returns a summary of the document, such as the number of layers.
+    pub fn summary(&self) -> DocumentSummary {
+        DocumentSummary {
+            size: self.size,
+            color_mode: self.color_mode.clone(),
+            bits_per_channel: self.bits_per_channel,
+            number_of_layers: self.number_of_layers(),
+            number_of_groups: self
+                .all_layers()
+                .iter()
+                .filter(|layer| matches!(layer.layer_type, LayerType::Group(_)))
+                .count(),
+            has_preview_image: self.preview_image.is_some(),
+        }
+    }
+}
+
+// MARK: Navigation
+
+impl Document {
+    /// Returns the layer at a path of indices, each one descending into
+    /// the child layers of a group. For example, `[0, 2]` is the third
+    /// child of the first layer. The indices follow `layer_order`.
+    pub fn layer_at_path(&self, path: &[usize]) -> Option<&Layer> {
+        let (&first_index, indices) = path.split_first()?;
+        let mut layer = self.layers.get(first_index)?;
+        for &index in indices {
+            let LayerType::Group(info) = &layer.layer_type else {
+                return None;
+            };
+            layer = info.layers.get(index)?;
+        }
+        Some(layer)
+    }
+
+    /// Returns the layer at a path of indices so that it can be changed.
+    /// See `layer_at_path`.
+    pub fn layer_at_path_mut(&mut self, path: &[usize]) -> Option<&mut Layer> {
+        let (&first_index, indices) = path.split_first()?;
+        let mut layer = self.layers.get_mut(first_index)?;
+        for &index in indices {
+            let LayerType::Group(info) = &mut layer.layer_type else {
+                return None;
+            };
+            layer = info.layers.get_mut(index)?;
+        }
+        Some(layer)
+    }
 }
 
 // MARK: Export
 
 impl Document {
-    /// Return the data for the file.
-    pub fn file_data(&self) -> anyhow::Result<Vec<u8>> {
+    /// Return the data for the file. The compressed channels are kept
+    /// with the layers, so that exporting again doesn’t compress them
+    /// again. Clear `channels` on any layer whose image changes afterwards.
+    pub fn file_data(&mut self) -> error::Result<Vec<u8>> {
+        Ok(self.file_data_for_format(FileFormat::Psd, &ImageCompression::Rle)?)
+    }
+
+    /// Returns the data for the file, with the channel data stored using
+    /// a compression method.
+    pub fn file_data_with_compression(
+        &mut self,
+        compression: ImageCompression,
+    ) -> error::Result<Vec<u8>> {
+        Ok(self.file_data_for_format(FileFormat::Psd, &compression)?)
+    }
+
+    /// Returns the data for the file with an empty image resources
+    /// section, leaving out the resolution, layer state, group info and
+    /// any other resources. This makes the smallest valid file, for test
+    /// fixtures and strict readers.
+    pub fn file_data_minimal(&mut self) -> error::Result<Vec<u8>> {
+        let format = FileFormat::Psd;
+        let compression = ImageCompression::Rle;
+        self.validate_size(format)?;
+
+        Ok([
+            self.header_data(format)?,
+            self.color_mode_data()?,
+            image_resources_section_data(&[])?,
+            self.layer_and_mask_information_data(format, &compression)?,
+            self.image_data(format, &compression)?,
+        ]
+        .concat())
+    }
+
+    /// Returns the data for the file as a large document (PSB), for
+    /// documents that are too big to be saved as a PSD.
+    pub fn file_data_psb(&mut self) -> error::Result<Vec<u8>> {
+        Ok(self.file_data_for_format(FileFormat::Psb, &ImageCompression::Rle)?)
+    }
+
+    /// Returns the data for the file, leaving out any layers for which
+    /// the predicate returns `false`. Leaving out a group also leaves out
+    /// all of its child layers. The document itself isn’t changed.
+    pub fn file_data_filtered(&self, predicate: impl Fn(&Layer) -> bool) -> error::Result<Vec<u8>> {
+        let mut document = self.clone();
+        document.layers = filtered_layers(&self.layers, &predicate);
+        // The selected layer may no longer exist.
+        if document.selected_layer >= document.number_of_layers() {
+            document.selected_layer = 0;
+        }
+        document.file_data()
+    }
+
+    /// Returns the size of the file data in bytes, without putting the
+    /// sections of the file together.
+    pub fn estimated_size(&mut self) -> error::Result<u64> {
+        let sections = self.sections_data(FileFormat::Psd, &ImageCompression::Rle)?;
+        Ok(sections.iter().map(|section| section.len() as u64).sum())
+    }
+
+    /// Compresses the channel data for the layers ahead of writing, so
+    /// that channels with identical data, such as fully opaque alpha
+    /// channels, are only compressed once. This saves time rather than
+    /// space, as each channel is still stored separately. The compressed
+    /// data is kept with the channels, so clear `channels` on any layer
+    /// whose image changes afterwards.
+    pub fn optimize(&mut self) -> error::Result<()> {
+        let is_grayscale = self.color_mode == ColorMode::Duotone;
+        let document_bounds = Rect {
+            origin: Point::zero(),
+            size: self.size.into(),
+        };
+        let mut channels = Vec::new();
+        for layer in self.layers.iter_mut() {
+            channels.append(&mut layer.channels_with_heights(is_grayscale, document_bounds)?);
+        }
+
+        // The first channel with each hash of data and height. Channels
+        // with the same hash are compared in full before sharing data.
+        let mut first_indices: HashMap<(u64, u32), usize> = HashMap::new();
+        for index in 0..channels.len() {
+            let mut hasher = DefaultHasher::new();
+            channels[index].1.data.hash(&mut hasher);
+            let key = (hasher.finish(), channels[index].0);
+            match first_indices.get(&key) {
+                Some(&first_index) if channels[first_index].1.data == channels[index].1.data => {
+                    let compressed_data = channels[first_index].1.compressed_data.clone();
+                    channels[index].1.compressed_data = compressed_data;
+                }
+                _ => {
+                    first_indices.insert(key, index);
+                    let (height, channel) = &mut channels[index];
+                    channel.compressed_data(*height)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the data for the file in a file format.
+    fn file_data_for_format(
+        &mut self,
+        format: FileFormat,
+        compression: &ImageCompression,
+    ) -> anyhow::Result<Vec<u8>> {
         // TODO: Create a file stream on disk to avoid
         // potentially running out of RAM.
-        let mut file_stream = FileStreamWriter::new();
+        Ok(self.sections_data(format, compression)?.concat())
+    }
+
+    /// Returns the data for each section of the file, in order.
+    fn sections_data(
+        &mut self,
+        format: FileFormat,
+        compression: &ImageCompression,
+    ) -> anyhow::Result<Vec<Vec<u8>>> {
+        self.validate_size(format)?;
+
+        Ok(vec![
+            self.header_data(format)?,
+            self.color_mode_data()?,
+            self.image_resources_data()?,
+            self.layer_and_mask_information_data(format, compression)?,
+            self.image_data(format, compression)?,
+        ])
+    }
+
+    /// Checks that the size of the document fits in a file format.
+    fn validate_size(&self, format: FileFormat) -> anyhow::Result<()> {
+        let maximum_dimension = format.maximum_dimension();
+        if self.size.width > maximum_dimension || self.size.height > maximum_dimension {
+            anyhow::bail!(WriteError::InvalidDocumentSize(
+                self.size.width,
+                self.size.height
+            ));
+        }
+        Ok(())
+    }
 
-        // HEADER SECTION
+    /// Returns the data for the header section.
+    fn header_data(&self, format: FileFormat) -> anyhow::Result<Vec<u8>> {
+        let mut file_stream = FileStreamWriter::new();
         file_stream.write_bytes(&constants::FILE_SIGNATURE)?;
-        file_stream.write_be(&1i16)?;
+        file_stream.write_i16(format.version())?;
 
         // Six bytes of padding.
         file_stream.write_zeros(6)?;
 
-        // The number of channels — always 4 for RGBA.
-        file_stream.write_be(&self.number_of_channels)?;
+        // The number of channels — 4 for RGBA unless the channels are independent.
+        file_stream.write_u16(self.written_number_of_channels())?;
 
         // The size of the image.
-        file_stream.write_be(&self.size.height)?;
-        file_stream.write_be(&self.size.width)?;
+        file_stream.write_u32(self.size.height)?;
+        file_stream.write_u32(self.size.width)?;
 
         // The colour depth.
-        file_stream.write_be(&8i16)?;
+        file_stream.write_u16(8)?;
 
         // The colour mode.
-        file_stream.write_be(&ColorMode::Rgb.raw_value())?;
+        file_stream.write_i16(self.written_color_mode().raw_value())?;
+
+        Ok(file_stream.data().to_vec())
+    }
+
+    /// Returns the data for the colour mode data section.
+    fn color_mode_data(&self) -> anyhow::Result<Vec<u8>> {
+        let color_mode_data = self.color_mode_specific_data()?;
+
+        let mut file_stream = FileStreamWriter::new();
+        file_stream.write_be(&data::section_length(color_mode_data.len())?)?;
+        file_stream.write_bytes(&color_mode_data)?;
+
+        Ok(file_stream.data().to_vec())
+    }
+
+    /// Returns the data that the colour mode needs, which is empty for
+    /// most colour modes.
+    fn color_mode_specific_data(&self) -> anyhow::Result<Vec<u8>> {
+        match self.color_mode {
+            ColorMode::Indexed => {
+                let Some(palette) = &self.palette else {
+                    anyhow::bail!(WriteError::MissingColorModeData);
+                };
+                palette_data(palette)
+            }
+            ColorMode::Duotone => {
+                let Some(duotone_specification) = &self.duotone_specification else {
+                    anyhow::bail!(WriteError::MissingColorModeData);
+                };
+                Ok(duotone_specification.clone())
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
 
-        // The colour mode data (come back to this when we have indexed documents).
-        file_stream.write_be(&0u32)?;
+    /// Returns the data for the image resources section, which is
+    /// kind of a second header, with meta-information.
+    fn image_resources_data(&self) -> anyhow::Result<Vec<u8>> {
+        image_resources_section_data(&[
+            &self.leading_resources_data()?,
+            &self.layer_resources_data()?,
+            &self.trailing_resources_data()?,
+        ])
+    }
 
-        // IMAGE RESOURCES SECTION
-        // Kind of a second header, with meta-information.
+    /// Returns the data for the image resources written before the
+    /// resources that depend on the layers.
+    fn leading_resources_data(&self) -> anyhow::Result<Vec<u8>> {
         let mut image_resources_file_stream = FileStreamWriter::new();
         image_resources_file_stream.write_bytes(&constants::RESOURCE_SIGNATURE)?;
 
@@ -86,24 +670,33 @@ impl Document {
             .write_be(&constants::resource_identifiers::RESOLUTION_INFORMATION)?;
         // Write null for the name.
         image_resources_file_stream.write_be(&0i16)?;
-        // We don’t have the definition for this, so the bytes are hard coded.
-        let resolution_information_data = vec![
-            0x00, 0x48, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x48, 0x00, 0x00, 0x00, 0x01,
-            0x00, 0x01,
-        ];
-        image_resources_file_stream.write_be(&(resolution_information_data.len() as u32))?;
+        let resolution_information_data = self.resolution_info.data()?;
+        image_resources_file_stream
+            .write_be(&data::section_length(resolution_information_data.len())?)?;
         image_resources_file_stream.write_bytes(&resolution_information_data)?;
 
-        // Selected layer (set to zero).
+        Ok(image_resources_file_stream.data().to_vec())
+    }
+
+    /// Returns the data for the image resources that depend on the
+    /// layers and on whether there is a merged image.
+    fn layer_resources_data(&self) -> anyhow::Result<Vec<u8>> {
+        let mut image_resources_file_stream = FileStreamWriter::new();
+
+        // The selected layer.
+        if self.selected_layer > 0 && self.selected_layer >= self.number_of_layers() {
+            anyhow::bail!(WriteError::InvalidLayerIndex(self.selected_layer));
+        }
         image_resources_file_stream.write_bytes(&constants::RESOURCE_SIGNATURE)?;
         image_resources_file_stream.write_be(&constants::resource_identifiers::LAYER_STATE)?;
 
         // Write null for the name.
         image_resources_file_stream.write_be(&0i16)?;
-        // The size is 2 bytes.
+        // The size is 2 bytes, holding only the index of the target layer.
+        // There’s no field for the background layer, which is instead
+        // recorded by its name and its lack of an alpha channel.
         image_resources_file_stream.write_be(&2u32)?;
-        // Write 0 for the actual data.
-        image_resources_file_stream.write_be(&0u16)?;
+        image_resources_file_stream.write_be(&(self.selected_layer as u16))?;
 
         // The layers group information.
         image_resources_file_stream.write_bytes(&constants::RESOURCE_SIGNATURE)?;
@@ -113,72 +706,480 @@ impl Document {
         image_resources_file_stream.write_be(&0i16)?;
 
         // Write the size of the group IDs data.
-        let layers_group_identifiers_size =
-            self.number_of_layers() as u32 * mem::size_of::<u16>() as u32;
-        image_resources_file_stream.write_be(&layers_group_identifiers_size)?;
+        let number_of_layers = self.number_of_layers();
+        let layers_group_identifiers_size = number_of_layers
+            .checked_mul(mem::size_of::<u16>())
+            .ok_or(WriteError::TooManyLayers(number_of_layers))?;
+        image_resources_file_stream
+            .write_be(&data::section_length(layers_group_identifiers_size)?)?;
         // image_resources_file_stream.write_be(&255u8)?;
         // For each layer (including groups), output the group ID.
         for _ in 0..self.number_of_layers() {
             image_resources_file_stream.write_be(&0i16)?;
         }
 
-        // Write the images resources section.
-        file_stream.write_be(&(image_resources_file_stream.data().len() as u32))?;
-        file_stream.write_bytes(&image_resources_file_stream.data())?;
+        // The version info. Readers recomposite the layers when
+        // there is no merged image.
+        image_resources::write_image_resource(
+            &mut image_resources_file_stream,
+            constants::resource_identifiers::VERSION_INFO,
+            &image_resources::version_info_data(
+                self.has_merged_image_data(),
+                &self.writer_name,
+                &self.reader_name,
+            )?,
+        )?;
+
+        // The layer comps.
+        if !self.layer_comps.is_empty() {
+            image_resources::write_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::LAYER_COMPS,
+                &layer_comp::data(&self.layer_comps)?,
+            )?;
+        }
 
-        // LAYER AND MASK INFORMATION SECTION
-        let mut layer_and_mask_info_file_stream = FileStreamWriter::new();
+        // The next free layer identifier, so that layers added later
+        // don’t reuse an identifier.
+        if let Some(max_id) = self.all_layers().iter().filter_map(|layer| layer.id).max() {
+            image_resources::write_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::LAYER_ID_GENERATOR_BASE,
+                &max_id.saturating_add(1).to_be_bytes(),
+            )?;
+        }
 
-        let mut layer_info_file_stream = FileStreamWriter::new();
-        layer_info_file_stream.write_be(&((self.number_of_layers() as i16) * -1))?;
+        Ok(image_resources_file_stream.data().to_vec())
+    }
 
-        // Obviously cloning here is bad. Really we need to rethink so many of these
-        // methods being mutable.
-        let mut layers: Vec<Layer> = self
-            .all_layers()
-            .iter()
-            .map(|&layer| layer.clone())
-            .collect();
+    /// Returns the data for the image resources written after the
+    /// resources that depend on the layers.
+    fn trailing_resources_data(&self) -> anyhow::Result<Vec<u8>> {
+        let mut image_resources_file_stream = FileStreamWriter::new();
 
-        // Layer records.
-        for layer in layers.iter_mut() {
-            // Procreate can’t handle zero width and height.
-            if layer.bounds == Rect::zero() {
-                layer.bounds = Rect {
-                    origin: Point::zero(),
-                    size: self.size.into(),
-                };
+        // The colour profile.
+        let color_profile = match &self.color_profile {
+            Some(color_profile) => Some(color_profile.clone()),
+            None if self.embeds_srgb_profile => Some(srgb_profile::data()?),
+            None => None,
+        };
+        if let Some(color_profile) = &color_profile {
+            image_resources::write_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::COLOR_PROFILE,
+                color_profile,
+            )?;
+        }
+
+        // The global light.
+        if let Some(global_light) = &self.global_light {
+            image_resources::write_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::GLOBAL_ANGLE,
+                &global_light.angle.to_be_bytes(),
+            )?;
+            image_resources::write_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::GLOBAL_ALTITUDE,
+                &global_light.altitude.to_be_bytes(),
+            )?;
+        }
+
+        // The slices.
+        if !self.slices.is_empty() {
+            image_resources::write_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::SLICES,
+                &slice::data(&self.slices, self.size)?,
+            )?;
+        }
+
+        // The pixel aspect ratio, after the version of the resource.
+        if let Some(pixel_aspect_ratio) = self.pixel_aspect_ratio {
+            let mut pixel_aspect_ratio_data =
+                constants::PIXEL_ASPECT_RATIO_VERSION.to_be_bytes().to_vec();
+            pixel_aspect_ratio_data.extend_from_slice(&pixel_aspect_ratio.to_be_bytes());
+            image_resources::write_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::PIXEL_ASPECT_RATIO,
+                &pixel_aspect_ratio_data,
+            )?;
+        }
+
+        // The print settings.
+        if let Some(print_settings) = &self.print_settings {
+            image_resources::write_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::PRINT_FLAGS,
+                &print_settings.print_flags_data(),
+            )?;
+            image_resources::write_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::PRINT_SCALE,
+                &print_settings.print_scale_data()?,
+            )?;
+            image_resources::write_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::PRINT_FLAGS_INFORMATION,
+                &print_settings.print_flags_information_data()?,
+            )?;
+        }
+
+        // The channel names for documents with independent channels.
+        if self.color_mode == ColorMode::Multichannel {
+            let mut channel_names_data = Vec::new();
+            for channel in self.channels.iter() {
+                channel_names_data
+                    .append(&mut string::pascal::data_from_string(Some(&channel.name))?);
             }
-            layer_info_file_stream.write_bytes(&(layer.layer_record_data()?))?;
+            image_resources::write_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::ALPHA_CHANNEL_NAMES,
+                &channel_names_data,
+            )?;
+        }
+
+        // The paths, each in its own resource.
+        let number_of_paths = (constants::resource_identifiers::LAST_PATH_INFORMATION
+            - constants::resource_identifiers::FIRST_PATH_INFORMATION
+            + 1) as usize;
+        if self.paths.len() > number_of_paths {
+            anyhow::bail!(WriteError::TooManyPaths(self.paths.len()));
+        }
+        for (index, path) in self.paths.iter().enumerate() {
+            image_resources::write_named_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::FIRST_PATH_INFORMATION + index as i16,
+                &path.name,
+                &path.data(self.size)?,
+            )?;
+        }
+        if let Some(clipping_path_name) = &self.clipping_path_name {
+            // The name, followed by the flatness, where zero is the default.
+            let mut clipping_path_data =
+                string::pascal::data_from_string(Some(clipping_path_name))?;
+            clipping_path_data.extend_from_slice(&[0, 0]);
+            image_resources::write_image_resource(
+                &mut image_resources_file_stream,
+                constants::resource_identifiers::CLIPPING_PATH_NAME,
+                &clipping_path_data,
+            )?;
         }
 
-        // Layer images.
-        for layer in layers.iter_mut() {
-            layer_info_file_stream.write_bytes(&(layer.encoded_image()?))?;
+        // Any resources kept from a parsed document.
+        for resource in self.extra_resources.iter() {
+            image_resources::write_named_image_resource(
+                &mut image_resources_file_stream,
+                resource.identifier,
+                &resource.name,
+                &resource.data,
+            )?;
         }
 
-        // Write the layer info to the layer and mask info file stream.
-        let mut layer_info_data = layer_info_file_stream.data().to_vec();
-        data::pad(&mut layer_info_data, 2);
-        layer_and_mask_info_file_stream.write_be(&(layer_info_data.len() as u32))?;
-        layer_and_mask_info_file_stream.write_bytes(&layer_info_data)?;
+        Ok(image_resources_file_stream.data().to_vec())
+    }
+
+    /// Returns the data for the layer and mask information section.
+    fn layer_and_mask_information_data(
+        &mut self,
+        format: FileFormat,
+        compression: &ImageCompression,
+    ) -> anyhow::Result<Vec<u8>> {
+        // Photoshop only has a merged image for indexed colour.
+        if self.color_mode == ColorMode::Indexed && !self.layers.is_empty() {
+            anyhow::bail!(WriteError::LayersInIndexedDocument);
+        }
+
+        // The buffers are moved rather than copied wherever possible,
+        // as the layer images make up most of the file.
+        // The count is stored negated, so it can’t be more than `i16::MAX`.
+        let number_of_layers = self.number_of_layers();
+        let Ok(layer_count) = i16::try_from(number_of_layers) else {
+            anyhow::bail!(WriteError::TooManyLayers(number_of_layers));
+        };
+        let mut layer_info_data = (-layer_count).to_be_bytes().to_vec();
+
+        // Each layer is encoded in place, with the compressed channels
+        // kept between its record, its image and later exports. The
+        // images follow all of the records, so they’re collected separately.
+        let mut layer_images_data = Vec::new();
+        let document_bounds = Rect {
+            origin: Point::zero(),
+            size: self.size.into(),
+        };
+        let is_grayscale = self.color_mode == ColorMode::Duotone;
+        let layer_comps = &self.layer_comps;
+        let mut write_layer = |layer: &mut Layer| {
+            layer.with_document_bounds(document_bounds, |layer| {
+                if is_grayscale && layer.layer_type == LayerType::Image && layer.channels.is_empty()
+                {
+                    layer.update_grayscale_channel_data()?;
+                }
+                // The layer’s visibility in the layer comps.
+                let settings_data = match layer.id {
+                    Some(id) => layer_comp::layer_settings_data(layer_comps, id)?,
+                    None => None,
+                };
+                let (mut record_data, mut image_data) =
+                    layer.with_raw_additional_info(*b"cmls", settings_data, |layer| {
+                        let record_data =
+                            layer.layer_record_data_for_format(format, compression)?;
+                        let image_data = layer.encoded_image_for_format(format, compression)?;
+                        Ok((record_data, image_data))
+                    })?;
+                layer_info_data.append(&mut record_data);
+                layer_images_data.append(&mut image_data);
+                Ok(())
+            })
+        };
+        let is_reversed = self.layer_order == LayerOrder::TopToBottom;
+        let layers: Box<dyn Iterator<Item = &mut Layer>> = if is_reversed {
+            Box::new(self.layers.iter_mut().rev())
+        } else {
+            Box::new(self.layers.iter_mut())
+        };
+        for layer in layers {
+            layer.for_each_record_layer(is_reversed, &mut write_layer)?;
+        }
+        layer_info_data.append(&mut layer_images_data);
+        data::pad(
+            &mut layer_info_data,
+            self.layer_info_alignment.number_of_bytes(),
+        );
+
+        let mut layer_info_length_file_stream = FileStreamWriter::new();
+        format.write_length(&mut layer_info_length_file_stream, layer_info_data.len())?;
+        let layer_info_length_data = layer_info_length_file_stream.data();
+
+        // The global layer mask info, which is empty without a mask overlay.
+        let mut global_layer_mask_info_data = match &self.mask_overlay {
+            Some(mask_overlay) => mask_overlay.data()?,
+            None => Vec::new(),
+        };
 
-        // The global layer mask info.
-        layer_and_mask_info_file_stream.write_be(&0u32)?;
+        // The layer info, followed by the global layer mask info.
+        let section_length = layer_info_length_data.len()
+            + layer_info_data.len()
+            + mem::size_of::<u32>()
+            + global_layer_mask_info_data.len();
+        let mut section_length_file_stream = FileStreamWriter::new();
+        format.write_length(&mut section_length_file_stream, section_length)?;
+        let section_length_data = section_length_file_stream.data();
+
+        let mut output = Vec::with_capacity(section_length_data.len() + section_length);
+        output.extend_from_slice(section_length_data);
+        output.extend_from_slice(layer_info_length_data);
+        output.append(&mut layer_info_data);
+        output.extend_from_slice(&(global_layer_mask_info_data.len() as u32).to_be_bytes());
+        output.append(&mut global_layer_mask_info_data);
+
+        Ok(output)
+    }
+
+    /// Returns the data for the image data section, which holds the
+    /// independent channels or a flattened preview image.
+    fn image_data(
+        &self,
+        format: FileFormat,
+        compression: &ImageCompression,
+    ) -> anyhow::Result<Vec<u8>> {
+        if !self.has_merged_image_data() {
+            return Ok(Vec::new());
+        }
 
-        // Write the layer info to the global file stream.
-        file_stream.write_be(&(layer_and_mask_info_file_stream.data().len() as u32))?;
-        file_stream.write_bytes(layer_and_mask_info_file_stream.data())?;
+        let image_data = if self.color_mode == ColorMode::Multichannel {
+            let number_of_rows = self.channels.len() * self.size.height as usize;
+            (self.multichannel_data(compression)?, number_of_rows)
+        } else if let Some(preview_image) = &self.preview_image {
+            // Readers expect the merged image to cover the document.
+            if preview_image.size != self.size {
+                anyhow::bail!(WriteError::PreviewSizeMismatch(
+                    preview_image.size.width,
+                    preview_image.size.height
+                ));
+            }
+            let preview_image = match &self.opaque_background {
+                Some(background) => {
+                    Cow::Owned(composite::flattened_image(preview_image, background))
+                }
+                None => Cow::Borrowed(preview_image),
+            };
+            let height = preview_image.size.height as usize;
+            if self.color_mode == ColorMode::Duotone {
+                let data = image::grayscale_psd_data(&preview_image, compression)?;
+                (data, height)
+            } else if self.color_mode == ColorMode::Indexed {
+                let Some(palette) = &self.palette else {
+                    anyhow::bail!(WriteError::MissingColorModeData);
+                };
+                let data = image::indexed_psd_data(&preview_image, palette, compression)?;
+                (data, height)
+            } else {
+                let number_of_channels = self.written_number_of_channels();
+                let data = image::psd_data(&preview_image, number_of_channels, compression)?;
+                (data, number_of_channels as usize * height)
+            }
+        } else {
+            return Ok(Vec::new());
+        };
+        let (image_data, number_of_rows) = image_data;
 
-        // IMAGE DATA SECTION
-        // A flattened preview image.
-        if let Some(preview_image) = &self.preview_image {
-            let preview_image_data = image::psd_data(preview_image, &ImageCompression::Rle)?;
-            file_stream.write_bytes(&preview_image_data)?;
+        // The compression, followed by the byte counts for every row when
+        // the data is RLE compressed.
+        let mut file_stream = FileStreamWriter::new();
+        let (compression_data, channels_data) = image_data.split_at(2);
+        file_stream.write_bytes(compression_data)?;
+        if compression_data == ImageCompression::Rle.raw_value().to_be_bytes() {
+            file_stream.write_bytes(&format.rle_data(channels_data, number_of_rows))?;
+        } else {
+            file_stream.write_bytes(channels_data)?;
         }
 
         Ok(file_stream.data().to_vec())
     }
+
+    /// Returns the number of channels in the merged image, which leaves
+    /// out the alpha channel when flattening onto an opaque background.
+    fn merged_number_of_channels(&self) -> u16 {
+        match self.opaque_background {
+            Some(_) => self.number_of_channels.min(3),
+            None => self.number_of_channels,
+        }
+    }
+
+    /// Returns the number of channels written to the header, which
+    /// matches the channels of the merged image. Colour modes written as
+    /// RGB have three channels, or four with alpha, whatever the number
+    /// of channels the document has.
+    fn written_number_of_channels(&self) -> u16 {
+        match self.written_color_mode() {
+            ColorMode::Multichannel => self.channels.len() as u16,
+            ColorMode::Duotone | ColorMode::Indexed => 1,
+            // Opaque documents leave out the alpha channel.
+            _ if self.merged_number_of_channels() <= 3 => 3,
+            _ => 4,
+        }
+    }
+
+    /// Returns whether a merged image is written to the image data
+    /// section, which is needed for the version info to match.
+    fn has_merged_image_data(&self) -> bool {
+        self.color_mode == ColorMode::Multichannel || self.preview_image.is_some()
+    }
+
+    /// Returns the swatches in the format used by Photoshop’s swatches
+    /// panel. Photoshop documents have no resource for swatches, so
+    /// these are meant to be saved alongside as an `.aco` file.
+    pub fn swatches_data(&self) -> error::Result<Vec<u8>> {
+        Ok(swatches::data(&self.swatches)?)
+    }
+
+    /// Returns the layers from the bottom up, which is the order
+    /// they’re stored in the file.
+    fn bottom_to_top_layers(&self) -> Cow<'_, [Layer]> {
+        match self.layer_order {
+            LayerOrder::BottomToTop => Cow::Borrowed(&self.layers),
+            LayerOrder::TopToBottom => Cow::Owned(reversed_layers(&self.layers)),
+        }
+    }
+
+    /// Returns the colour mode written to the header. Modes that
+    /// can’t be written yet fall back to RGB.
+    fn written_color_mode(&self) -> ColorMode {
+        match self.color_mode {
+            ColorMode::Multichannel => ColorMode::Multichannel,
+            ColorMode::Duotone => ColorMode::Duotone,
+            ColorMode::Indexed => ColorMode::Indexed,
+            _ => ColorMode::Rgb,
+        }
+    }
+
+    /// Returns the image data for the independent channels
+    /// of a multichannel document.
+    fn multichannel_data(&self, compression: &ImageCompression) -> anyhow::Result<Vec<u8>> {
+        let data_length = (self.size.width * self.size.height) as usize;
+        let mut channels = Vec::new();
+        for channel in self.channels.iter() {
+            if channel.data.len() != data_length {
+                anyhow::bail!(WriteError::InvalidImage);
+            }
+            let mut color_channel = ColorChannel::new(ColorChannelType::Unknown, data_length);
+            color_channel.data = channel.data.clone();
+            channels.push(color_channel);
+        }
+        image::channels_psd_data(&channels, self.size.height, compression)
+    }
+}
+
+/// Returns the layers for which the predicate returns `true`,
+/// filtering the child layers of groups in the same way.
+fn filtered_layers(layers: &[Layer], predicate: &impl Fn(&Layer) -> bool) -> Vec<Layer> {
+    layers
+        .iter()
+        .filter(|layer| predicate(layer))
+        .map(|layer| {
+            let mut layer = layer.clone();
+            if let LayerType::Group(info) = &mut layer.layer_type {
+                info.layers = filtered_layers(&info.layers, predicate);
+            }
+            layer
+        })
+        .collect()
+}
+
+/// Returns the layers in reverse order, reversing the child
+/// layers of groups too.
+fn reversed_layers(layers: &[Layer]) -> Vec<Layer> {
+    layers
+        .iter()
+        .rev()
+        .map(|layer| {
+            let mut layer = layer.clone();
+            if let LayerType::Group(info) = &mut layer.layer_type {
+                info.layers = reversed_layers(&info.layers);
+            }
+            layer
+        })
+        .collect()
+}
+
+/// Returns the data for the image resources section, made of the
+/// length of the resources followed by the resources in order.
+fn image_resources_section_data(resources: &[&[u8]]) -> anyhow::Result<Vec<u8>> {
+    let length = resources.iter().map(|data| data.len()).sum();
+    let mut file_stream = FileStreamWriter::new();
+    file_stream.write_be(&data::section_length(length)?)?;
+    for data in resources {
+        file_stream.write_bytes(data)?;
+    }
+    Ok(file_stream.data().to_vec())
+}
+
+/// Returns the colour table for an indexed document: 256 red values,
+/// then 256 green values, then 256 blue values. Unused entries are black.
+fn palette_data(palette: &[Color]) -> anyhow::Result<Vec<u8>> {
+    if palette.len() > constants::PALETTE_SIZE {
+        anyhow::bail!(WriteError::PaletteTooLarge(palette.len()));
+    }
+
+    let mut data = vec![0; constants::PALETTE_SIZE * 3];
+    for (index, color) in palette.iter().enumerate() {
+        data[index] = color.red;
+        data[constants::PALETTE_SIZE + index] = color.green;
+        data[constants::PALETTE_SIZE * 2 + index] = color.blue;
+    }
+    Ok(data)
+}
+
+/// Removes the transparent layers, including those in groups.
+fn prune_empty_layers(layers: &mut Vec<Layer>, removes_empty_groups: bool) {
+    layers.retain_mut(|layer| {
+        if let LayerType::Group(info) = &mut layer.layer_type {
+            prune_empty_layers(&mut info.layers, removes_empty_groups);
+            return !(removes_empty_groups && info.layers.is_empty());
+        }
+        !layer.is_transparent()
+    });
 }
 
 // MARK: Layer container metods
@@ -195,6 +1196,9 @@ mod tests {
 
     use graphics::Color;
 
+    use crate::error::Error;
+    use crate::reader::Reader;
+
     use super::*;
 
     #[test]
@@ -209,11 +1213,10 @@ mod tests {
         let mut layer_0 = Layer::new(layer_0_bounds);
         layer_0.name = Some("Yellow".to_string());
         let yellow_image = Image::color(&Color::YELLOW, layer_0_bounds.size.into());
-        layer_0.image = Some(yellow_image.clone());
-
-        document.preview_image = Some(yellow_image);
+        layer_0.image = Some(yellow_image);
 
         document.layers = vec![layer_0];
+        document.set_preview_from(|_| true);
 
         let data = document.file_data().unwrap();
 
@@ -293,20 +1296,20 @@ mod tests {
         // Header
         assert_eq!(data[0..30], expected_data[0..30]);
         // Image resources
-        assert_eq!(data[30..92], expected_data[30..92]);
+        assert_eq!(data[30..134], expected_data[30..134]);
         // Layer and mask info length
-        assert_eq!(data[92..96], expected_data[92..96]);
+        assert_eq!(data[134..138], expected_data[134..138]);
         // Layer info length
-        assert_eq!(data[96..100], expected_data[96..100]);
+        assert_eq!(data[138..142], expected_data[138..142]);
 
         // And the rest…
         assert_eq!(data, expected_data);
     }
 
     #[test]
-    fn file_data_with_group() {
+    fn file_data_selected_layer() {
         let image = Image::color(
-            &Color::MAGENTA,
+            &Color::CYAN,
             Size {
                 width: 2,
                 height: 2,
@@ -321,25 +1324,1412 @@ mod tests {
             size: image.size.into(),
         };
         let mut layer_0 = Layer::new(bounds);
-        layer_0.name = Some("Background".to_string());
         layer_0.image = Some(image.clone());
+        let layer_1 = Layer::new(bounds);
 
-        let mut layer_1 = Layer::new(bounds);
-        layer_1.name = Some("Empty".to_string());
+        document.layers = vec![layer_0, layer_1];
+        document.selected_layer = 1;
 
-        let mut group = Layer::group(vec![layer_0, layer_1], true);
-        group.name = Some("Group".to_string());
+        let data = document.file_data().unwrap();
 
-        document.layers = vec![group];
-        document.preview_image = Some(image.clone());
+        // Layer state resource identifier.
+        assert_eq!(data[66..68], [0x04, 0x00]);
+        // Selected layer index.
+        assert_eq!(data[74..76], [0x00, 0x01]);
+
+        document.selected_layer = 2;
+        let error = document.file_data().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Write(WriteError::InvalidLayerIndex(2))
+        ));
+    }
+
+    #[test]
+    fn file_data_version_info() {
+        let mut document = Document::from_image(Image::color(
+            &Color::CYAN,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        ));
+        document.writer_name = "Pixels".to_string();
 
         let data = document.file_data().unwrap();
 
-        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        path.push("tests/resources/simple.psd");
-        let expected_data = std::fs::read(path).unwrap();
+        // Version info resource identifier, after the resolution info,
+        // layer state and layers group information.
+        assert_eq!(data[94..96], [0x04, 0x21]);
+        // Version, and whether there is merged data.
+        assert_eq!(data[102..107], [0x00, 0x00, 0x00, 0x01, 0x01]);
+        // The writer name, in UTF-16.
+        assert_eq!(
+            data[107..123],
+            [
+                0x00, 0x00, 0x00, 0x06, 0x00, 0x50, 0x00, 0x69, 0x00, 0x78, 0x00, 0x65, 0x00, 0x6c,
+                0x00, 0x73
+            ]
+        );
+
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.writer_name, "Pixels");
+        assert_eq!(result.reader_name, "psd");
+    }
+
+    #[test]
+    fn file_data_print_settings() {
+        let mut document = Document::from_image(Image::color(
+            &Color::CYAN,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        ));
+        let data_without_print_settings = document.file_data().unwrap();
+
+        document.print_settings = Some(PrintSettings {
+            scale: 1.0,
+            prints_labels: true,
+            ..Default::default()
+        });
+        let data = document.file_data().unwrap();
+
+        // The print flags, print scale and print flags information resources.
+        assert_eq!(
+            data.len(),
+            data_without_print_settings.len() + (12 + 10) + (12 + 14) + (12 + 10)
+        );
+        // The print scale resource follows the print flags, after the
+        // version info that ends at 132.
+        assert_eq!(data[132..138], [0x38, 0x42, 0x49, 0x4d, 0x03, 0xf3]);
+        assert_eq!(data[154..160], [0x38, 0x42, 0x49, 0x4d, 0x04, 0x26]);
+        // A length of 14, a centred style, a position of zero and a scale of 1.
+        assert_eq!(
+            data[162..180],
+            [
+                0x00, 0x00, 0x00, 0x0e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x3f, 0x80, 0x00, 0x00
+            ]
+        );
 
-        std::fs::write("/tmp/simple-with-group.psd", &data).unwrap();
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.print_settings, document.print_settings);
+        assert!(result.extra_resources.is_empty());
+    }
+
+    #[test]
+    fn header_data() {
+        let mut document = Document::from_image(Image::color(
+            &Color::CYAN,
+            Size {
+                width: 3,
+                height: 2,
+            },
+        ));
+        document.number_of_channels = 3;
+
+        let data = document.header_data(FileFormat::Psb).unwrap();
+
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.read_array::<4>().unwrap(), constants::FILE_SIGNATURE);
+        assert_eq!(
+            reader.read_i16().unwrap(),
+            constants::LARGE_DOCUMENT_VERSION_NUMBER
+        );
+        assert_eq!(reader.read_array::<6>().unwrap(), [0; 6]);
+        assert_eq!(reader.read_u16().unwrap(), 3);
+        assert_eq!(reader.read_u32().unwrap(), 2);
+        assert_eq!(reader.read_u32().unwrap(), 3);
+        assert_eq!(reader.read_u16().unwrap(), 8);
+        assert_eq!(reader.read_i16().unwrap(), ColorMode::Rgb.raw_value());
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn rgb() {
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        let mut document = Document::rgb(size);
+        assert_eq!(document.color_mode, ColorMode::Rgb);
+        assert_eq!(document.bits_per_channel, 8);
+        assert_eq!(document.number_of_channels, 4);
+
+        let mut layer = Layer::new(Rect::new(0, 0, 2, 2));
+        layer.image = Some(Image::color(&Color::RED, size));
+        document.layers.push(layer);
+
+        let data = document.file_data().unwrap();
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.size, size);
+        assert_eq!(result.color_mode, ColorMode::Rgb);
+        assert_eq!(result.number_of_layers(), 1);
+    }
+
+    #[test]
+    fn set_color_mode() {
+        let mut document = Document::new();
+        document.set_color_mode(ColorMode::Grayscale);
+        assert_eq!(document.color_mode, ColorMode::Grayscale);
+        assert_eq!(document.number_of_channels, 1);
+
+        document.set_color_mode(ColorMode::Cmyk);
+        assert_eq!(document.number_of_channels, 4);
+
+        // Multichannel documents keep the count they had.
+        document.set_color_mode(ColorMode::Multichannel);
+        assert_eq!(document.number_of_channels, 4);
+    }
+
+    #[test]
+    fn file_data_with_color_modes() {
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        let color_modes = [
+            ColorMode::Bitmap,
+            ColorMode::Grayscale,
+            ColorMode::Indexed,
+            ColorMode::Rgb,
+            ColorMode::Cmyk,
+            ColorMode::Multichannel,
+            ColorMode::Duotone,
+            ColorMode::Lab,
+        ];
+
+        for color_mode in color_modes {
+            let mut document = Document::from_image(Image::color(&Color::WHITE, size));
+            document.set_color_mode(color_mode.clone());
+            if color_mode == ColorMode::Indexed {
+                document.layers.clear();
+            }
+            document.palette = Some(vec![Color::WHITE]);
+            document.duotone_specification = Some(vec![0x00, 0x01]);
+            document.channels = vec![NamedChannel {
+                name: "Spot".to_string(),
+                data: vec![0xff; 4],
+            }];
+
+            let data = document.file_data().unwrap();
+            let result = Document::from_data(&data).unwrap();
+
+            assert_eq!(result.color_mode, document.written_color_mode());
+            assert_eq!(
+                result.number_of_channels,
+                document.written_number_of_channels()
+            );
+            if color_mode != ColorMode::Multichannel {
+                let merged_image = Document::decode_merged_image(&data).unwrap();
+                assert_eq!(merged_image.data, vec![0xff; 16]);
+            }
+        }
+    }
+
+    #[test]
+    fn file_data_indexed() {
+        let size = Size {
+            width: 3,
+            height: 1,
+        };
+        let mut image = Image::color(&Color::RED, size);
+        // A blue pixel, and a pixel closest to red.
+        image.data[4..12].copy_from_slice(&[0x00, 0x00, 0xff, 0xff, 0xf0, 0x10, 0x00, 0xff]);
+        let mut document = Document::new();
+        document.size = size;
+        document.set_color_mode(ColorMode::Indexed);
+        document.palette = Some(vec![Color::RED, Color::BLUE]);
+        document.preview_image = Some(image);
+
+        let data = document.file_data().unwrap();
+
+        // One channel in the indexed colour mode.
+        assert_eq!(data[12..14], [0x00, 0x01]);
+        assert_eq!(data[24..26], [0x00, 0x02]);
+        // The raw indices of the pixels into the palette.
+        let data = document
+            .file_data_with_compression(ImageCompression::RawData)
+            .unwrap();
+        assert_eq!(data[data.len() - 3..], [0x00, 0x01, 0x00]);
+
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.color_mode, ColorMode::Indexed);
+        assert_eq!(result.palette.unwrap()[0..2], [Color::RED, Color::BLUE]);
+        let mut expected_image = Image::color(&Color::RED, size);
+        expected_image.data[4..8].copy_from_slice(&[0x00, 0x00, 0xff, 0xff]);
+        assert_eq!(result.preview_image, Some(expected_image.clone()));
+        assert_eq!(
+            Document::decode_merged_image(&data).unwrap(),
+            expected_image
+        );
+
+        // Photoshop doesn’t allow layers in indexed documents.
+        document.layers = vec![Layer::new(Rect::new(0, 0, 3, 1))];
+        let error = document.file_data().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Write(WriteError::LayersInIndexedDocument)
+        ));
+    }
+
+    #[test]
+    fn color_mode_data() {
+        let mut document = Document::new();
+        document.color_mode = ColorMode::Rgb;
+        assert_eq!(
+            document.color_mode_data().unwrap(),
+            [0x00, 0x00, 0x00, 0x00]
+        );
+
+        document.color_mode = ColorMode::Indexed;
+        let error = document.color_mode_data().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<WriteError>(),
+            Some(WriteError::MissingColorModeData)
+        ));
+
+        document.palette = Some(vec![Color::from_rgb_u32(0x24a4ee), Color::WHITE]);
+        let data = document.color_mode_data().unwrap();
+        assert_eq!(data.len(), 4 + 768);
+        assert_eq!(data[0..4], [0x00, 0x00, 0x03, 0x00]);
+        // The reds, greens and blues, with black for the unused entries.
+        assert_eq!(data[4..7], [0x24, 0xff, 0x00]);
+        assert_eq!(data[260..263], [0xa4, 0xff, 0x00]);
+        assert_eq!(data[516..519], [0xee, 0xff, 0x00]);
+
+        document.palette = Some(vec![Color::WHITE; 257]);
+        let error = document.color_mode_data().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<WriteError>(),
+            Some(WriteError::PaletteTooLarge(257))
+        ));
+    }
+
+    #[test]
+    fn file_data_global_light() {
+        let mut document = Document::from_image(Image::color(
+            &Color::CYAN,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        ));
+        document.global_light = Some(GlobalLight::new(120, 30));
+
+        let data = document.file_data().unwrap();
+
+        // The global angle resource, after the version info that ends at 132.
+        let angle_data = [
+            0x38, 0x42, 0x49, 0x4d, // 8BIM
+            0x04, 0x19, // Identifier
+            0x00, 0x00, // Name
+            0x00, 0x00, 0x00, 0x04, // Length
+            0x00, 0x00, 0x00, 0x78, // Angle
+        ];
+        assert_eq!(data[132..148], angle_data);
+        // The global altitude resource.
+        assert_eq!(data[152..154], [0x04, 0x2b]);
+        assert_eq!(data[160..164], [0x00, 0x00, 0x00, 0x1e]);
+
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.global_light, document.global_light);
+    }
+
+    #[test]
+    fn resource_accessors() {
+        let mut document = Document::from_image(Image::color(
+            &Color::CYAN,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        ));
+        document.resolution_info = ResolutionInfo::new(300.0, ResolutionUnit::PixelsPerCentimeter);
+        let guides = [
+            Guide::new(1.0, GuideDirection::Vertical),
+            Guide::new(0.5, GuideDirection::Horizontal),
+        ];
+        document.set_guides(&guides).unwrap();
+
+        let data = document.file_data().unwrap();
+        let result = Document::from_data(&data).unwrap();
+
+        assert_eq!(result.resolution(), document.resolution_info);
+        assert_eq!(result.guides().unwrap(), guides);
+        assert_eq!(result.icc_profile(), None);
+    }
+
+    #[test]
+    fn file_data_mask_overlay() {
+        let mut document = Document::rgb(Size {
+            width: 2,
+            height: 2,
+        });
+        document.mask_overlay = Some(MaskOverlay::new(Color::RED, 50));
+
+        let data = document
+            .layer_and_mask_information_data(FileFormat::Psd, &ImageCompression::Rle)
+            .unwrap();
+
+        let global_layer_mask_info_data = [
+            0x00, 0x00, 0x00, 0x0e, // Length
+            0x00, 0x00, // Colour space
+            0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Colour components
+            0x00, 0x32, // Opacity
+            0x80, // Kind
+            0x00, // Filler
+        ];
+        assert_eq!(data[data.len() - 18..], global_layer_mask_info_data);
+
+        let result = Document::from_data(&document.file_data().unwrap()).unwrap();
+        assert_eq!(result.mask_overlay, document.mask_overlay);
+    }
+
+    #[test]
+    fn file_data_layer_id_generator_base() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut document = Document::rgb(bounds.size.into());
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.id = Some(1);
+        let mut layer_1 = Layer::new(bounds);
+        layer_1.id = Some(2);
+        document.layers = vec![layer_0, layer_1];
+
+        let data = document.file_data().unwrap();
+
+        // 8BIM, the identifier, an empty name and a length of 4.
+        let resource_header = [
+            0x38, 0x42, 0x49, 0x4d, 0x04, 0x3e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04,
+        ];
+        let position = data
+            .windows(resource_header.len())
+            .position(|window| window == resource_header)
+            .unwrap();
+        assert_eq!(data[position + 12..position + 16], [0x00, 0x00, 0x00, 0x03]);
+
+        let result = Document::from_data(&data).unwrap();
+        assert!(result.extra_resources.is_empty());
+        assert_eq!(result.layers[1].id, Some(2));
+    }
+
+    #[test]
+    fn file_data_mismatched_preview() {
+        let mut document = Document::rgb(Size {
+            width: 4,
+            height: 2,
+        });
+        document.preview_image = Some(Image::color(
+            &Color::YELLOW,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        ));
+
+        let error = document.file_data().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Write(WriteError::PreviewSizeMismatch(2, 2))
+        ));
+    }
+
+    #[test]
+    fn file_data_slices() {
+        let mut document = Document::rgb(Size {
+            width: 8,
+            height: 6,
+        });
+        let mut slice = Slice::new("A", Rect::new(1, 2, 3, 4));
+        slice.url = "b".to_string();
+        document.slices = vec![slice.clone()];
+
+        let data = document.file_data().unwrap();
+
+        // 8BIM, the identifier and an empty name, then the length and
+        // the slice data.
+        let resource_header = [0x38, 0x42, 0x49, 0x4d, 0x04, 0x1a, 0x00, 0x00];
+        let position = data
+            .windows(resource_header.len())
+            .position(|window| window == resource_header)
+            .unwrap();
+        let expected_data = slice::data(&[slice], document.size).unwrap();
+        let length = u32::from_be_bytes(data[position + 8..position + 12].try_into().unwrap());
+        assert_eq!(length as usize, expected_data.len());
+        assert_eq!(
+            data[position + 12..position + 12 + expected_data.len()],
+            expected_data
+        );
+    }
+
+    #[test]
+    fn file_data_layer_comps() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut document = Document::rgb(bounds.size.into());
+        let mut layer = Layer::new(bounds);
+        layer.id = Some(7);
+        document.layers = vec![layer];
+        let mut layer_comp = LayerComp::new(1, "A");
+        layer_comp.captures_visibility = true;
+        layer_comp.visibility.insert(7, true);
+        document.layer_comps = vec![layer_comp];
+
+        let data = document.file_data().unwrap();
+
+        // 8BIM, the identifier, an empty name and a length of 116.
+        let resource_header = [
+            0x38, 0x42, 0x49, 0x4d, 0x04, 0x29, 0x00, 0x00, 0x00, 0x00, 0x00, 0x74,
+        ];
+        let position = data
+            .windows(resource_header.len())
+            .position(|window| window == resource_header)
+            .unwrap();
+        let expected_data = [
+            0x00, 0x00, 0x00, 0x10, // Descriptor version
+            0x00, 0x00, 0x00, 0x00, // Empty name
+            0x00, 0x00, 0x00, 0x00, 0x6e, 0x75, 0x6c, 0x6c, // null
+            0x00, 0x00, 0x00, 0x01, // Number of items
+            0x00, 0x00, 0x00, 0x00, 0x6c, 0x69, 0x73, 0x74, // list
+            0x56, 0x6c, 0x4c, 0x73, 0x00, 0x00, 0x00, 0x01, // VlLs with one comp
+            0x4f, 0x62, 0x6a, 0x63, 0x00, 0x00, 0x00, 0x00, // Objc with an empty name
+            0x00, 0x00, 0x00, 0x00, 0x43, 0x6f, 0x6d, 0x70, // Comp
+            0x00, 0x00, 0x00, 0x03, // Number of items
+            0x00, 0x00, 0x00, 0x00, 0x4e, 0x6d, 0x20, 0x20, // Nm
+            0x54, 0x45, 0x58, 0x54, 0x00, 0x00, 0x00, 0x01, 0x00, 0x41, // TEXT A
+            0x00, 0x00, 0x00, 0x06, 0x63, 0x6f, 0x6d, 0x70, 0x49, 0x44, // compID
+            0x6c, 0x6f, 0x6e, 0x67, 0x00, 0x00, 0x00, 0x01, // long 1
+            0x00, 0x00, 0x00, 0x0c, 0x63, 0x61, 0x70, 0x74, 0x75, 0x72, 0x65, 0x64, 0x49, 0x6e,
+            0x66, 0x6f, // capturedInfo
+            0x6c, 0x6f, 0x6e, 0x67, 0x00, 0x00, 0x00, 0x01, // long 1, for the visibility
+        ];
+        assert_eq!(data[position + 12..position + 12 + 116], expected_data);
+
+        // The layer’s visibility is written with the layer, but isn’t
+        // kept with it for later exports.
+        assert!(data.windows(8).any(|window| window == b"8BIMcmls"));
+        assert_eq!(document.file_data().unwrap(), data);
+    }
+
+    #[test]
+    fn file_data_opaque_background() {
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        let mut document = Document::rgb(size);
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(Image::color(&Color::CLEAR, size));
+        document.layers = vec![layer];
+        document.preview_image = Some(Image::color(&Color::CLEAR, size));
+        document.opaque_background = Some(Color::RED);
+
+        // The number of channels in the header.
+        let header_data = document.header_data(FileFormat::Psd).unwrap();
+        assert_eq!(header_data[12..14], [0x00, 0x03]);
+
+        // The compression, a line length for each row of three channels,
+        // then each row of two repeated bytes.
+        let image_data = document
+            .image_data(FileFormat::Psd, &ImageCompression::Rle)
+            .unwrap();
+        assert_eq!(image_data.len(), 2 + 3 * 2 * 2 + 3 * 2 * 3);
+
+        let data = document.file_data().unwrap();
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.preview_image, Some(Image::color(&Color::RED, size)));
+        // The layer keeps its transparency.
+        assert_eq!(
+            result.layers[0].image,
+            Some(Image::color(&Color::CLEAR, size))
+        );
+    }
+
+    #[test]
+    fn file_data_minimal() {
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        let mut document = Document::from_image(Image::color(&Color::CYAN, size));
+
+        let data = document.file_data_minimal().unwrap();
+
+        // The header, the empty colour mode data, then the empty
+        // image resources.
+        assert_eq!(data[26..34], [0x00; 8]);
+        assert!(crate::validate_bytes(&data).is_ok());
+
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.layers.len(), 1);
+        assert_eq!(result.preview_image, Some(Image::color(&Color::CYAN, size)));
+    }
+
+    #[test]
+    fn file_data_pixel_aspect_ratio() {
+        let mut document = Document::from_image(Image::color(
+            &Color::CYAN,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        ));
+        document.pixel_aspect_ratio = Some(1.333);
+
+        let data = document.file_data().unwrap();
+
+        // The pixel aspect ratio resource, after the version info that ends at 132.
+        let pixel_aspect_ratio_data = [
+            0x38, 0x42, 0x49, 0x4d, // 8BIM
+            0x04, 0x28, // Identifier
+            0x00, 0x00, // Name
+            0x00, 0x00, 0x00, 0x0c, // Length
+            0x00, 0x00, 0x00, 0x02, // Version
+            0x3f, 0xf5, 0x53, 0xf7, 0xce, 0xd9, 0x16, 0x87, // 1.333
+        ];
+        assert_eq!(data[132..156], pixel_aspect_ratio_data);
+
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.pixel_aspect_ratio, Some(1.333));
+    }
+
+    #[test]
+    fn file_data_with_srgb_profile() {
+        let mut document = Document::from_image(Image::color(
+            &Color::RED,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        ));
+        let description = b"sRGB IEC61966-2.1";
+        let contains =
+            |data: &[u8], bytes: &[u8]| data.windows(bytes.len()).any(|window| window == bytes);
+        assert!(!contains(&document.file_data().unwrap(), description));
+
+        document.embeds_srgb_profile = true;
+        let data = document.file_data().unwrap();
+        // 8BIM, the colour profile identifier and an empty name.
+        assert!(contains(
+            &data,
+            &[0x38, 0x42, 0x49, 0x4d, 0x04, 0x0f, 0x00, 0x00]
+        ));
+        assert!(contains(&data, description));
+
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.color_profile, Some(srgb_profile::data().unwrap()));
+    }
+
+    #[test]
+    fn file_data_has_real_merged_data() {
+        let mut document = Document::from_image(Image::color(
+            &Color::CYAN,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        ));
+
+        // The flag follows the version of the version info resource.
+        let data = document.file_data().unwrap();
+        assert_eq!(data[94..96], [0x04, 0x21]);
+        assert_eq!(data[106], 0x01);
+        assert!(!document
+            .image_data(FileFormat::Psd, &ImageCompression::Rle)
+            .unwrap()
+            .is_empty());
+
+        document.preview_image = None;
+        let data = document.file_data().unwrap();
+        assert_eq!(data[94..96], [0x04, 0x21]);
+        assert_eq!(data[106], 0x00);
+        assert!(document
+            .image_data(FileFormat::Psd, &ImageCompression::Rle)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn file_data_filtered() {
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: size.into(),
+        };
+
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.name = Some("Keep".to_string());
+        layer_0.image = Some(Image::color(&Color::CYAN, size));
+        let mut layer_1 = Layer::new(bounds);
+        layer_1.name = Some("Skip".to_string());
+        let mut layer_2 = Layer::new(bounds);
+        layer_2.name = Some("Grouped".to_string());
+        let mut group = Layer::group(vec![layer_2], true);
+        group.name = Some("Skip".to_string());
+
+        let mut document = Document::new();
+        document.size = size;
+        document.layers = vec![layer_0, layer_1, group];
+        document.selected_layer = 3;
+
+        let is_kept = |layer: &Layer| layer.name != Some("Skip".to_string());
+        let data = document.file_data_filtered(is_kept).unwrap();
+
+        // The document is left as it was.
+        assert_eq!(document.layers.len(), 3);
+        assert_eq!(document.selected_layer, 3);
+
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.layers.len(), 1);
+        assert_eq!(result.number_of_layers(), 1);
+        assert_eq!(result.layers[0].name, Some("Keep".to_string()));
+        assert_eq!(result.selected_layer, 0);
+    }
+
+    #[test]
+    fn from_image() {
+        let image = Image::color(
+            &Color::MAGENTA,
+            Size {
+                width: 3,
+                height: 2,
+            },
+        );
+
+        let mut document = Document::from_image(image.clone());
+
+        assert_eq!(document.size, image.size);
+        assert_eq!(document.layers.len(), 1);
+        assert_eq!(document.layers[0].name, Some("Layer 1".to_string()));
+        assert_eq!(document.layers[0].bounds, Rect::new(0, 0, 3, 2));
+        assert_eq!(document.layers[0].image, Some(image.clone()));
+        assert_eq!(document.preview_image, Some(image));
+        assert!(document.file_data().is_ok());
+    }
+
+    #[test]
+    fn from_atlas() {
+        // Four columns of colour.
+        let mut image = Image::empty(Size {
+            width: 4,
+            height: 2,
+        });
+        let colors = [Color::RED, Color::GREEN, Color::BLUE, Color::YELLOW];
+        for y_position in 0..2 {
+            for (x_position, color) in colors.iter().enumerate() {
+                let index = y_position * 16 + x_position * 4;
+                image.data[index..index + 4].copy_from_slice(&[
+                    color.red,
+                    color.green,
+                    color.blue,
+                    color.alpha,
+                ]);
+            }
+        }
+        let regions = vec![
+            ("Red".to_string(), Rect::new(0, 0, 1, 2)),
+            ("Green and blue".to_string(), Rect::new(1, 1, 2, 1)),
+            ("Yellow".to_string(), Rect::new(3, 0, 4, 4)),
+        ];
+
+        let document = Document::from_atlas(image, &regions);
+
+        assert_eq!(document.layers.len(), 3);
+        let red_layer = &document.layers[0];
+        assert_eq!(red_layer.name, Some("Red".to_string()));
+        assert_eq!(red_layer.bounds, Rect::new(0, 0, 1, 2));
+        assert_eq!(
+            red_layer.image,
+            Some(Image::color(
+                &Color::RED,
+                Size {
+                    width: 1,
+                    height: 2,
+                }
+            ))
+        );
+
+        let green_and_blue_layer = &document.layers[1];
+        assert_eq!(green_and_blue_layer.bounds, Rect::new(1, 1, 2, 1));
+        assert_eq!(
+            green_and_blue_layer.image.as_ref().unwrap().data,
+            vec![0x00, 0xff, 0x00, 0xff, 0x00, 0x00, 0xff, 0xff]
+        );
+
+        // Clipped to the image.
+        let yellow_layer = &document.layers[2];
+        assert_eq!(yellow_layer.bounds, Rect::new(3, 0, 1, 2));
+
+        // The first row of the preview leaves out the green and blue.
+        let preview_image = document.preview_image.unwrap();
+        assert_eq!(
+            preview_image.data[0..16],
+            [
+                0xff, 0x00, 0x00, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff,
+                0x00, 0xff
+            ]
+        );
+    }
+
+    #[test]
+    fn prune_empty_layers() {
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: size.into(),
+        };
+
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.name = Some("Opaque".to_string());
+        layer_0.image = Some(Image::color(&Color::RED, size));
+        let mut layer_1 = Layer::new(bounds);
+        layer_1.image = Some(Image::color(&Color::CLEAR, size));
+        let group = Layer::group(vec![Layer::new(bounds)], true);
+
+        let mut document = Document::new();
+        document.size = size;
+        document.layers = vec![layer_0, layer_1, group];
+        document.selected_layer = 3;
+
+        let mut kept_groups_document = document.clone();
+        kept_groups_document.prune_empty_layers(false);
+        assert_eq!(kept_groups_document.layers.len(), 2);
+        assert_eq!(kept_groups_document.number_of_layers(), 3);
+
+        document.prune_empty_layers(true);
+        assert_eq!(document.layers.len(), 1);
+        assert_eq!(document.selected_layer, 0);
+
+        let data = document.file_data().unwrap();
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.layers.len(), 1);
+        assert_eq!(result.layers[0].name, Some("Opaque".to_string()));
+    }
+
+    #[test]
+    fn group_layers() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut document = Document::new();
+        for index in 0..4 {
+            let mut layer = Layer::new(bounds);
+            layer.name = Some(format!("Layer {index}"));
+            document.layers.push(layer);
+        }
+
+        document.group_layers(&[2, 0], "Group").unwrap();
+
+        let names: Vec<Option<String>> = document
+            .iter_with_depth()
+            .map(|(_, layer)| layer.name.clone())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                Some("Group".to_string()),
+                Some("Layer 0".to_string()),
+                Some("Layer 2".to_string()),
+                Some("Layer 1".to_string()),
+                Some("Layer 3".to_string()),
+            ]
+        );
+        assert_eq!(
+            document.layer_at_path(&[0, 1]).unwrap().name,
+            Some("Layer 2".to_string())
+        );
+
+        let error = document.group_layers(&[0, 3], "Group").unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Write(WriteError::InvalidLayerIndex(3))
+        ));
+        assert_eq!(document.layers.len(), 3);
+    }
+
+    #[test]
+    fn thumbnail() {
+        let size = Size {
+            width: 100,
+            height: 50,
+        };
+        let mut document = Document::from_image(Image::color(&Color::RED, size));
+        let mut layer = Layer::new(Rect::new(50, 0, 50, 50));
+        layer.image = Some(Image::color(
+            &Color::BLUE,
+            Size {
+                width: 50,
+                height: 50,
+            },
+        ));
+        document.layers.push(layer);
+
+        let result = document
+            .thumbnail(Size {
+                width: 32,
+                height: 32,
+            })
+            .unwrap();
+
+        assert_eq!(
+            result.size,
+            Size {
+                width: 32,
+                height: 16,
+            }
+        );
+        // The left half is red and the right half blue.
+        assert_eq!(result.data[0..4], [0xff, 0x00, 0x00, 0xff]);
+        let last_index = result.data.len() - 4;
+        assert_eq!(result.data[last_index..], [0x00, 0x00, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn set_preview_from() {
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        let mut document = Document::from_image(Image::color(&Color::RED, size));
+        let mut guide_layer = Layer::new(Rect::new(0, 0, 2, 2));
+        guide_layer.name = Some("Guides".to_string());
+        guide_layer.image = Some(Image::color(&Color::BLUE, size));
+        document.layers.push(guide_layer);
+
+        document.set_preview_from(|layer| layer.name.as_deref() != Some("Guides"));
+
+        assert_eq!(
+            document.preview_image,
+            Some(Image::color(&Color::RED, size))
+        );
+        assert_eq!(document.layers.len(), 2);
+    }
+
+    #[test]
+    fn set_preview_from_off_canvas_layer() {
+        let mut document = Document::rgb(Size {
+            width: 2,
+            height: 2,
+        });
+        // Only the top right pixel of the layer is on the canvas.
+        let bounds = Rect::new(1, -1, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(Image::color(&Color::BLUE, bounds.size.into()));
+        document.layers.push(layer);
+
+        document.set_preview_from(|_| true);
+
+        let preview_image = document.preview_image.clone().unwrap();
+        assert_eq!(preview_image.size, document.size);
+        assert_eq!(
+            preview_image.data,
+            [
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, // Top row
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Bottom row
+            ]
+        );
+
+        // The layer record keeps the full bounds.
+        let data = document.file_data().unwrap();
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.layers[0].bounds, bounds);
+    }
+
+    #[test]
+    fn layer_at_path() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut nested_layer = Layer::new(bounds);
+        nested_layer.name = Some("Nested".to_string());
+        let inner_group = Layer::group(vec![Layer::new(bounds), nested_layer], true);
+        let outer_group = Layer::group(vec![Layer::new(bounds), inner_group], true);
+
+        let mut document = Document::new();
+        document.layers = vec![outer_group, Layer::new(bounds)];
+
+        let layer = document.layer_at_path(&[0, 1, 1]).unwrap();
+        assert_eq!(layer.name, Some("Nested".to_string()));
+
+        document.layer_at_path_mut(&[0, 1, 1]).unwrap().opacity = 0x80;
+        assert_eq!(document.layer_at_path(&[0, 1, 1]).unwrap().opacity, 0x80);
+
+        // Out of range, descending into a layer that isn’t a group, and empty.
+        assert!(document.layer_at_path(&[0, 2]).is_none());
+        assert!(document.layer_at_path(&[1, 0]).is_none());
+        assert!(document.layer_at_path(&[]).is_none());
+        assert!(document.layer_at_path_mut(&[2]).is_none());
+    }
+
+    #[test]
+    fn file_data_compresses_once() {
+        let size = Size {
+            width: 3,
+            height: 2,
+        };
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: size.into(),
+        };
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.image = Some(Image::color(&Color::CYAN, size));
+        let mut layer_1 = Layer::new(bounds);
+        layer_1.image = Some(Image::color(&Color::MAGENTA, size));
+
+        let mut document = Document::new();
+        document.size = size;
+        document.layers = vec![layer_0, layer_1];
+
+        let count_before = crate::color_channel::RLE_ENCODED_DATA_COUNT.with(|count| count.get());
+        let data = document.file_data().unwrap();
+        let second_data = document.file_data().unwrap();
+        let count_after = crate::color_channel::RLE_ENCODED_DATA_COUNT.with(|count| count.get());
+
+        // Four channels for each of the two layers, which are kept
+        // for the second export.
+        assert_eq!(count_after - count_before, 8);
+        assert_eq!(second_data, data);
+    }
+
+    #[test]
+    fn layer_info_alignment() {
+        let size = Size {
+            width: 3,
+            height: 1,
+        };
+        let mut document = Document::rgb(size);
+        let mut layer = Layer::new(Rect::new(0, 0, 3, 1));
+        layer.image = Some(Image::color(&Color::RED, size));
+        document.layers.push(layer);
+
+        for alignment in [LayerInfoAlignment::TwoBytes, LayerInfoAlignment::FourBytes] {
+            document.layer_info_alignment = alignment;
+            let data = document.file_data().unwrap();
+
+            // Skip the header, colour mode data and image resources.
+            let read_length = |offset: usize| {
+                u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+            };
+            let mut offset = 26;
+            offset += 4 + read_length(offset);
+            offset += 4 + read_length(offset);
+
+            // The layer and mask information holds the layer information
+            // and the empty global layer mask information.
+            let section_length = read_length(offset);
+            let layer_info_length = read_length(offset + 4);
+            assert_eq!(section_length, 4 + layer_info_length + 4);
+            assert_eq!(layer_info_length % alignment.number_of_bytes(), 0);
+
+            let result = Document::from_data(&data).unwrap();
+            assert_eq!(result.layers.len(), 1);
+        }
+    }
+
+    #[test]
+    fn file_data_with_zero_area_image() {
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        let empty_size = Size {
+            width: 0,
+            height: 0,
+        };
+        let mut document = Document::rgb(size);
+        let mut layer_0 = Layer::new(Rect::new(0, 0, 2, 2));
+        layer_0.image = Some(Image::empty(empty_size));
+        // Layers without bounds cover the document.
+        let mut layer_1 = Layer::new(Rect::zero());
+        layer_1.image = Some(Image::empty(empty_size));
+        document.layers = vec![layer_0, layer_1];
+
+        let data = document.file_data().unwrap();
+        let result = Document::from_data(&data).unwrap();
+
+        assert_eq!(result.layers.len(), 2);
+        for layer in &result.layers {
+            assert_eq!(layer.bounds, Rect::new(0, 0, 2, 2));
+            assert_eq!(layer.image, Some(Image::empty(size)));
+        }
+    }
+
+    #[test]
+    fn layer_and_mask_information_data() {
+        let size = Size {
+            width: 3,
+            height: 2,
+        };
+        let mut document = Document::rgb(size);
+        let bounds = Rect::new(0, 0, 3, 2);
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.image = Some(Image::color(&Color::YELLOW, size));
+        let mut layer_1 = Layer::new(bounds);
+        layer_1.image = Some(Image::color(&Color::RED, size));
+        document.layers = vec![layer_0.clone(), layer_1.clone()];
+
+        let data = document
+            .layer_and_mask_information_data(FileFormat::Psd, &ImageCompression::Rle)
+            .unwrap();
+
+        // The records for both layers, followed by their images.
+        let mut layer_info_data = vec![0xff, 0xfe];
+        for layer in [&mut layer_0, &mut layer_1] {
+            layer_info_data.append(&mut layer.layer_record_data().unwrap());
+        }
+        for layer in [&mut layer_0, &mut layer_1] {
+            layer_info_data.append(&mut layer.encoded_image().unwrap());
+        }
+        data::pad(&mut layer_info_data, 2);
+
+        let mut expected_data = Vec::new();
+        expected_data.extend_from_slice(&(layer_info_data.len() as u32 + 8).to_be_bytes());
+        expected_data.extend_from_slice(&(layer_info_data.len() as u32).to_be_bytes());
+        expected_data.append(&mut layer_info_data);
+        expected_data.extend_from_slice(&[0, 0, 0, 0]);
+        assert_eq!(data, expected_data);
+    }
+
+    #[test]
+    fn file_data_too_many_layers() {
+        let size = Size {
+            width: 1,
+            height: 1,
+        };
+        let mut document = Document::rgb(size);
+        document.layers = vec![Layer::new(Rect::zero()); i16::MAX as usize + 1];
+
+        let error = document.file_data().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Write(WriteError::TooManyLayers(32768))
+        ));
+    }
+
+    #[test]
+    fn optimize() {
+        let size = Size {
+            width: 3,
+            height: 2,
+        };
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: size.into(),
+        };
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.image = Some(Image::color(&Color::CYAN, size));
+        let mut layer_1 = Layer::new(bounds);
+        layer_1.image = Some(Image::color(&Color::MAGENTA, size));
+
+        let mut document = Document::new();
+        document.size = size;
+        document.layers = vec![layer_0, layer_1];
+        let expected_data = document.clone().file_data().unwrap();
+
+        let count_before = crate::color_channel::RLE_ENCODED_DATA_COUNT.with(|count| count.get());
+        document.optimize().unwrap();
+        let data = document.file_data().unwrap();
+        let count_after = crate::color_channel::RLE_ENCODED_DATA_COUNT.with(|count| count.get());
+
+        // The eight channels are either empty or full, so only
+        // two of them are compressed.
+        assert_eq!(count_after - count_before, 2);
+        assert_eq!(data, expected_data);
+    }
+
+    #[test]
+    fn optimize_layer_without_bounds() {
+        let size = Size {
+            width: 3,
+            height: 2,
+        };
+        let mut layer = Layer::new(Rect::zero());
+        layer.image = Some(Image::color(&Color::CYAN, size));
+
+        let mut document = Document::new();
+        document.size = size;
+        document.layers = vec![layer, Layer::new(Rect::zero())];
+        let expected_data = document.clone().file_data().unwrap();
+
+        // The layers take the document’s bounds, as they do when written.
+        document.optimize().unwrap();
+        assert_eq!(document.layers[0].bounds, Rect::zero());
+        assert_eq!(document.file_data().unwrap(), expected_data);
+    }
+
+    #[test]
+    fn file_data_top_to_bottom() {
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: size.into(),
+        };
+        let named_layer = |name: &str| {
+            let mut layer = Layer::new(bounds);
+            layer.name = Some(name.to_string());
+            layer
+        };
+        let mut group = Layer::group(vec![named_layer("Upper"), named_layer("Lower")], true);
+        group.name = Some("Group".to_string());
+
+        let mut document = Document::new();
+        document.size = size;
+        document.layers = vec![named_layer("Top"), group, named_layer("Bottom")];
+        document.layer_order = LayerOrder::TopToBottom;
+
+        let data = document.file_data().unwrap();
+        let result = Document::from_data(&data).unwrap();
+
+        // Read back from the bottom up.
+        assert_eq!(result.layer_order, LayerOrder::BottomToTop);
+        let names: Vec<Option<String>> = result
+            .all_layers()
+            .iter()
+            .map(|layer| layer.name.clone())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                Some("Bottom".to_string()),
+                Some("Group".to_string()),
+                Some("Lower".to_string()),
+                Some("Upper".to_string()),
+                Some("Top".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn estimated_size() {
+        let size = Size {
+            width: 3,
+            height: 2,
+        };
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: size.into(),
+        };
+
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.image = Some(Image::color(&Color::CYAN, size));
+        let layer_1 = Layer::new(bounds);
+        let group = Layer::group(vec![layer_1], true);
+
+        let mut document = Document::new();
+        document.size = size;
+        document.layers = vec![layer_0, group];
+        document.preview_image = Some(Image::color(&Color::CYAN, size));
+
+        let estimated_size = document.estimated_size().unwrap();
+        assert_eq!(estimated_size, document.file_data().unwrap().len() as u64);
+    }
+
+    #[test]
+    fn file_data_multichannel() {
+        let mut document = Document::new();
+        document.size = Size {
+            width: 2,
+            height: 2,
+        };
+        document.color_mode = ColorMode::Multichannel;
+        document.channels = vec![
+            NamedChannel::new("Cyan", vec![0x11; 4]),
+            NamedChannel::new("Spot", vec![0x22; 4]),
+            NamedChannel::new("Varnish", vec![0x33; 4]),
+        ];
+
+        let data = document.file_data().unwrap();
+
+        // Number of channels.
+        assert_eq!(data[12..14], [0x00, 0x03]);
+        // Colour mode.
+        assert_eq!(data[24..26], [0x00, 0x07]);
+
+        // Alpha channel names resource.
+        let names = [
+            0x38, 0x42, 0x49, 0x4D, // 8BIM
+            0x03, 0xEE, // Identifier
+            0x00, 0x00, // Name
+            0x00, 0x00, 0x00, 0x12, // Length
+            0x04, 0x43, 0x79, 0x61, 0x6e, // Cyan
+            0x04, 0x53, 0x70, 0x6f, 0x74, // Spot
+            0x07, 0x56, 0x61, 0x72, 0x6e, 0x69, 0x73, 0x68, // Varnish
+        ];
+        assert!(data.windows(names.len()).any(|window| window == names));
+
+        // Image data: the compression, three channels of line lengths, then the rows.
+        let expected_image_data = vec![
+            0x00, 0x01, // RLE
+            0x00, 0x03, 0x00, 0x03, // Cyan line lengths
+            0x00, 0x03, 0x00, 0x03, // Spot line lengths
+            0x00, 0x03, 0x00, 0x03, // Varnish line lengths
+            0x01, 0x11, 0x11, 0x01, 0x11, 0x11, // Cyan
+            0x01, 0x22, 0x22, 0x01, 0x22, 0x22, // Spot
+            0x01, 0x33, 0x33, 0x01, 0x33, 0x33, // Varnish
+        ];
+        assert_eq!(data[data.len() - 32..], expected_image_data);
+    }
+
+    #[test]
+    fn file_data_duotone() {
+        let image = Image::color(
+            &Color::WHITE,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        );
+
+        let mut document = Document::new();
+        document.size = image.size;
+        document.color_mode = ColorMode::Duotone;
+        document.preview_image = Some(image);
+
+        let error = document.file_data().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Write(WriteError::MissingColorModeData)
+        ));
+
+        let duotone_specification = vec![0x00, 0x01, 0x00, 0x02, 0xde, 0xad, 0xbe, 0xef, 0x42];
+        document.duotone_specification = Some(duotone_specification.clone());
+
+        let data = document.file_data().unwrap();
+
+        // Number of channels.
+        assert_eq!(data[12..14], [0x00, 0x01]);
+        // Colour mode.
+        assert_eq!(data[24..26], [0x00, 0x08]);
+        // Colour mode data length.
+        assert_eq!(data[26..30], [0x00, 0x00, 0x00, 0x09]);
+        // Colour mode data.
+        assert_eq!(data[30..39], duotone_specification);
+
+        // A single grey channel of image data.
+        let expected_image_data = vec![
+            0x00, 0x01, // RLE
+            0x00, 0x03, 0x00, 0x03, // Line lengths
+            0x01, 0xff, 0xff, 0x01, 0xff, 0xff, // Grey
+        ];
+        assert_eq!(data[data.len() - 12..], expected_image_data);
+    }
+
+    #[test]
+    fn file_data_psb() {
+        let size = Size {
+            width: 40_000,
+            height: 1,
+        };
+        let mut document = Document::new();
+        document.size = size;
+
+        let mut layer = Layer::new(Rect::new(0, 0, 2, 2));
+        layer.name = Some("Layer".to_string());
+        document.layers = vec![layer];
+
+        // Too wide for a PSD.
+        let error = document.file_data().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Write(WriteError::InvalidDocumentSize(40_000, 1))
+        ));
+
+        let data = document.file_data_psb().unwrap();
+
+        // Version.
+        assert_eq!(data[4..6], [0x00, 0x02]);
+
+        // The layer and mask information length is 64-bit.
+        let image_resources_length = u32::from_be_bytes([data[30], data[31], data[32], data[33]]);
+        let offset = 34 + image_resources_length as usize;
+        let mut length_data = [0u8; 8];
+        length_data.copy_from_slice(&data[offset..offset + 8]);
+        let layer_and_mask_length = u64::from_be_bytes(length_data);
+        assert_eq!(data.len(), offset + 8 + layer_and_mask_length as usize);
+    }
+
+    #[test]
+    fn summary() {
+        let image = Image::color(
+            &Color::MAGENTA,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        );
+
+        let mut document = Document::new();
+        document.size = image.size;
+
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: image.size.into(),
+        };
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.name = Some("Background".to_string());
+        layer_0.image = Some(image.clone());
+
+        let mut layer_1 = Layer::new(bounds);
+        layer_1.name = Some("Empty".to_string());
+
+        let mut group = Layer::group(vec![layer_0, layer_1], true);
+        group.name = Some("Group".to_string());
+
+        document.layers = vec![group];
+        document.preview_image = Some(image.clone());
+
+        let summary = document.summary();
+
+        assert_eq!(summary.size, image.size);
+        assert_eq!(summary.color_mode, ColorMode::Bitmap);
+        assert_eq!(summary.bits_per_channel, 1);
+        // The group, its divider and the two child layers.
+        assert_eq!(summary.number_of_layers, 4);
+        assert_eq!(summary.number_of_groups, 1);
+        assert!(summary.has_preview_image);
+    }
+
+    #[test]
+    fn file_data_with_group() {
+        let image = Image::color(
+            &Color::MAGENTA,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        );
+
+        let mut document = Document::new();
+        document.size = image.size;
+
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: image.size.into(),
+        };
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.name = Some("Background".to_string());
+        layer_0.image = Some(image.clone());
+
+        let mut layer_1 = Layer::new(bounds);
+        layer_1.name = Some("Empty".to_string());
+
+        let mut group = Layer::group(vec![layer_0, layer_1], true);
+        group.name = Some("Group".to_string());
+
+        document.layers = vec![group];
+        document.preview_image = Some(image.clone());
+
+        let data = document.file_data().unwrap();
+
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("tests/resources/simple-with-group.psd");
+        let expected_data = std::fs::read(path).unwrap();
 
         assert_eq!(data, expected_data);
     }
@@ -0,0 +1,29 @@
+use graphics::Point;
+
+/// A point on a path, with the control points of the curves
+/// on either side of it. Positions are in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BezierKnot {
+    /// The control point for the curve coming into the knot.
+    pub preceding: Point<f64>,
+    /// The point the path passes through.
+    pub anchor: Point<f64>,
+    /// The control point for the curve leaving the knot.
+    pub leaving: Point<f64>,
+    /// Whether the control points move together, keeping the curve smooth.
+    pub is_linked: bool,
+}
+
+// MARK: Creation
+
+impl BezierKnot {
+    /// Creates a corner knot, with both control points on the anchor.
+    pub fn corner(anchor: Point<f64>) -> Self {
+        Self {
+            preceding: anchor,
+            anchor,
+            leaving: anchor,
+            is_linked: false,
+        }
+    }
+}
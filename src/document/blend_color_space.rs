@@ -0,0 +1,14 @@
+/// The colour space layers are blended in when they’re composited,
+/// which changes the result of blend modes such as multiply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendColorSpace {
+    /// The sRGB components are blended as they are, which is
+    /// Photoshop’s default.
+    #[default]
+    Srgb,
+    /// The components are blended in linear RGB, as with Photoshop’s
+    /// option to blend RGB colours using gamma 1.0.
+    Linear,
+    /// The colours are blended as Lab lightness, a and b components.
+    Lab,
+}
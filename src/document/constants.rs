@@ -9,5 +9,20 @@ pub const FILE_SIGNATURE: [u8; 4] = [0x38, 0x42, 0x50, 0x53]; // 8BPS
 /// The signature for various resources.
 pub const RESOURCE_SIGNATURE: [u8; 4] = [0x38, 0x42, 0x49, 0x4d]; // "8BIM"
 
-/// The version number of all PSDs (PSBs are version 2, but aren’t supported).
+/// The version number of all PSDs.
 pub const VERSION_NUMBER: i16 = 1;
+
+/// The version number of large documents (PSBs).
+pub const LARGE_DOCUMENT_VERSION_NUMBER: i16 = 2;
+
+/// The number of colours in the colour table of indexed documents.
+pub const PALETTE_SIZE: usize = 256;
+
+/// The version of the version info resource.
+pub const VERSION_INFO_VERSION: u32 = 1;
+
+/// The file version written to the version info resource.
+pub const VERSION_INFO_FILE_VERSION: u32 = 1;
+
+/// The version of the pixel aspect ratio resource.
+pub const PIXEL_ASPECT_RATIO_VERSION: u32 = 2;
@@ -1,11 +1,56 @@
 /// The resource identifier for the resolution info. [03ED]
 pub const RESOLUTION_INFORMATION: i16 = 0x03ED;
 
+/// The resource identifier for the alpha channel names. [03EE]
+pub const ALPHA_CHANNEL_NAMES: i16 = 0x03EE;
+
+/// The resource identifier for the print flags. [03F3]
+pub const PRINT_FLAGS: i16 = 0x03F3;
+
 /// The resource identifier for the layer state. [0400]
 pub const LAYER_STATE: i16 = 0x0400;
 
 /// The resource identifier for the layers group information. [0402]
 pub const LAYERS_GROUP_INFORMATION: i16 = 0x0402;
 
+/// The resource identifier for the grid and guides. [0408]
+pub const GRID_AND_GUIDES: i16 = 0x0408;
+
 /// The resource identifier for the colour profile. [040F]
 pub const COLOR_PROFILE: i16 = 0x040F;
+
+/// The resource identifier for the global angle. [0419]
+pub const GLOBAL_ANGLE: i16 = 0x0419;
+
+/// The resource identifier for the slices. [041A]
+pub const SLICES: i16 = 0x041A;
+
+/// The resource identifier for the version info. [0421]
+pub const VERSION_INFO: i16 = 0x0421;
+
+/// The resource identifier for the print scale. [0426]
+pub const PRINT_SCALE: i16 = 0x0426;
+
+/// The resource identifier for the pixel aspect ratio. [0428]
+pub const PIXEL_ASPECT_RATIO: i16 = 0x0428;
+
+/// The resource identifier for the layer comps. [0429]
+pub const LAYER_COMPS: i16 = 0x0429;
+
+/// The resource identifier for the global altitude. [042B]
+pub const GLOBAL_ALTITUDE: i16 = 0x042B;
+
+/// The resource identifier for the layer ID generator base. [043E]
+pub const LAYER_ID_GENERATOR_BASE: i16 = 0x043E;
+
+/// The resource identifier for the first path. [07D0]
+pub const FIRST_PATH_INFORMATION: i16 = 0x07D0;
+
+/// The resource identifier for the last path. [0BB6]
+pub const LAST_PATH_INFORMATION: i16 = 0x0BB6;
+
+/// The resource identifier for the name of the clipping path. [0BB7]
+pub const CLIPPING_PATH_NAME: i16 = 0x0BB7;
+
+/// The resource identifier for the print flags information. [2710]
+pub const PRINT_FLAGS_INFORMATION: i16 = 0x2710;
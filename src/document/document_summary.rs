@@ -0,0 +1,20 @@
+use graphics::Size;
+
+use crate::color_mode::ColorMode;
+
+/// A summary of a document, worked out without encoding anything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentSummary {
+    /// The size of the document in pixels.
+    pub size: Size<u32>,
+    /// The colour mode of the document.
+    pub color_mode: ColorMode,
+    /// The number of bits per channel.
+    pub bits_per_channel: u16,
+    /// The number of layer records, where groups count as two.
+    pub number_of_layers: usize,
+    /// The number of groups, including nested groups.
+    pub number_of_groups: usize,
+    /// Whether the document has a preview image.
+    pub has_preview_image: bool,
+}
@@ -0,0 +1,123 @@
+use file_stream::write::FileStreamWriter;
+use graphics::Image;
+
+use crate::error;
+use crate::file_format::FileFormat;
+use crate::image_compression::ImageCompression;
+use crate::layer::Layer;
+
+use super::Document;
+
+/// A document with the sections that don’t depend on the layers
+/// already written, so that many files can be exported from it
+/// without rebuilding those sections each time.
+#[derive(Debug, Clone)]
+pub struct DocumentTemplate {
+    /// The document the sections were written from, without its
+    /// layers, preview image or larger resources.
+    document: Document,
+    /// The data for the header section.
+    header_data: Vec<u8>,
+    /// The data for the colour mode data section.
+    color_mode_data: Vec<u8>,
+    /// The image resources written before the layer resources.
+    leading_resources_data: Vec<u8>,
+    /// The image resources written after the layer resources.
+    trailing_resources_data: Vec<u8>,
+}
+
+impl DocumentTemplate {
+    /// Creates a template from a document. The document’s layers and
+    /// preview image are left out, as they’re given to each export.
+    pub fn new(document: Document) -> error::Result<Self> {
+        let mut document = document;
+        document.layers = Vec::new();
+        document.preview_image = None;
+        document.validate_size(FileFormat::Psd)?;
+
+        let header_data = document.header_data(FileFormat::Psd)?;
+        let color_mode_data = document.color_mode_data()?;
+        let leading_resources_data = document.leading_resources_data()?;
+        let trailing_resources_data = document.trailing_resources_data()?;
+
+        // These are only needed for the resources that were just
+        // written, so there’s no need to clone them for each export.
+        document.color_profile = None;
+        document.paths = Vec::new();
+        document.extra_resources = Vec::new();
+
+        Ok(Self {
+            document,
+            header_data,
+            color_mode_data,
+            leading_resources_data,
+            trailing_resources_data,
+        })
+    }
+
+    /// Returns the data for a file with the layers and preview image,
+    /// identical to the data from `Document::file_data`.
+    pub fn export(
+        &self,
+        layers: Vec<Layer>,
+        preview_image: Option<Image>,
+    ) -> error::Result<Vec<u8>> {
+        let mut document = self.document.clone();
+        document.layers = layers;
+        document.preview_image = preview_image;
+
+        let compression = ImageCompression::Rle;
+        let mut file_stream = FileStreamWriter::new();
+        file_stream.write_bytes(&self.header_data)?;
+        file_stream.write_bytes(&self.color_mode_data)?;
+        file_stream.write_bytes(&super::image_resources_section_data(&[
+            &self.leading_resources_data,
+            &document.layer_resources_data()?,
+            &self.trailing_resources_data,
+        ])?)?;
+        file_stream.write_bytes(
+            &document.layer_and_mask_information_data(FileFormat::Psd, &compression)?,
+        )?;
+        file_stream.write_bytes(&document.image_data(FileFormat::Psd, &compression)?)?;
+
+        Ok(file_stream.data().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graphics::{Color, Rect, Size};
+
+    use crate::document::Path;
+
+    use super::*;
+
+    #[test]
+    fn export() {
+        let mut document = Document::new();
+        document.size = Size {
+            width: 32,
+            height: 16,
+        };
+        document.embeds_srgb_profile = true;
+        document.paths = vec![Path::new("Path 1", Vec::new())];
+        let template = DocumentTemplate::new(document.clone()).unwrap();
+
+        let bounds = Rect::new(2, 1, 14, 17);
+        let mut yellow_layer = Layer::new(bounds);
+        yellow_layer.image = Some(Image::color(&Color::YELLOW, bounds.size.into()));
+        let mut red_layer = Layer::new(bounds);
+        red_layer.image = Some(Image::color(&Color::RED, bounds.size.into()));
+        let preview_image = Image::color(&Color::YELLOW, document.size);
+
+        for layers in [vec![yellow_layer.clone()], vec![yellow_layer, red_layer]] {
+            let data = template
+                .export(layers.clone(), Some(preview_image.clone()))
+                .unwrap();
+
+            document.layers = layers;
+            document.preview_image = Some(preview_image.clone());
+            assert_eq!(data, document.file_data().unwrap());
+        }
+    }
+}
@@ -0,0 +1,25 @@
+/// The light shared by layer styles that use the global light,
+/// such as drop shadows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlobalLight {
+    /// The angle of the light, in degrees.
+    pub angle: i32,
+    /// The altitude of the light, in degrees.
+    pub altitude: i32,
+}
+
+// MARK: Creation
+
+impl GlobalLight {
+    /// Creates a new global light.
+    pub fn new(angle: i32, altitude: i32) -> Self {
+        Self { angle, altitude }
+    }
+}
+
+impl Default for GlobalLight {
+    /// Photoshop’s default global light.
+    fn default() -> Self {
+        Self::new(120, 30)
+    }
+}
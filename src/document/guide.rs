@@ -0,0 +1,111 @@
+use file_stream::write::FileStreamWriter;
+
+use crate::endian::WriteBigEndian;
+use crate::error::ReadError;
+use crate::reader::Reader;
+
+use super::guide_direction::GuideDirection;
+
+/// The version of the grid and guides resource that Photoshop writes.
+pub const DEFAULT_GUIDES_VERSION: u32 = 1;
+
+/// The grid spacing Photoshop writes by default, in 1/32 pixels.
+const GRID_CYCLE: u32 = 576;
+
+/// The number of fractional units in a pixel for guide positions.
+const UNITS_PER_PIXEL: f64 = 32.0;
+
+/// A ruler guide.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Guide {
+    /// The position of the guide in pixels, stored to 1/32 of a pixel.
+    pub position: f64,
+    /// The direction of the guide.
+    pub direction: GuideDirection,
+}
+
+// MARK: Creation
+
+impl Guide {
+    /// Creates a new guide.
+    pub fn new(position: f64, direction: GuideDirection) -> Self {
+        Self {
+            position,
+            direction,
+        }
+    }
+}
+
+// MARK: Encoding
+
+/// Returns the data for the grid and guides resource, with a version
+/// and the default grid.
+pub(crate) fn data(guides: &[Guide], version: u32) -> anyhow::Result<Vec<u8>> {
+    let mut file_stream = FileStreamWriter::new();
+    file_stream.write_u32(version)?;
+    file_stream.write_u32(GRID_CYCLE)?;
+    file_stream.write_u32(GRID_CYCLE)?;
+    file_stream.write_u32(guides.len() as u32)?;
+    for guide in guides {
+        file_stream.write_be(&((guide.position * UNITS_PER_PIXEL).round() as i32))?;
+        file_stream.write_bytes(&[guide.direction.raw_value()])?;
+    }
+    Ok(file_stream.data().to_vec())
+}
+
+// MARK: Decoding
+
+/// Reads the guides from the grid and guides resource data.
+pub(crate) fn from_reader(reader: &mut Reader) -> anyhow::Result<Vec<Guide>> {
+    // The version and the grid.
+    reader.skip(12)?;
+    let number_of_guides = reader.read_u32()?;
+    let mut guides = Vec::new();
+    for _ in 0..number_of_guides {
+        let position = reader.read_i32()? as f64 / UNITS_PER_PIXEL;
+        let raw_direction = reader.read_u8()?;
+        let direction = GuideDirection::from_value(raw_direction)
+            .ok_or(ReadError::InvalidGuideDirection(raw_direction))?;
+        guides.push(Guide::new(position, direction));
+    }
+    Ok(guides)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guides_data() {
+        let guides = [
+            Guide::new(10.5, GuideDirection::Vertical),
+            Guide::new(-2.0, GuideDirection::Horizontal),
+        ];
+
+        let expected_data = [
+            0x00, 0x00, 0x00, 0x01, // Version
+            0x00, 0x00, 0x02, 0x40, // Horizontal grid
+            0x00, 0x00, 0x02, 0x40, // Vertical grid
+            0x00, 0x00, 0x00, 0x02, // Number of guides
+            0x00, 0x00, 0x01, 0x50, 0x00, // 10.5, vertical
+            0xff, 0xff, 0xff, 0xc0, 0x01, // -2, horizontal
+        ];
+        let data = data(&guides, DEFAULT_GUIDES_VERSION).unwrap();
+        assert_eq!(data, expected_data);
+
+        let result = from_reader(&mut Reader::new(&data)).unwrap();
+        assert_eq!(result, guides);
+    }
+
+    #[test]
+    fn guides_data_with_version() {
+        let guides = [Guide::new(4.0, GuideDirection::Vertical)];
+
+        let data = data(&guides, 2).unwrap();
+
+        // The version comes first, followed by the grid and then the
+        // number of guides.
+        assert_eq!(data[0..4], [0x00, 0x00, 0x00, 0x02]);
+        assert_eq!(data[12..16], [0x00, 0x00, 0x00, 0x01]);
+    }
+}
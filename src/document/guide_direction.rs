@@ -0,0 +1,27 @@
+/// The direction of a guide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuideDirection {
+    /// A vertical guide, positioned along the x axis.
+    Vertical,
+    /// A horizontal guide, positioned along the y axis.
+    Horizontal,
+}
+
+impl GuideDirection {
+    /// Creates a new guide direction from a raw value.
+    pub fn from_value(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(GuideDirection::Vertical),
+            1 => Some(GuideDirection::Horizontal),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw value for the guide direction.
+    pub fn raw_value(&self) -> u8 {
+        match self {
+            GuideDirection::Vertical => 0,
+            GuideDirection::Horizontal => 1,
+        }
+    }
+}
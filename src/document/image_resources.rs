@@ -0,0 +1,107 @@
+use file_stream::write::FileStreamWriter;
+
+use crate::{data, string};
+
+use super::constants;
+
+/// Writes an image resource block with an empty name, padding the
+/// data to an even length as the format requires.
+pub(crate) fn write_image_resource(
+    file_stream: &mut FileStreamWriter,
+    identifier: i16,
+    data: &[u8],
+) -> anyhow::Result<()> {
+    write_named_image_resource(file_stream, identifier, "", data)
+}
+
+/// Writes an image resource block, padding the name and the
+/// data to even lengths as the format requires.
+pub(crate) fn write_named_image_resource(
+    file_stream: &mut FileStreamWriter,
+    identifier: i16,
+    name: &str,
+    data: &[u8],
+) -> anyhow::Result<()> {
+    file_stream.write_bytes(&constants::RESOURCE_SIGNATURE)?;
+    file_stream.write_be(&identifier)?;
+
+    let mut name_data = string::pascal::data_from_string(Some(&name.to_string()))?;
+    data::pad(&mut name_data, 2);
+    file_stream.write_bytes(&name_data)?;
+
+    file_stream.write_be(&data::section_length(data.len())?)?;
+
+    let mut data = data.to_vec();
+    data::pad(&mut data, 2);
+    file_stream.write_bytes(&data)?;
+    Ok(())
+}
+
+/// Returns the data for the version info resource.
+pub(crate) fn version_info_data(
+    has_real_merged_data: bool,
+    writer_name: &str,
+    reader_name: &str,
+) -> anyhow::Result<Vec<u8>> {
+    let mut file_stream = FileStreamWriter::new();
+    file_stream.write_be(&constants::VERSION_INFO_VERSION)?;
+    file_stream.write_be(&(has_real_merged_data as u8))?;
+    for name in [writer_name, reader_name] {
+        file_stream.write_be(&(name.encode_utf16().count() as u32))?;
+        for character in name.encode_utf16() {
+            file_stream.write_be(&character)?;
+        }
+    }
+    file_stream.write_be(&constants::VERSION_INFO_FILE_VERSION)?;
+    Ok(file_stream.data().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use file_stream::write::FileStreamWriter;
+
+    #[test]
+    fn write_image_resource() {
+        let mut file_stream = FileStreamWriter::new();
+        super::write_image_resource(&mut file_stream, 0x0400, &[0xab, 0xcd, 0xef]).unwrap();
+
+        let expected_data = vec![
+            0x38, 0x42, 0x49, 0x4D, // 8BIM
+            0x04, 0x00, // Identifier
+            0x00, 0x00, // Name
+            0x00, 0x00, 0x00, 0x03, // Length
+            0xab, 0xcd, 0xef, // Data
+            0x00, // Padding
+        ];
+        assert_eq!(file_stream.data(), expected_data);
+    }
+
+    #[test]
+    fn write_named_image_resource() {
+        let mut file_stream = FileStreamWriter::new();
+        super::write_named_image_resource(&mut file_stream, 0x0FA0, "abc", &[0x01, 0x02]).unwrap();
+
+        let expected_data = vec![
+            0x38, 0x42, 0x49, 0x4D, // 8BIM
+            0x0F, 0xA0, // Identifier
+            0x03, 0x61, 0x62, 0x63, // Name
+            0x00, 0x00, 0x00, 0x02, // Length
+            0x01, 0x02, // Data
+        ];
+        assert_eq!(file_stream.data(), expected_data);
+    }
+
+    #[test]
+    fn version_info_data() {
+        let data = super::version_info_data(true, "ab", "c").unwrap();
+
+        let expected_data = vec![
+            0x00, 0x00, 0x00, 0x01, // Version
+            0x01, // Has real merged data
+            0x00, 0x00, 0x00, 0x02, 0x00, 0x61, 0x00, 0x62, // Writer name
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x63, // Reader name
+            0x00, 0x00, 0x00, 0x01, // File version
+        ];
+        assert_eq!(data, expected_data);
+    }
+}
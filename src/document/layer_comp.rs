@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+use file_stream::write::FileStreamWriter;
+
+use crate::descriptor::{Descriptor, DescriptorValue};
+use crate::endian::WriteBigEndian;
+
+/// The version of the layer comps descriptors.
+const DESCRIPTOR_VERSION: u32 = 16;
+
+/// The bit in the captured info for the layers’ visibility.
+const CAPTURES_VISIBILITY: i32 = 0b001;
+
+/// The bit in the captured info for the layers’ positions.
+const CAPTURES_POSITION: i32 = 0b010;
+
+/// The bit in the captured info for the layers’ styles.
+const CAPTURES_APPEARANCE: i32 = 0b100;
+
+/// A saved snapshot of the layers, which designers switch between to
+/// show different versions of a design.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerComp {
+    /// The identifier of the comp, unique within the document.
+    pub id: u32,
+    /// The name shown in the layer comps panel.
+    pub name: String,
+    /// Whether the comp captures the visibility of the layers.
+    pub captures_visibility: bool,
+    /// Whether the comp captures the positions of the layers.
+    pub captures_position: bool,
+    /// Whether the comp captures the layer styles.
+    pub captures_appearance: bool,
+    /// Whether each layer is visible in the comp, keyed by the layer’s
+    /// identifier. Layers without an identifier can’t be captured.
+    pub visibility: BTreeMap<u32, bool>,
+}
+
+// MARK: Creation
+
+impl LayerComp {
+    /// Creates a new layer comp that captures nothing.
+    pub fn new(id: u32, name: &str) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            captures_visibility: false,
+            captures_position: false,
+            captures_appearance: false,
+            visibility: BTreeMap::new(),
+        }
+    }
+}
+
+// MARK: Data
+
+impl LayerComp {
+    /// Returns the bits for the settings the comp captures.
+    fn captured_info(&self) -> i32 {
+        let mut captured_info = 0;
+        if self.captures_visibility {
+            captured_info |= CAPTURES_VISIBILITY;
+        }
+        if self.captures_position {
+            captured_info |= CAPTURES_POSITION;
+        }
+        if self.captures_appearance {
+            captured_info |= CAPTURES_APPEARANCE;
+        }
+        captured_info
+    }
+
+    /// Returns the `Comp` descriptor.
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::new("Comp")
+            .with("Nm  ", DescriptorValue::Text(self.name.clone()))
+            .with("compID", DescriptorValue::Integer(self.id as i32))
+            .with(
+                "capturedInfo",
+                DescriptorValue::Integer(self.captured_info()),
+            )
+    }
+}
+
+/// Returns the data for the layer comps resource, which is the
+/// descriptor version followed by a descriptor listing the comps.
+pub(crate) fn data(layer_comps: &[LayerComp]) -> anyhow::Result<Vec<u8>> {
+    let list = layer_comps
+        .iter()
+        .map(|layer_comp| DescriptorValue::Descriptor(layer_comp.descriptor()))
+        .collect();
+    let descriptor = Descriptor::new("null").with("list", DescriptorValue::List(list));
+
+    let mut file_stream = FileStreamWriter::new();
+    file_stream.write_u32(DESCRIPTOR_VERSION)?;
+    file_stream.write_bytes(&descriptor.data()?)?;
+    Ok(file_stream.data().to_vec())
+}
+
+/// Returns the data for a layer’s `cmls` block, which lists the comps
+/// the layer is shown or hidden in, or `None` when no comp captures
+/// the layer’s visibility.
+pub(crate) fn layer_settings_data(
+    layer_comps: &[LayerComp],
+    layer_id: u32,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut layer_settings = Vec::new();
+    for is_visible in [true, false] {
+        let comp_list: Vec<DescriptorValue> = layer_comps
+            .iter()
+            .filter(|layer_comp| {
+                layer_comp.captures_visibility
+                    && layer_comp.visibility.get(&layer_id) == Some(&is_visible)
+            })
+            .map(|layer_comp| DescriptorValue::Integer(layer_comp.id as i32))
+            .collect();
+        if !comp_list.is_empty() {
+            let settings = Descriptor::new("null")
+                .with("enab", DescriptorValue::Boolean(is_visible))
+                .with("compList", DescriptorValue::List(comp_list));
+            layer_settings.push(DescriptorValue::Descriptor(settings));
+        }
+    }
+    if layer_settings.is_empty() {
+        return Ok(None);
+    }
+
+    let descriptor = Descriptor::new("null")
+        .with("layerID", DescriptorValue::Integer(layer_id as i32))
+        .with("layerSettings", DescriptorValue::List(layer_settings));
+
+    let mut file_stream = FileStreamWriter::new();
+    file_stream.write_u32(DESCRIPTOR_VERSION)?;
+    file_stream.write_bytes(&descriptor.data()?)?;
+    Ok(Some(file_stream.data().to_vec()))
+}
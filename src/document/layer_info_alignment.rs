@@ -0,0 +1,20 @@
+/// The number of bytes the layer information is padded to a multiple of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LayerInfoAlignment {
+    /// Two bytes, which is what the specification asks for.
+    #[default]
+    TwoBytes,
+    /// Four bytes, which matches files saved by Photoshop and is
+    /// expected by some importers.
+    FourBytes,
+}
+
+impl LayerInfoAlignment {
+    /// Returns the number of bytes to align to.
+    pub fn number_of_bytes(&self) -> usize {
+        match self {
+            LayerInfoAlignment::TwoBytes => 2,
+            LayerInfoAlignment::FourBytes => 4,
+        }
+    }
+}
@@ -0,0 +1,11 @@
+/// The order of the layers in a document, and in each of its groups.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum LayerOrder {
+    /// The first layer is the bottom layer, which is how layers
+    /// are stored in the file.
+    #[default]
+    BottomToTop,
+    /// The first layer is the top layer, which is how layers are
+    /// shown in Photoshop’s layers panel.
+    TopToBottom,
+}
@@ -0,0 +1,80 @@
+use file_stream::write::FileStreamWriter;
+use graphics::Color;
+
+use crate::endian::WriteBigEndian;
+use crate::reader::Reader;
+
+use super::mask_overlay_kind::MaskOverlayKind;
+
+/// The colour space of the overlay colour, which is always RGB.
+const RGB_COLOR_SPACE: u16 = 0;
+
+/// The colour and opacity used to show layer masks, stored in the
+/// global layer mask info.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaskOverlay {
+    /// The overlay colour. The alpha component is ignored.
+    pub color: Color,
+    /// The opacity of the overlay, from 0 to 100.
+    pub opacity: u16,
+    /// How the colour is used.
+    pub kind: MaskOverlayKind,
+}
+
+// MARK: Creation
+
+impl MaskOverlay {
+    /// Creates a new mask overlay that uses each layer’s own setting.
+    pub fn new(color: Color, opacity: u16) -> Self {
+        Self {
+            color,
+            opacity,
+            kind: MaskOverlayKind::PerLayer,
+        }
+    }
+}
+
+// MARK: Encoding
+
+impl MaskOverlay {
+    /// Returns the data for the global layer mask info, without its
+    /// length. The colour components are 16-bit, followed by a fourth
+    /// unused component, and the data is padded to an even length.
+    pub(crate) fn data(&self) -> anyhow::Result<Vec<u8>> {
+        let mut file_stream = FileStreamWriter::new();
+        file_stream.write_u16(RGB_COLOR_SPACE)?;
+        for component in [self.color.red, self.color.green, self.color.blue, 0] {
+            file_stream.write_u16(component as u16 * 257)?;
+        }
+        file_stream.write_u16(self.opacity)?;
+        file_stream.write_bytes(&[self.kind.raw_value()])?;
+        file_stream.write_zeros(1)?;
+        Ok(file_stream.data().to_vec())
+    }
+}
+
+// MARK: Decoding
+
+impl MaskOverlay {
+    /// Reads the mask overlay from the global layer mask info.
+    pub(crate) fn from_reader(reader: &mut Reader) -> anyhow::Result<Self> {
+        // The colour space.
+        reader.skip(2)?;
+        let red = reader.read_u16()?;
+        let green = reader.read_u16()?;
+        let blue = reader.read_u16()?;
+        reader.skip(2)?;
+        let opacity = reader.read_u16()?;
+        let kind = MaskOverlayKind::from_value(reader.read_u8()?).unwrap_or_default();
+        Ok(Self {
+            color: Color {
+                red: (red >> 8) as u8,
+                green: (green >> 8) as u8,
+                blue: (blue >> 8) as u8,
+                alpha: u8::MAX,
+            },
+            opacity,
+            kind,
+        })
+    }
+}
@@ -0,0 +1,32 @@
+/// How the mask overlay colour is used when masks are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaskOverlayKind {
+    /// The colour shows the selected areas, inverting the mask.
+    ColorSelected,
+    /// The colour shows the protected areas.
+    ColorProtected,
+    /// Each layer’s own setting is used.
+    #[default]
+    PerLayer,
+}
+
+impl MaskOverlayKind {
+    /// Creates a new mask overlay kind from a raw value.
+    pub fn from_value(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(MaskOverlayKind::ColorSelected),
+            1 => Some(MaskOverlayKind::ColorProtected),
+            128 => Some(MaskOverlayKind::PerLayer),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw value for the mask overlay kind.
+    pub fn raw_value(&self) -> u8 {
+        match self {
+            MaskOverlayKind::ColorSelected => 0,
+            MaskOverlayKind::ColorProtected => 1,
+            MaskOverlayKind::PerLayer => 128,
+        }
+    }
+}
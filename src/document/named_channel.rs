@@ -0,0 +1,21 @@
+/// A named channel of a document, used by colour modes where
+/// every channel is independent of the others.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedChannel {
+    /// The name of the channel, shown in the channels panel.
+    pub name: String,
+    /// The 8-bit data for the channel, one byte per pixel.
+    pub data: Vec<u8>,
+}
+
+// MARK: Creation
+
+impl NamedChannel {
+    /// Creates a new named channel.
+    pub fn new(name: &str, data: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            data,
+        }
+    }
+}
@@ -0,0 +1,581 @@
+use graphics::{Color, Image, Size};
+
+use crate::color_mode::ColorMode;
+use crate::error::{self, ReadError};
+use crate::image;
+use crate::layer::parse::ChannelInfo;
+use crate::layer::Layer;
+use crate::reader::Reader;
+
+use super::constants::{self, resource_identifiers};
+use super::{
+    Document, GlobalLight, MaskOverlay, NamedChannel, Path, PrintSettings, RawResource,
+    ResolutionInfo,
+};
+
+// MARK: Decoding
+
+impl Document {
+    /// Creates a document from the data for a Photoshop file.
+    pub fn from_data(data: &[u8]) -> error::Result<Self> {
+        let mut reader = Reader::new(data);
+        let mut document = Document::new();
+
+        // HEADER SECTION
+        read_header(&mut reader, &mut document)?;
+
+        // COLOUR MODE DATA SECTION
+        let mut color_mode_data = reader.read_section()?;
+        if document.color_mode == ColorMode::Duotone {
+            let length = color_mode_data.remaining();
+            document.duotone_specification = Some(color_mode_data.read_bytes(length)?.to_vec());
+        } else if document.color_mode == ColorMode::Indexed {
+            document.palette = Some(read_palette(&mut color_mode_data)?);
+        }
+
+        // IMAGE RESOURCES SECTION
+        let mut channel_names = Vec::new();
+        let mut image_resources = reader.read_section()?;
+        while image_resources.remaining() > 0 {
+            if image_resources.read_array::<4>()? != constants::RESOURCE_SIGNATURE {
+                return Err(ReadError::InvalidSignature.into());
+            }
+            let identifier = image_resources.read_i16()?;
+            let name = image_resources.read_pascal_string(2)?;
+            let length = image_resources.read_u32()? as usize;
+            let mut resource = image_resources.section(length)?;
+            // The data is padded to an even length.
+            image_resources.skip(length % 2)?;
+
+            match identifier {
+                resource_identifiers::RESOLUTION_INFORMATION => {
+                    document.resolution_info = ResolutionInfo::from_reader(&mut resource)?;
+                }
+                // These are always written by the crate from the layers.
+                resource_identifiers::LAYERS_GROUP_INFORMATION
+                | resource_identifiers::LAYER_ID_GENERATOR_BASE => (),
+                resource_identifiers::LAYER_STATE => {
+                    document.selected_layer = resource.read_u16()? as usize;
+                }
+                resource_identifiers::VERSION_INFO => {
+                    // The version and whether there is merged data.
+                    resource.skip(5)?;
+                    document.writer_name = resource.read_unicode_string()?;
+                    document.reader_name = resource.read_unicode_string()?;
+                }
+                resource_identifiers::COLOR_PROFILE => {
+                    document.color_profile = Some(resource.read_bytes(length)?.to_vec());
+                }
+                resource_identifiers::GLOBAL_ANGLE => {
+                    document
+                        .global_light
+                        .get_or_insert_with(GlobalLight::default)
+                        .angle = resource.read_i32()?;
+                }
+                resource_identifiers::GLOBAL_ALTITUDE => {
+                    document
+                        .global_light
+                        .get_or_insert_with(GlobalLight::default)
+                        .altitude = resource.read_i32()?;
+                }
+                resource_identifiers::PIXEL_ASPECT_RATIO => {
+                    // The version.
+                    resource.skip(4)?;
+                    document.pixel_aspect_ratio = Some(f64::from_be_bytes(resource.read_array()?));
+                }
+                resource_identifiers::PRINT_FLAGS => document
+                    .print_settings
+                    .get_or_insert_with(PrintSettings::default)
+                    .read_print_flags(&mut resource)?,
+                resource_identifiers::PRINT_SCALE => document
+                    .print_settings
+                    .get_or_insert_with(PrintSettings::default)
+                    .read_print_scale(&mut resource)?,
+                resource_identifiers::PRINT_FLAGS_INFORMATION => document
+                    .print_settings
+                    .get_or_insert_with(PrintSettings::default)
+                    .read_print_flags_information(&mut resource)?,
+                resource_identifiers::FIRST_PATH_INFORMATION
+                    ..=resource_identifiers::LAST_PATH_INFORMATION => {
+                    let path = Path::from_reader(&mut resource, &name, document.size)?;
+                    document.paths.push(path);
+                }
+                resource_identifiers::CLIPPING_PATH_NAME => {
+                    document.clipping_path_name = Some(resource.read_pascal_string(1)?);
+                }
+                resource_identifiers::ALPHA_CHANNEL_NAMES
+                    if document.color_mode == ColorMode::Multichannel =>
+                {
+                    while resource.remaining() > 0 {
+                        channel_names.push(resource.read_pascal_string(1)?);
+                    }
+                }
+                _ => document.extra_resources.push(RawResource {
+                    identifier,
+                    name,
+                    data: resource.read_bytes(length)?.to_vec(),
+                }),
+            }
+        }
+
+        // LAYER AND MASK INFORMATION SECTION
+        let mut layer_and_mask_info = reader.read_section()?;
+        if layer_and_mask_info.remaining() > 0 {
+            let mut layer_info = layer_and_mask_info.read_section()?;
+            if layer_info.remaining() > 0 {
+                // Negative if the first alpha channel has the merged transparency.
+                let number_of_layers = layer_info.read_i16()?.unsigned_abs();
+
+                let mut records: Vec<(Layer, Vec<ChannelInfo>)> = Vec::new();
+                for _ in 0..number_of_layers {
+                    records.push(Layer::from_record(&mut layer_info)?);
+                }
+
+                for (layer, channel_info) in records.iter_mut() {
+                    layer.read_channel_data(&mut layer_info, channel_info)?;
+                }
+
+                document.layers =
+                    Layer::nested_layers(records.into_iter().map(|(layer, _)| layer).collect())?;
+            }
+
+            // The global layer mask info.
+            if layer_and_mask_info.remaining() >= 4 {
+                let mut global_layer_mask_info = layer_and_mask_info.read_section()?;
+                if global_layer_mask_info.remaining() > 0 {
+                    document.mask_overlay =
+                        Some(MaskOverlay::from_reader(&mut global_layer_mask_info)?);
+                }
+            }
+        }
+
+        // IMAGE DATA SECTION
+        if reader.remaining() > 0 {
+            let channels = image::decoded_channels(
+                &mut reader,
+                document.size,
+                document.number_of_channels as usize,
+            )?;
+
+            if document.color_mode == ColorMode::Multichannel {
+                document.channels = channels
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, data)| NamedChannel {
+                        name: channel_names.get(index).cloned().unwrap_or_default(),
+                        data,
+                    })
+                    .collect();
+            } else {
+                document.preview_image = merged_image(
+                    document.size,
+                    &document.color_mode,
+                    document.palette.as_deref(),
+                    channels,
+                );
+            }
+        }
+
+        Ok(document)
+    }
+
+    /// Decodes only the merged image from the data for a Photoshop file,
+    /// skipping over the resources and layers without parsing them. This
+    /// is much faster than `from_data` for viewers that only need the
+    /// composite.
+    pub fn decode_merged_image(data: &[u8]) -> error::Result<Image> {
+        let mut reader = Reader::new(data);
+        let mut document = Document::new();
+        read_header(&mut reader, &mut document)?;
+
+        // The colour mode data, which is only needed for the palette, then
+        // the image resources, and layer and mask information sections.
+        let mut color_mode_data = reader.read_section()?;
+        let palette = match document.color_mode {
+            ColorMode::Indexed => Some(read_palette(&mut color_mode_data)?),
+            _ => None,
+        };
+        for _ in 0..2 {
+            reader.read_section()?;
+        }
+        if reader.remaining() == 0 || document.color_mode == ColorMode::Multichannel {
+            return Err(ReadError::MissingMergedImage.into());
+        }
+
+        let channels = image::decoded_channels(
+            &mut reader,
+            document.size,
+            document.number_of_channels as usize,
+        )?;
+        merged_image(
+            document.size,
+            &document.color_mode,
+            palette.as_deref(),
+            channels,
+        )
+        .ok_or(ReadError::MissingMergedImage.into())
+    }
+}
+
+/// Reads the header section into a document.
+fn read_header(reader: &mut Reader, document: &mut Document) -> anyhow::Result<()> {
+    if reader.read_array::<4>()? != constants::FILE_SIGNATURE {
+        anyhow::bail!(ReadError::InvalidSignature);
+    }
+    let version = reader.read_i16()?;
+    if version != constants::VERSION_NUMBER {
+        anyhow::bail!(ReadError::UnsupportedVersion(version));
+    }
+
+    // Six bytes of padding.
+    reader.skip(6)?;
+
+    document.number_of_channels = reader.read_u16()?;
+    let height = reader.read_u32()?;
+    let width = reader.read_u32()?;
+    document.size = Size { width, height };
+
+    document.bits_per_channel = reader.read_u16()?;
+    if document.bits_per_channel != 8 {
+        anyhow::bail!(ReadError::UnsupportedBitDepth(document.bits_per_channel));
+    }
+
+    let raw_color_mode = reader.read_i16()?;
+    document.color_mode = ColorMode::from_value(raw_color_mode)
+        .ok_or(ReadError::UnsupportedColorMode(raw_color_mode))?;
+    Ok(())
+}
+
+/// Reads the colour table of an indexed document: 256 red values,
+/// then 256 green values, then 256 blue values.
+fn read_palette(reader: &mut Reader) -> anyhow::Result<Vec<Color>> {
+    let palette_data = reader.read_bytes(constants::PALETTE_SIZE * 3)?;
+    let (red_data, green_and_blue_data) = palette_data.split_at(constants::PALETTE_SIZE);
+    let (green_data, blue_data) = green_and_blue_data.split_at(constants::PALETTE_SIZE);
+    Ok((0..constants::PALETTE_SIZE)
+        .map(|index| Color {
+            red: red_data[index],
+            green: green_data[index],
+            blue: blue_data[index],
+            alpha: u8::MAX,
+        })
+        .collect())
+}
+
+/// Creates the merged image from the channels of the image data section.
+/// Single channel modes are shown in grey, indexed colours are looked up
+/// in the palette, and anything else needs at least red, green and blue
+/// channels.
+fn merged_image(
+    size: Size<u32>,
+    color_mode: &ColorMode,
+    palette: Option<&[Color]>,
+    channels: Vec<Vec<u8>>,
+) -> Option<Image> {
+    match color_mode {
+        ColorMode::Indexed => {
+            let indices = channels.first()?;
+            let palette = palette?;
+            let color = |component: fn(&Color) -> u8| -> Option<Vec<u8>> {
+                indices
+                    .iter()
+                    .map(|&index| palette.get(index as usize).map(component))
+                    .collect()
+            };
+            Some(image::image_from_channels(
+                size,
+                &color(|color| color.red)?,
+                &color(|color| color.green)?,
+                &color(|color| color.blue)?,
+                None,
+            ))
+        }
+        ColorMode::Duotone | ColorMode::Grayscale => {
+            let gray = channels.first()?;
+            Some(image::image_from_channels(size, gray, gray, gray, None))
+        }
+        _ if channels.len() >= 3 => Some(image::image_from_channels(
+            size,
+            &channels[0],
+            &channels[1],
+            &channels[2],
+            channels.get(3).map(|alpha| alpha.as_slice()),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use graphics::{Color, Image, Point, Rect};
+
+    use crate::document::{BezierKnot, Subpath};
+    use crate::error::Error;
+    use crate::image_compression::ImageCompression;
+    use crate::layer::LayerType;
+    use crate::layer_container::LayerContainer;
+
+    use super::*;
+
+    #[test]
+    fn from_data() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("tests/resources/simple.psd");
+        let data = std::fs::read(path).unwrap();
+
+        let mut document = Document::from_data(&data).unwrap();
+
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        assert_eq!(document.size, size);
+        assert_eq!(document.color_mode, ColorMode::Rgb);
+        assert_eq!(document.layers.len(), 2);
+
+        let image = Image::color(&Color::CYAN, size);
+
+        let layer_0 = &document.layers[0];
+        assert_eq!(layer_0.name, Some("Background".to_string()));
+        assert_eq!(layer_0.bounds, Rect::new(0, 0, 2, 2));
+        assert_eq!(layer_0.image, Some(image.clone()));
+
+        let layer_1 = &document.layers[1];
+        assert_eq!(layer_1.name, Some("Empty".to_string()));
+        assert_eq!(layer_1.image, Some(Image::empty(size)));
+
+        assert_eq!(document.preview_image, Some(image));
+
+        // Writing the document back out gives the same file.
+        assert_eq!(document.file_data().unwrap(), data);
+    }
+
+    #[test]
+    fn from_raw_data() {
+        let size = Size {
+            width: 3,
+            height: 2,
+        };
+        let image = Image::color(&Color::from_rgba_u32(0x24a4ee99), size);
+        let mut document = Document::from_image(image.clone());
+
+        let data = document
+            .file_data_with_compression(ImageCompression::RawData)
+            .unwrap();
+        let result = Document::from_data(&data).unwrap();
+
+        assert_eq!(result.layers.len(), 1);
+        assert_eq!(result.layers[0].image, Some(image.clone()));
+        assert_eq!(result.preview_image, Some(image));
+
+        // Every channel is stored uncompressed.
+        for channel in &result.layers[0].channels {
+            assert_eq!(channel.data.len(), 6);
+        }
+        assert!(data.ends_with(&[0x99; 6]));
+    }
+
+    #[test]
+    fn from_zip_data() {
+        let size = Size {
+            width: 3,
+            height: 2,
+        };
+        let mut image = Image::color(&Color::from_rgba_u32(0x24a4ee99), size);
+        image.data[0..4].copy_from_slice(&[0xff, 0x00, 0x00, 0xff]);
+        let mut document = Document::from_image(image.clone());
+
+        for compression in [
+            ImageCompression::ZipWithoutPrediction,
+            ImageCompression::ZipWithPrediction,
+        ] {
+            let data = document.file_data_with_compression(compression).unwrap();
+            let result = Document::from_data(&data).unwrap();
+
+            assert_eq!(result.layers[0].image, Some(image.clone()));
+            assert_eq!(result.preview_image, Some(image.clone()));
+        }
+    }
+
+    #[test]
+    fn paths_round_trip() {
+        let mut document = Document::from_image(Image::color(
+            &Color::RED,
+            Size {
+                width: 4,
+                height: 2,
+            },
+        ));
+        let knots = [(1.0, 0.5), (3.0, 0.5), (3.0, 1.5), (1.0, 1.5)]
+            .iter()
+            .map(|&(x, y)| BezierKnot::corner(Point { x, y }))
+            .collect();
+        document.paths = vec![Path::new("Cut", vec![Subpath::new(knots, true)])];
+        document.clipping_path_name = Some("Cut".to_string());
+
+        let data = document.file_data().unwrap();
+        // 8BIM, the first path identifier and the name.
+        let path_header = [0x38, 0x42, 0x49, 0x4d, 0x07, 0xd0, 0x03, 0x43, 0x75, 0x74];
+        assert!(data
+            .windows(path_header.len())
+            .any(|window| window == path_header));
+
+        let result = Document::from_data(&data).unwrap();
+        assert_eq!(result.paths, document.paths);
+        assert_eq!(result.clipping_path_name, document.clipping_path_name);
+        assert!(result.extra_resources.is_empty());
+    }
+
+    #[test]
+    fn decode_merged_image() {
+        let size = Size {
+            width: 3,
+            height: 2,
+        };
+        let mut image = Image::color(&Color::from_rgba_u32(0x24a4ee99), size);
+        image.data[0..4].copy_from_slice(&[0xff, 0x00, 0x00, 0xff]);
+        let mut document = Document::from_image(image.clone());
+        document.layers[0].name = Some("Ignored".to_string());
+
+        for compression in [ImageCompression::RawData, ImageCompression::Rle] {
+            let data = document.file_data_with_compression(compression).unwrap();
+            assert_eq!(Document::decode_merged_image(&data).unwrap(), image);
+        }
+
+        // Documents without a preview have nothing to decode.
+        document.preview_image = None;
+        let data = document.file_data().unwrap();
+        let error = Document::decode_merged_image(&data).unwrap_err();
+        assert!(matches!(error, Error::Read(ReadError::MissingMergedImage)));
+    }
+
+    #[test]
+    fn from_photoshop_data() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("tests/resources/foldered.psd");
+        let data = std::fs::read(path).unwrap();
+
+        let document = Document::from_data(&data).unwrap();
+
+        let names: Vec<String> = document
+            .all_layers()
+            .iter()
+            .filter_map(|layer| layer.name.clone())
+            .collect();
+        assert!(names.contains(&"Background".to_string()));
+        assert!(document.preview_image.is_some());
+    }
+
+    #[test]
+    fn from_group_data() {
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: size.into(),
+        };
+
+        let mut layer_0 = Layer::new(bounds);
+        layer_0.name = Some("Background".to_string());
+        layer_0.image = Some(Image::color(&Color::MAGENTA, size));
+
+        let mut layer_1 = Layer::new(bounds);
+        layer_1.name = Some("Empty".to_string());
+
+        let mut layer_2 = Layer::new(bounds);
+        layer_2.name = Some("Nested".to_string());
+        let mut inner_group = Layer::group(vec![layer_2], false);
+        inner_group.name = Some("Inner".to_string());
+
+        let mut group = Layer::group(vec![layer_0, layer_1, inner_group], true);
+        group.name = Some("Group".to_string());
+
+        let mut document = Document::new();
+        document.size = size;
+        document.layers = vec![group, Layer::new(bounds)];
+        document.preview_image = Some(Image::color(&Color::MAGENTA, size));
+
+        let data = document.file_data().unwrap();
+        let result = Document::from_data(&data).unwrap();
+
+        assert_eq!(result.layers.len(), 2);
+        assert_eq!(result.number_of_layers(), document.number_of_layers());
+
+        let LayerType::Group(info) = &result.layers[0].layer_type else {
+            panic!("Expected a group.");
+        };
+        assert_eq!(result.layers[0].name, Some("Group".to_string()));
+        let names: Vec<Option<String>> =
+            info.layers.iter().map(|layer| layer.name.clone()).collect();
+        assert_eq!(
+            names,
+            vec![
+                Some("Background".to_string()),
+                Some("Empty".to_string()),
+                Some("Inner".to_string())
+            ]
+        );
+
+        let LayerType::Group(inner_info) = &info.layers[2].layer_type else {
+            panic!("Expected a nested group.");
+        };
+        assert_eq!(inner_info.layers.len(), 1);
+        assert_eq!(inner_info.layers[0].name, Some("Nested".to_string()));
+        assert_eq!(inner_info.layers[0].layer_type, LayerType::Image);
+    }
+
+    #[test]
+    fn unknown_resource_round_trip() {
+        let mut document = Document::new();
+        document.size = Size {
+            width: 2,
+            height: 2,
+        };
+        let mut layer = Layer::new(Rect::new(0, 0, 2, 2));
+        layer.name = Some("Before".to_string());
+        document.layers = vec![layer];
+
+        let resource = RawResource {
+            identifier: 0x0FA0,
+            name: "Plug-in".to_string(),
+            data: vec![0x01, 0x02, 0x03],
+        };
+        document.extra_resources = vec![resource.clone()];
+
+        let data = document.file_data().unwrap();
+        let mut parsed_document = Document::from_data(&data).unwrap();
+        assert_eq!(parsed_document.extra_resources, vec![resource]);
+
+        // Modify and write again.
+        parsed_document.layers[0].name = Some("After".to_string());
+        let modified_data = parsed_document.file_data().unwrap();
+
+        let resource_data = [
+            0x38, 0x42, 0x49, 0x4D, // 8BIM
+            0x0F, 0xA0, // Identifier
+            0x07, 0x50, 0x6c, 0x75, 0x67, 0x2d, 0x69, 0x6e, // Name
+            0x00, 0x00, 0x00, 0x03, // Length
+            0x01, 0x02, 0x03, 0x00, // Data
+        ];
+        assert!(modified_data
+            .windows(resource_data.len())
+            .any(|window| window == resource_data));
+
+        let reparsed_document = Document::from_data(&modified_data).unwrap();
+        assert_eq!(
+            reparsed_document.extra_resources,
+            parsed_document.extra_resources
+        );
+        assert_eq!(reparsed_document.layers[0].name, Some("After".to_string()));
+    }
+
+    #[test]
+    fn from_invalid_data() {
+        let error = Document::from_data(&[0x38, 0x42, 0x50, 0x54]).unwrap_err();
+        assert!(matches!(error, Error::Read(ReadError::InvalidSignature)));
+    }
+}
@@ -0,0 +1,191 @@
+use file_stream::write::FileStreamWriter;
+use graphics::{Point, Size};
+
+use crate::endian::WriteBigEndian;
+use crate::reader::Reader;
+
+use super::{BezierKnot, Subpath};
+
+/// The length of every path record.
+const RECORD_LENGTH: usize = 26;
+
+/// The record selector for the length of a closed subpath.
+const CLOSED_SUBPATH_LENGTH: u16 = 0;
+
+/// The record selector for a linked knot in a closed subpath.
+const CLOSED_SUBPATH_LINKED_KNOT: u16 = 1;
+
+/// The record selector for an unlinked knot in a closed subpath.
+const CLOSED_SUBPATH_UNLINKED_KNOT: u16 = 2;
+
+/// The record selector for the length of an open subpath.
+const OPEN_SUBPATH_LENGTH: u16 = 3;
+
+/// The record selector for a linked knot in an open subpath.
+const OPEN_SUBPATH_LINKED_KNOT: u16 = 4;
+
+/// The record selector for an unlinked knot in an open subpath.
+const OPEN_SUBPATH_UNLINKED_KNOT: u16 = 5;
+
+/// The record selector for the path fill rule, which always comes first.
+const PATH_FILL_RULE: u16 = 6;
+
+/// The scale of the 8.24 fixed point values that knots are stored as.
+const FIXED_POINT_SCALE: f64 = (1 << 24) as f64;
+
+/// A work path, such as a cut path, stored with the document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    /// The name of the path, shown in the paths panel.
+    pub name: String,
+    /// The subpaths that make up the path.
+    pub subpaths: Vec<Subpath>,
+}
+
+// MARK: Creation
+
+impl Path {
+    /// Creates a new path.
+    pub fn new(name: &str, subpaths: Vec<Subpath>) -> Self {
+        Self {
+            name: name.to_string(),
+            subpaths,
+        }
+    }
+}
+
+// MARK: Encoding
+
+impl Path {
+    /// Returns the path records for the path resource. Knots are stored
+    /// as fractions of the document size.
+    pub(crate) fn data(&self, size: Size<u32>) -> anyhow::Result<Vec<u8>> {
+        let mut file_stream = FileStreamWriter::new();
+        file_stream.write_u16(PATH_FILL_RULE)?;
+        file_stream.write_zeros(RECORD_LENGTH - 2)?;
+
+        for subpath in &self.subpaths {
+            let length_selector = if subpath.is_closed {
+                CLOSED_SUBPATH_LENGTH
+            } else {
+                OPEN_SUBPATH_LENGTH
+            };
+            file_stream.write_u16(length_selector)?;
+            file_stream.write_u16(subpath.knots.len() as u16)?;
+            file_stream.write_zeros(RECORD_LENGTH - 4)?;
+
+            for knot in &subpath.knots {
+                let selector = match (subpath.is_closed, knot.is_linked) {
+                    (true, true) => CLOSED_SUBPATH_LINKED_KNOT,
+                    (true, false) => CLOSED_SUBPATH_UNLINKED_KNOT,
+                    (false, true) => OPEN_SUBPATH_LINKED_KNOT,
+                    (false, false) => OPEN_SUBPATH_UNLINKED_KNOT,
+                };
+                file_stream.write_u16(selector)?;
+                for point in [knot.preceding, knot.anchor, knot.leaving] {
+                    // The vertical component comes first.
+                    file_stream.write_be(&fixed_point_value(point.y, size.height))?;
+                    file_stream.write_be(&fixed_point_value(point.x, size.width))?;
+                }
+            }
+        }
+
+        Ok(file_stream.data().to_vec())
+    }
+}
+
+// MARK: Decoding
+
+impl Path {
+    /// Reads the path records from a path resource.
+    pub(crate) fn from_reader(
+        reader: &mut Reader,
+        name: &str,
+        size: Size<u32>,
+    ) -> anyhow::Result<Self> {
+        let mut path = Path::new(name, Vec::new());
+        while reader.remaining() >= RECORD_LENGTH {
+            let mut record = reader.section(RECORD_LENGTH)?;
+            match record.read_u16()? {
+                selector @ (CLOSED_SUBPATH_LENGTH | OPEN_SUBPATH_LENGTH) => {
+                    let is_closed = selector == CLOSED_SUBPATH_LENGTH;
+                    path.subpaths.push(Subpath::new(Vec::new(), is_closed));
+                }
+                selector @ (CLOSED_SUBPATH_LINKED_KNOT
+                | CLOSED_SUBPATH_UNLINKED_KNOT
+                | OPEN_SUBPATH_LINKED_KNOT
+                | OPEN_SUBPATH_UNLINKED_KNOT) => {
+                    let mut points = [Point::zero(); 3];
+                    for point in points.iter_mut() {
+                        let y_position = record.read_i32()?;
+                        let x_position = record.read_i32()?;
+                        *point = Point {
+                            x: x_position as f64 / FIXED_POINT_SCALE * size.width as f64,
+                            y: y_position as f64 / FIXED_POINT_SCALE * size.height as f64,
+                        };
+                    }
+                    let knot = BezierKnot {
+                        preceding: points[0],
+                        anchor: points[1],
+                        leaving: points[2],
+                        is_linked: matches!(
+                            selector,
+                            CLOSED_SUBPATH_LINKED_KNOT | OPEN_SUBPATH_LINKED_KNOT
+                        ),
+                    };
+                    if let Some(subpath) = path.subpaths.last_mut() {
+                        subpath.knots.push(knot);
+                    }
+                }
+                // The fill rule and clipboard records.
+                _ => (),
+            }
+        }
+        Ok(path)
+    }
+}
+
+/// Returns a position as an 8.24 fixed point fraction of a length.
+fn fixed_point_value(position: f64, length: u32) -> i32 {
+    if length == 0 {
+        return 0;
+    }
+    (position / length as f64 * FIXED_POINT_SCALE).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rectangle_data() {
+        let size = Size {
+            width: 200,
+            height: 100,
+        };
+        let knots = [(50.0, 25.0), (150.0, 25.0), (150.0, 75.0), (50.0, 75.0)]
+            .iter()
+            .map(|&(x, y)| BezierKnot::corner(Point { x, y }))
+            .collect();
+        let path = Path::new("Cut", vec![Subpath::new(knots, true)]);
+
+        let data = path.data(size).unwrap();
+
+        // The fill rule, the subpath length, then the four knots.
+        assert_eq!(data.len(), 6 * RECORD_LENGTH);
+        assert_eq!(data[0..2], [0x00, 0x06]);
+        assert_eq!(data[26..30], [0x00, 0x00, 0x00, 0x04]);
+
+        // An unlinked knot in a closed subpath, at a quarter of the
+        // height and a quarter of the width, three times.
+        let mut knot_data = vec![0x00, 0x02];
+        for _ in 0..3 {
+            knot_data.extend_from_slice(&[0x00, 0x40, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00]);
+        }
+        assert_eq!(data[52..78], knot_data);
+
+        let mut reader = Reader::new(&data);
+        let result = Path::from_reader(&mut reader, "Cut", size).unwrap();
+        assert_eq!(result, path);
+    }
+}
@@ -0,0 +1,32 @@
+/// How an image is placed on the page when it’s printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrintScaleStyle {
+    /// Centred on the page.
+    #[default]
+    Centered,
+    /// Scaled to fit the page.
+    SizeToFit,
+    /// Placed using the position and scale given.
+    UserDefined,
+}
+
+impl PrintScaleStyle {
+    /// Creates a new print scale style from a raw value.
+    pub fn from_value(value: i16) -> Option<Self> {
+        match value {
+            0 => Some(PrintScaleStyle::Centered),
+            1 => Some(PrintScaleStyle::SizeToFit),
+            2 => Some(PrintScaleStyle::UserDefined),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw value for the print scale style.
+    pub fn raw_value(&self) -> i16 {
+        match self {
+            PrintScaleStyle::Centered => 0,
+            PrintScaleStyle::SizeToFit => 1,
+            PrintScaleStyle::UserDefined => 2,
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use file_stream::write::FileStreamWriter;
+
+use crate::reader::Reader;
+
+use super::print_scale_style::PrintScaleStyle;
+
+/// The settings used when printing a document, so that it prints
+/// the same way every time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrintSettings {
+    /// How the image is placed on the page.
+    pub style: PrintScaleStyle,
+    /// The horizontal position of the image on the page.
+    pub x_position: f32,
+    /// The vertical position of the image on the page.
+    pub y_position: f32,
+    /// The scale of the image, where 1 is the actual size.
+    pub scale: f32,
+    /// Whether the crop marks are centred.
+    pub centers_crop_marks: bool,
+    /// Whether the file name is printed as a label.
+    pub prints_labels: bool,
+}
+
+// MARK: Creation
+
+impl Default for PrintSettings {
+    fn default() -> Self {
+        Self {
+            style: PrintScaleStyle::default(),
+            x_position: 0.0,
+            y_position: 0.0,
+            scale: 1.0,
+            centers_crop_marks: false,
+            prints_labels: false,
+        }
+    }
+}
+
+// MARK: Encoding
+
+impl PrintSettings {
+    /// Returns the data for the print scale resource. The position
+    /// and scale are 32-bit floating-point numbers.
+    pub(crate) fn print_scale_data(&self) -> anyhow::Result<Vec<u8>> {
+        let mut file_stream = FileStreamWriter::new();
+        file_stream.write_be(&self.style.raw_value())?;
+        file_stream.write_be(&self.x_position.to_bits())?;
+        file_stream.write_be(&self.y_position.to_bits())?;
+        file_stream.write_be(&self.scale.to_bits())?;
+        Ok(file_stream.data().to_vec())
+    }
+
+    /// Returns the data for the print flags resource, which is a series
+    /// of booleans. Only the labels flag is set by these settings.
+    pub(crate) fn print_flags_data(&self) -> Vec<u8> {
+        // Labels, crop marks, colour bars, registration marks, negative,
+        // flip, interpolate, caption and print flags.
+        let mut data = vec![0; 9];
+        data[0] = self.prints_labels as u8;
+        data
+    }
+
+    /// Returns the data for the print flags information resource.
+    pub(crate) fn print_flags_information_data(&self) -> anyhow::Result<Vec<u8>> {
+        let mut file_stream = FileStreamWriter::new();
+        // The version.
+        file_stream.write_be(&1u16)?;
+        file_stream.write_be(&(self.centers_crop_marks as u8))?;
+        file_stream.write_be(&0u8)?;
+        // The bleed width value and scale.
+        file_stream.write_be(&0u32)?;
+        file_stream.write_be(&0u16)?;
+        Ok(file_stream.data().to_vec())
+    }
+}
+
+// MARK: Decoding
+
+impl PrintSettings {
+    /// Reads the print scale resource into the settings.
+    pub(crate) fn read_print_scale(&mut self, reader: &mut Reader) -> anyhow::Result<()> {
+        self.style = PrintScaleStyle::from_value(reader.read_i16()?).unwrap_or_default();
+        self.x_position = f32::from_bits(reader.read_u32()?);
+        self.y_position = f32::from_bits(reader.read_u32()?);
+        self.scale = f32::from_bits(reader.read_u32()?);
+        Ok(())
+    }
+
+    /// Reads the print flags resource into the settings.
+    pub(crate) fn read_print_flags(&mut self, reader: &mut Reader) -> anyhow::Result<()> {
+        self.prints_labels = reader.read_u8()? != 0;
+        Ok(())
+    }
+
+    /// Reads the print flags information resource into the settings.
+    pub(crate) fn read_print_flags_information(
+        &mut self,
+        reader: &mut Reader,
+    ) -> anyhow::Result<()> {
+        // The version.
+        reader.skip(2)?;
+        self.centers_crop_marks = reader.read_u8()? != 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_scale_data() {
+        let print_settings = PrintSettings {
+            style: PrintScaleStyle::UserDefined,
+            x_position: 0.5,
+            ..Default::default()
+        };
+
+        let data = print_settings.print_scale_data().unwrap();
+
+        let expected_data = vec![
+            0x00, 0x02, // Style
+            0x3f, 0x00, 0x00, 0x00, // x position
+            0x00, 0x00, 0x00, 0x00, // y position
+            0x3f, 0x80, 0x00, 0x00, // Scale
+        ];
+        assert_eq!(data, expected_data);
+
+        let mut result = PrintSettings::default();
+        result.read_print_scale(&mut Reader::new(&data)).unwrap();
+        assert_eq!(result, print_settings);
+    }
+}
@@ -0,0 +1,24 @@
+/// An image resource that isn’t otherwise understood, kept
+/// as is so that it can be written back out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawResource {
+    /// The resource identifier.
+    pub identifier: i16,
+    /// The resource name, which is usually empty.
+    pub name: String,
+    /// The resource data, without padding.
+    pub data: Vec<u8>,
+}
+
+// MARK: Creation
+
+impl RawResource {
+    /// Creates a new raw resource with an empty name.
+    pub fn new(identifier: i16, data: Vec<u8>) -> Self {
+        Self {
+            identifier,
+            name: String::new(),
+            data,
+        }
+    }
+}
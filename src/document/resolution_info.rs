@@ -0,0 +1,111 @@
+use file_stream::write::FileStreamWriter;
+
+use crate::reader::Reader;
+
+use super::resolution_unit::ResolutionUnit;
+
+/// The resolution of a document, as stored in the resolution info
+/// image resource.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResolutionInfo {
+    /// The horizontal resolution in pixels per inch. Photoshop always
+    /// stores the resolution in pixels per inch, whatever the unit.
+    pub horizontal_resolution: f64,
+    /// The vertical resolution in pixels per inch.
+    pub vertical_resolution: f64,
+    /// The unit the resolution and sizes are shown in.
+    pub unit: ResolutionUnit,
+}
+
+// MARK: Creation
+
+impl ResolutionInfo {
+    /// Creates new resolution info with the same resolution in
+    /// both directions, in pixels per inch.
+    pub fn new(resolution: f64, unit: ResolutionUnit) -> Self {
+        Self {
+            horizontal_resolution: resolution,
+            vertical_resolution: resolution,
+            unit,
+        }
+    }
+}
+
+impl Default for ResolutionInfo {
+    fn default() -> Self {
+        Self::new(72.0, ResolutionUnit::PixelsPerInch)
+    }
+}
+
+// MARK: Encoding
+
+impl ResolutionInfo {
+    /// Returns the data for the resolution info resource. The
+    /// resolutions are 16.16 fixed-point numbers.
+    pub(crate) fn data(&self) -> anyhow::Result<Vec<u8>> {
+        let mut file_stream = FileStreamWriter::new();
+        for resolution in [self.horizontal_resolution, self.vertical_resolution] {
+            file_stream.write_be(&((resolution * 65536.0).round() as u32))?;
+            file_stream.write_be(&self.unit.raw_value())?;
+            file_stream.write_be(&self.unit.size_unit_raw_value())?;
+        }
+        Ok(file_stream.data().to_vec())
+    }
+}
+
+// MARK: Decoding
+
+impl ResolutionInfo {
+    /// Reads the resolution info from the resource data. The unit is
+    /// taken from the horizontal resolution.
+    pub(crate) fn from_reader(reader: &mut Reader) -> anyhow::Result<Self> {
+        let horizontal_resolution = reader.read_u32()? as f64 / 65536.0;
+        let unit = ResolutionUnit::from_value(reader.read_i16()?).unwrap_or_default();
+        // The width unit.
+        reader.skip(2)?;
+        let vertical_resolution = reader.read_u32()? as f64 / 65536.0;
+        Ok(Self {
+            horizontal_resolution,
+            vertical_resolution,
+            unit,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_data() {
+        let data = ResolutionInfo::default().data().unwrap();
+
+        let expected_data = vec![
+            0x00, 0x48, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x48, 0x00, 0x00, 0x00, 0x01,
+            0x00, 0x01,
+        ];
+        assert_eq!(data, expected_data);
+    }
+
+    #[test]
+    fn centimeter_data() {
+        let resolution_info = ResolutionInfo::new(300.5, ResolutionUnit::PixelsPerCentimeter);
+        let data = resolution_info.data().unwrap();
+
+        let expected_data = vec![
+            0x01, 0x2c, 0x80, 0x00, // Horizontal resolution
+            0x00, 0x02, // Pixels per centimetre
+            0x00, 0x02, // Centimetres
+            0x01, 0x2c, 0x80, 0x00, // Vertical resolution
+            0x00, 0x02, // Pixels per centimetre
+            0x00, 0x02, // Centimetres
+        ];
+        assert_eq!(data, expected_data);
+
+        let mut reader = Reader::new(&data);
+        assert_eq!(
+            ResolutionInfo::from_reader(&mut reader).unwrap(),
+            resolution_info
+        );
+    }
+}
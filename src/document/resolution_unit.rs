@@ -0,0 +1,37 @@
+/// The unit a document’s resolution is shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResolutionUnit {
+    /// Pixels per inch, with sizes shown in inches.
+    #[default]
+    PixelsPerInch,
+    /// Pixels per centimetre, with sizes shown in centimetres.
+    PixelsPerCentimeter,
+}
+
+impl ResolutionUnit {
+    /// Creates a new resolution unit from a raw value.
+    pub fn from_value(value: i16) -> Option<Self> {
+        match value {
+            1 => Some(ResolutionUnit::PixelsPerInch),
+            2 => Some(ResolutionUnit::PixelsPerCentimeter),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw value for the resolution unit.
+    pub fn raw_value(&self) -> i16 {
+        match self {
+            ResolutionUnit::PixelsPerInch => 1,
+            ResolutionUnit::PixelsPerCentimeter => 2,
+        }
+    }
+
+    /// Returns the raw value for the unit sizes are shown in, which
+    /// is inches (1) or centimetres (2) to match the resolution.
+    pub fn size_unit_raw_value(&self) -> i16 {
+        match self {
+            ResolutionUnit::PixelsPerInch => 1,
+            ResolutionUnit::PixelsPerCentimeter => 2,
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use file_stream::write::FileStreamWriter;
+use graphics::{Rect, Size};
+
+use crate::endian::WriteBigEndian;
+
+/// The version of the slices resource.
+const VERSION: u32 = 6;
+
+/// The origin of a slice made by the user rather than generated.
+const USER_ORIGIN: u32 = 2;
+
+/// The type of a slice that shows part of the image.
+const IMAGE_TYPE: u32 = 1;
+
+/// A named region of the document for web export, which links to a URL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slice {
+    /// The name of the slice, used for the exported image.
+    pub name: String,
+    /// The bounds of the slice in the document.
+    pub bounds: Rect<i32>,
+    /// The URL the slice links to, which can be empty.
+    pub url: String,
+}
+
+// MARK: Creation
+
+impl Slice {
+    /// Creates a new slice without a URL.
+    pub fn new(name: &str, bounds: Rect<i32>) -> Self {
+        Self {
+            name: name.to_string(),
+            bounds,
+            url: String::new(),
+        }
+    }
+}
+
+// MARK: Encoding
+
+/// Returns the data for the slices resource, in the version 6 layout.
+/// The slices are numbered from 1 in order, within a single unnamed
+/// group covering the document.
+pub(crate) fn data(slices: &[Slice], size: Size<u32>) -> anyhow::Result<Vec<u8>> {
+    let mut file_stream = FileStreamWriter::new();
+    file_stream.write_u32(VERSION)?;
+    // The bounds of the group: top, left, bottom and right.
+    file_stream.write_be(&0i32)?;
+    file_stream.write_be(&0i32)?;
+    file_stream.write_be(&(size.height as i32))?;
+    file_stream.write_be(&(size.width as i32))?;
+    write_unicode_string(&mut file_stream, "")?;
+    file_stream.write_u32(slices.len() as u32)?;
+
+    for (index, slice) in slices.iter().enumerate() {
+        file_stream.write_u32(index as u32 + 1)?;
+        // The group identifier.
+        file_stream.write_u32(0)?;
+        file_stream.write_u32(USER_ORIGIN)?;
+        write_unicode_string(&mut file_stream, &slice.name)?;
+        file_stream.write_u32(IMAGE_TYPE)?;
+        file_stream.write_be(&slice.bounds.min_x())?;
+        file_stream.write_be(&slice.bounds.min_y())?;
+        file_stream.write_be(&slice.bounds.max_x())?;
+        file_stream.write_be(&slice.bounds.max_y())?;
+        write_unicode_string(&mut file_stream, &slice.url)?;
+        // The target, message, alt tag, whether the cell text is HTML,
+        // the cell text, the horizontal and vertical alignment, and the
+        // background colour as alpha, red, green and blue.
+        write_unicode_string(&mut file_stream, "")?;
+        write_unicode_string(&mut file_stream, "")?;
+        write_unicode_string(&mut file_stream, "")?;
+        file_stream.write_bytes(&[0])?;
+        write_unicode_string(&mut file_stream, "")?;
+        file_stream.write_u32(0)?;
+        file_stream.write_u32(0)?;
+        file_stream.write_bytes(&[0, 0, 0, 0])?;
+    }
+
+    Ok(file_stream.data().to_vec())
+}
+
+/// Writes a Unicode string as the number of UTF-16 code units followed
+/// by the code units.
+fn write_unicode_string(file_stream: &mut FileStreamWriter, string: &str) -> anyhow::Result<()> {
+    file_stream.write_u32(string.encode_utf16().count() as u32)?;
+    for code_unit in string.encode_utf16() {
+        file_stream.write_u16(code_unit)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slices_data() {
+        let mut slice = Slice::new("A", Rect::new(1, 2, 3, 4));
+        slice.url = "b".to_string();
+        let size = Size {
+            width: 8,
+            height: 6,
+        };
+
+        let expected_data = [
+            0x00, 0x00, 0x00, 0x06, // Version
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Top and left
+            0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x08, // Bottom and right
+            0x00, 0x00, 0x00, 0x00, // Empty group name
+            0x00, 0x00, 0x00, 0x01, // Number of slices
+            0x00, 0x00, 0x00, 0x01, // Identifier
+            0x00, 0x00, 0x00, 0x00, // Group identifier
+            0x00, 0x00, 0x00, 0x02, // User origin
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x41, // Name
+            0x00, 0x00, 0x00, 0x01, // Image type
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, // Left and top
+            0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x06, // Right and bottom
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x62, // URL
+            0x00, 0x00, 0x00, 0x00, // Empty target
+            0x00, 0x00, 0x00, 0x00, // Empty message
+            0x00, 0x00, 0x00, 0x00, // Empty alt tag
+            0x00, // Cell text isn’t HTML
+            0x00, 0x00, 0x00, 0x00, // Empty cell text
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Alignment
+            0x00, 0x00, 0x00, 0x00, // Background colour
+        ];
+        assert_eq!(data(&[slice], size).unwrap(), expected_data);
+    }
+}
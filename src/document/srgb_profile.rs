@@ -0,0 +1,174 @@
+//! A small ICC profile for sRGB, written for documents that ask for one
+//! without supplying their own profile.
+
+use file_stream::write::FileStreamWriter;
+
+use crate::data;
+use crate::endian::WriteBigEndian;
+
+/// The description shown for the profile.
+const DESCRIPTION: &str = "sRGB IEC61966-2.1";
+
+/// The copyright text for the profile.
+const COPYRIGHT: &str = "No copyright, use freely";
+
+/// The length of the profile header.
+const HEADER_LENGTH: usize = 128;
+
+/// The number of entries in the tone curve table.
+const CURVE_LENGTH: usize = 256;
+
+/// The D50 illuminant that the profile connection space uses.
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+/// The red, green and blue primaries, adapted to D50.
+const PRIMARIES: [(&[u8; 4], [f64; 3]); 3] = [
+    (b"rXYZ", [0.4361, 0.2225, 0.0139]),
+    (b"gXYZ", [0.3851, 0.7169, 0.0971]),
+    (b"bXYZ", [0.1431, 0.0606, 0.7141]),
+];
+
+/// Returns the data for a version 2 ICC display profile for sRGB.
+pub(crate) fn data() -> anyhow::Result<Vec<u8>> {
+    let curve = curve_data()?;
+    let mut tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"desc", description_data()?),
+        (b"cprt", text_data(COPYRIGHT)?),
+        (b"wtpt", xyz_data(D50)?),
+    ];
+    for (signature, primary) in PRIMARIES {
+        tags.push((signature, xyz_data(primary)?));
+    }
+    // The three tone curves share the same data.
+    for signature in [b"rTRC", b"gTRC", b"bTRC"] {
+        tags.push((signature, curve.clone()));
+    }
+
+    // The tag table, followed by the padded data for each tag.
+    let mut tag_table = FileStreamWriter::new();
+    let mut tag_data = Vec::new();
+    let tag_table_length = 4 + tags.len() * 12;
+    tag_table.write_u32(tags.len() as u32)?;
+    for (signature, data) in &tags {
+        let offset = HEADER_LENGTH + tag_table_length + tag_data.len();
+        tag_table.write_bytes(*signature)?;
+        tag_table.write_u32(offset as u32)?;
+        tag_table.write_u32(data.len() as u32)?;
+        tag_data.extend_from_slice(data);
+        data::pad(&mut tag_data, 4);
+    }
+    let length = HEADER_LENGTH + tag_table_length + tag_data.len();
+
+    let mut file_stream = FileStreamWriter::new();
+    file_stream.write_u32(length as u32)?;
+    // No preferred colour management module.
+    file_stream.write_u32(0)?;
+    // Version 2.1.
+    file_stream.write_u32(0x02100000)?;
+    file_stream.write_bytes(b"mntr")?;
+    file_stream.write_bytes(b"RGB ")?;
+    file_stream.write_bytes(b"XYZ ")?;
+    // The creation date, as the year, month, day, hour, minute and second.
+    for value in [1998, 2, 9, 6, 49, 0] {
+        file_stream.write_u16(value)?;
+    }
+    file_stream.write_bytes(b"acsp")?;
+    // The platform, flags, manufacturer, model, attributes and
+    // rendering intent, which are all left unset.
+    file_stream.write_zeros(28)?;
+    for value in D50 {
+        file_stream.write_u32(s15_fixed_16(value))?;
+    }
+    // The creator, followed by reserved bytes.
+    file_stream.write_zeros(HEADER_LENGTH - 80)?;
+
+    file_stream.write_bytes(tag_table.data())?;
+    file_stream.write_bytes(&tag_data)?;
+    Ok(file_stream.data().to_vec())
+}
+
+/// Returns a number as the signed 15.16 fixed point value ICC profiles use.
+fn s15_fixed_16(value: f64) -> u32 {
+    (value * 65536.0).round() as i32 as u32
+}
+
+/// Returns the data for a text description tag.
+fn description_data() -> anyhow::Result<Vec<u8>> {
+    let mut file_stream = FileStreamWriter::new();
+    file_stream.write_bytes(b"desc")?;
+    file_stream.write_zeros(4)?;
+    // The ASCII description, including the null.
+    file_stream.write_u32(DESCRIPTION.len() as u32 + 1)?;
+    file_stream.write_bytes(DESCRIPTION.as_bytes())?;
+    file_stream.write_zeros(1)?;
+    // Empty Unicode and ScriptCode descriptions.
+    file_stream.write_zeros(8 + 2 + 1 + 67)?;
+    Ok(file_stream.data().to_vec())
+}
+
+/// Returns the data for a text tag.
+fn text_data(text: &str) -> anyhow::Result<Vec<u8>> {
+    let mut file_stream = FileStreamWriter::new();
+    file_stream.write_bytes(b"text")?;
+    file_stream.write_zeros(4)?;
+    file_stream.write_bytes(text.as_bytes())?;
+    file_stream.write_zeros(1)?;
+    Ok(file_stream.data().to_vec())
+}
+
+/// Returns the data for an XYZ tag.
+fn xyz_data(value: [f64; 3]) -> anyhow::Result<Vec<u8>> {
+    let mut file_stream = FileStreamWriter::new();
+    file_stream.write_bytes(b"XYZ ")?;
+    file_stream.write_zeros(4)?;
+    for component in value {
+        file_stream.write_u32(s15_fixed_16(component))?;
+    }
+    Ok(file_stream.data().to_vec())
+}
+
+/// Returns the data for the sRGB tone curve, as a table.
+fn curve_data() -> anyhow::Result<Vec<u8>> {
+    let mut file_stream = FileStreamWriter::new();
+    file_stream.write_bytes(b"curv")?;
+    file_stream.write_zeros(4)?;
+    file_stream.write_u32(CURVE_LENGTH as u32)?;
+    for index in 0..CURVE_LENGTH {
+        let value = index as f64 / (CURVE_LENGTH - 1) as f64;
+        let linear = if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        };
+        file_stream.write_u16((linear * u16::MAX as f64).round() as u16)?;
+    }
+    Ok(file_stream.data().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::Reader;
+
+    #[test]
+    fn data() {
+        let data = super::data().unwrap();
+        let mut reader = Reader::new(&data);
+
+        assert_eq!(reader.read_u32().unwrap() as usize, data.len());
+        assert_eq!(data[36..40], *b"acsp");
+
+        // Every tag lies within the profile.
+        reader.skip(124).unwrap();
+        let number_of_tags = reader.read_u32().unwrap();
+        assert_eq!(number_of_tags, 9);
+        for _ in 0..number_of_tags {
+            let signature = reader.read_array::<4>().unwrap();
+            let offset = reader.read_u32().unwrap() as usize;
+            let length = reader.read_u32().unwrap() as usize;
+            assert!(offset + length <= data.len());
+            if signature == *b"desc" {
+                assert_eq!(data[offset + 12..offset + 29], *b"sRGB IEC61966-2.1");
+            }
+        }
+    }
+}
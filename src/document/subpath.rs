@@ -0,0 +1,19 @@
+use super::BezierKnot;
+
+/// A single connected run of knots in a path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subpath {
+    /// Whether the last knot joins back up with the first.
+    pub is_closed: bool,
+    /// The knots, in order.
+    pub knots: Vec<BezierKnot>,
+}
+
+// MARK: Creation
+
+impl Subpath {
+    /// Creates a new subpath.
+    pub fn new(knots: Vec<BezierKnot>, is_closed: bool) -> Self {
+        Self { is_closed, knots }
+    }
+}
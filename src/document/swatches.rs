@@ -0,0 +1,70 @@
+use file_stream::write::FileStreamWriter;
+use graphics::Color;
+
+use crate::color_space::ColorSpace;
+
+/// Returns the swatches encoded in the layout Photoshop uses for its
+/// swatches panel (an `.aco` file): a version 1 section without names,
+/// followed by a version 2 section with UTF-16 names.
+pub(crate) fn data(swatches: &[(String, Color, ColorSpace)]) -> anyhow::Result<Vec<u8>> {
+    let mut file_stream = FileStreamWriter::new();
+
+    for version in [1u16, 2u16] {
+        file_stream.write_be(&version)?;
+        file_stream.write_be(&(swatches.len() as u16))?;
+
+        for (name, color, color_space) in swatches.iter() {
+            file_stream.write_be(&color_space.color_mode())?;
+            file_stream.write_bytes(&color_space.encode(color))?;
+
+            if version == 2 {
+                // The length includes the null terminator.
+                let length = name.encode_utf16().count() as u32 + 1;
+                file_stream.write_be(&length)?;
+                for character in name.encode_utf16() {
+                    file_stream.write_be(&character)?;
+                }
+                file_stream.write_be(&0u16)?;
+            }
+        }
+    }
+
+    Ok(file_stream.data().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use graphics::Color;
+
+    use crate::color_space::ColorSpace;
+
+    #[test]
+    fn data() {
+        let swatches = vec![
+            ("Red".to_string(), Color::RED, ColorSpace::Rgb),
+            ("Ink".to_string(), Color::BLACK, ColorSpace::Grayscale),
+        ];
+
+        let result = super::data(&swatches).unwrap();
+
+        let expected_data = vec![
+            0x00, 0x01, // Version 1
+            0x00, 0x02, // Number of swatches
+            0x00, 0x00, // RGB
+            0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Red
+            0x00, 0x08, // Greyscale
+            0x27, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Black
+            0x00, 0x02, // Version 2
+            0x00, 0x02, // Number of swatches
+            0x00, 0x00, // RGB
+            0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Red
+            0x00, 0x00, 0x00, 0x04, // Name length
+            0x00, 0x52, 0x00, 0x65, 0x00, 0x64, 0x00, 0x00, // Red
+            0x00, 0x08, // Greyscale
+            0x27, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Black
+            0x00, 0x00, 0x00, 0x04, // Name length
+            0x00, 0x49, 0x00, 0x6e, 0x00, 0x6b, 0x00, 0x00, // Ink
+        ];
+        assert_eq!(result, expected_data);
+    }
+}
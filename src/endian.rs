@@ -0,0 +1,55 @@
+use file_stream::write::FileStreamWriter;
+
+/// Typed big endian writers, matching the reads of `Reader` so that
+/// the width of every value is spelled out where it’s written. More
+/// widths can be added as other sections move over to these.
+pub(crate) trait WriteBigEndian {
+    /// Writes a big endian signed 16-bit integer.
+    fn write_i16(&mut self, value: i16) -> anyhow::Result<()>;
+
+    /// Writes a big endian unsigned 16-bit integer.
+    fn write_u16(&mut self, value: u16) -> anyhow::Result<()>;
+
+    /// Writes a big endian unsigned 32-bit integer.
+    fn write_u32(&mut self, value: u32) -> anyhow::Result<()>;
+}
+
+impl WriteBigEndian for FileStreamWriter {
+    fn write_i16(&mut self, value: i16) -> anyhow::Result<()> {
+        self.write_be(&value)?;
+        Ok(())
+    }
+
+    fn write_u16(&mut self, value: u16) -> anyhow::Result<()> {
+        self.write_be(&value)?;
+        Ok(())
+    }
+
+    fn write_u32(&mut self, value: u32) -> anyhow::Result<()> {
+        self.write_be(&value)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use file_stream::write::FileStreamWriter;
+
+    use crate::reader::Reader;
+
+    use super::WriteBigEndian;
+
+    #[test]
+    fn write_values() {
+        let mut file_stream = FileStreamWriter::new();
+        file_stream.write_i16(-2).unwrap();
+        file_stream.write_u16(0xabcd).unwrap();
+        file_stream.write_u32(0x12345678).unwrap();
+
+        let mut reader = Reader::new(file_stream.data());
+        assert_eq!(reader.read_i16().unwrap(), -2);
+        assert_eq!(reader.read_u16().unwrap(), 0xabcd);
+        assert_eq!(reader.read_u32().unwrap(), 0x12345678);
+        assert_eq!(reader.remaining(), 0);
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::color_channel::ColorChannelType;
+
 #[derive(Error, Debug)]
 /// An error that can occur when writing PSD data.
 pub enum WriteError {
@@ -7,4 +9,146 @@ pub enum WriteError {
     UnsupportedCompression,
     #[error("The image is invalid.")]
     InvalidImage,
+    #[error("The image data is too short for the pixel at {0}, {1}.")]
+    InvalidImageData(u32, u32),
+    #[error("The layer index {0} is out of range.")]
+    InvalidLayerIndex(usize),
+    #[error("The colour mode data is missing.")]
+    MissingColorModeData,
+    #[error("The document size {0}×{1} is too large for the file format.")]
+    InvalidDocumentSize(u32, u32),
+    #[error("An RLE encoded row is too long to store its length.")]
+    RleRowOverflow,
+    #[error("The layer image size doesn’t match the layer bounds.")]
+    BoundsMismatch,
+    #[error("The preview image size {0}×{1} doesn’t match the document size.")]
+    PreviewSizeMismatch(u32, u32),
+    #[error("The palette has {0} colours, but can have no more than 256.")]
+    PaletteTooLarge(usize),
+    #[error("Indexed documents can’t have layers, only a merged image.")]
+    LayersInIndexedDocument,
+    #[error(
+        "A section of {0} bytes is too large to store its length. Try a large document (PSB)."
+    )]
+    SectionTooLarge(usize),
+    #[error("The document has {0} paths, but can have no more than 999.")]
+    TooManyPaths(usize),
+    #[error("The document has {0} layer records, but can have no more than 32767.")]
+    TooManyLayers(usize),
+    #[error("A length section was ended without being started, or never ended.")]
+    UnbalancedLengthSections,
+}
+
+#[derive(Error, Debug)]
+/// An error that can occur when changing layers.
+pub enum LayerError {
+    #[error("The layer isn’t a group.")]
+    NotAGroup,
+    #[error("The layer has no {0} channel.")]
+    MissingChannel(ColorChannelType),
+}
+
+#[derive(Error, Debug)]
+/// An error that can occur when reading PSD data.
+pub enum ReadError {
+    #[error("The file signature is invalid.")]
+    InvalidSignature,
+    #[error("The file version {0} is not supported.")]
+    UnsupportedVersion(i16),
+    #[error("The colour mode {0} is not supported.")]
+    UnsupportedColorMode(i16),
+    #[error("The bit depth {0} is not supported.")]
+    UnsupportedBitDepth(u16),
+    #[error("The image compression {0} is not supported.")]
+    UnsupportedCompression(i16),
+    #[error("The data ended unexpectedly at byte {0}.")]
+    UnexpectedEnd(usize),
+    #[error("The RLE data is invalid.")]
+    InvalidRleData,
+    #[error("The ZIP data is invalid.")]
+    InvalidZipData,
+    #[error("The layer group markers are unbalanced.")]
+    UnbalancedGroups,
+    #[error("The file has no merged image data.")]
+    MissingMergedImage,
+    #[error("The guide direction {0} is invalid.")]
+    InvalidGuideDirection(u8),
+}
+
+#[derive(Error, Debug)]
+/// A problem found when validating PSD data.
+pub enum ValidationError {
+    #[error("The file signature is invalid.")]
+    InvalidSignature,
+    #[error("The file version {0} is not supported.")]
+    UnsupportedVersion(i16),
+    #[error("The {0} runs past the end of the data that holds it.")]
+    Overrun(&'static str),
+    #[error("The {0} has {1} bytes left over.")]
+    UnusedData(&'static str, usize),
+    #[error("The image resource signature is invalid.")]
+    InvalidResourceSignature,
+    #[error("The image compression {0} is not supported.")]
+    UnsupportedCompression(i16),
+    #[error("The file can’t be read: {0}")]
+    Unreadable(String),
+}
+
+#[derive(Error, Debug)]
+/// An error returned by the public API, so that callers can match on
+/// what went wrong without depending on `anyhow`.
+pub enum Error {
+    #[error(transparent)]
+    Write(#[from] WriteError),
+    #[error(transparent)]
+    Read(#[from] ReadError),
+    #[error(transparent)]
+    Layer(#[from] LayerError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Other(String),
+}
+
+/// A result with the crate’s error type.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<anyhow::Error> for Error {
+    /// Recovers the crate error from an error raised internally, keeping
+    /// the message of any other error.
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<Error>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<WriteError>() {
+            Ok(error) => return Error::Write(error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<ReadError>() {
+            Ok(error) => return Error::Read(error),
+            Err(error) => error,
+        };
+        match error.downcast::<LayerError>() {
+            Ok(error) => Error::Layer(error),
+            Err(error) => Error::Other(error.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_from_anyhow() {
+        let error = Error::from(anyhow::Error::new(WriteError::RleRowOverflow));
+        assert!(matches!(error, Error::Write(WriteError::RleRowOverflow)));
+
+        let error = Error::from(anyhow::Error::new(Error::Read(ReadError::InvalidRleData)));
+        assert!(matches!(error, Error::Read(ReadError::InvalidRleData)));
+
+        let error = Error::from(anyhow::anyhow!("Something else."));
+        assert!(matches!(error, Error::Other(message) if message == "Something else."));
+    }
 }
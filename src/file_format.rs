@@ -0,0 +1,122 @@
+use file_stream::write::FileStreamWriter;
+
+use crate::data;
+use crate::document::constants;
+
+/// The formats a Photoshop document can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    /// A standard Photoshop document (PSD).
+    Psd,
+    /// A large document (PSB), needed for documents over 30,000 pixels
+    /// wide or high, or over 2GB.
+    Psb,
+}
+
+impl FileFormat {
+    /// Returns the version number written to the header.
+    pub fn version(&self) -> i16 {
+        match self {
+            FileFormat::Psd => constants::VERSION_NUMBER,
+            FileFormat::Psb => constants::LARGE_DOCUMENT_VERSION_NUMBER,
+        }
+    }
+
+    /// Returns the maximum width and height of a document, in pixels.
+    pub fn maximum_dimension(&self) -> u32 {
+        match self {
+            FileFormat::Psd => 30_000,
+            FileFormat::Psb => 300_000,
+        }
+    }
+}
+
+// MARK: Encoding
+
+impl FileFormat {
+    /// Writes the length of a section, which is 64-bit in large documents.
+    pub(crate) fn write_length(
+        &self,
+        file_stream: &mut FileStreamWriter,
+        length: usize,
+    ) -> anyhow::Result<()> {
+        match self {
+            FileFormat::Psd => file_stream.write_be(&data::section_length(length)?)?,
+            FileFormat::Psb => file_stream.write_be(&(length as u64))?,
+        }
+        Ok(())
+    }
+
+    /// Returns RLE data for the format. The data starts with the byte
+    /// count of each row, which are 32-bit in large documents.
+    pub(crate) fn rle_data(&self, data: &[u8], number_of_rows: usize) -> Vec<u8> {
+        let line_lengths_length = (number_of_rows * 2).min(data.len());
+        if *self == FileFormat::Psd {
+            return data.to_vec();
+        }
+
+        let mut output = Vec::with_capacity(data.len() + line_lengths_length);
+        for line_length in data[..line_lengths_length].chunks_exact(2) {
+            output.extend_from_slice(&[0, 0]);
+            output.extend_from_slice(line_length);
+        }
+        output.extend_from_slice(&data[line_lengths_length..]);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::WriteError;
+
+    use super::*;
+
+    #[test]
+    fn write_length() {
+        let mut file_stream = FileStreamWriter::new();
+        FileFormat::Psd
+            .write_length(&mut file_stream, 0x1234)
+            .unwrap();
+        FileFormat::Psb
+            .write_length(&mut file_stream, 0x1234)
+            .unwrap();
+
+        assert_eq!(
+            file_stream.data(),
+            [0x00, 0x00, 0x12, 0x34, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x34]
+        );
+    }
+
+    #[test]
+    fn write_oversized_length() {
+        let length = u32::MAX as usize + 1;
+        let mut file_stream = FileStreamWriter::new();
+
+        let error = FileFormat::Psd
+            .write_length(&mut file_stream, length)
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<WriteError>(),
+            Some(WriteError::SectionTooLarge(value)) if *value == length
+        ));
+        assert!(file_stream.data().is_empty());
+
+        // Large documents have room for the length.
+        FileFormat::Psb
+            .write_length(&mut file_stream, length)
+            .unwrap();
+        assert_eq!(file_stream.data(), [0, 0, 0, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rle_data() {
+        // Two rows of three bytes.
+        let data = [0x00, 0x03, 0x00, 0x03, 0x01, 0xfb, 0xe5, 0x01, 0x42, 0x20];
+
+        assert_eq!(FileFormat::Psd.rle_data(&data, 2), data);
+        assert_eq!(
+            FileFormat::Psb.rle_data(&data, 2),
+            [0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x03, 0x01, 0xfb, 0xe5, 0x01, 0x42, 0x20]
+        );
+    }
+}
@@ -1,72 +1,402 @@
 use file_stream::write::FileStreamWriter;
-use graphics::Image;
+use graphics::{Color, Image, Rect, Size};
 
 use crate::{
-    color_channel::{ColorChannel, ColorChannelType},
-    error::WriteError,
+    color_channel::{self, ColorChannel, ColorChannelType},
+    error::{self, ReadError, WriteError},
     image_compression::ImageCompression,
+    reader::Reader,
+    rle, zip,
 };
 
-/// Returns the image data for use in Photoshop documents.
-pub fn psd_data(image: &Image, compression: &ImageCompression) -> anyhow::Result<Vec<u8>> {
-    match compression {
-        ImageCompression::ZipWithoutPrediction | ImageCompression::ZipWithPrediction => {
-            anyhow::bail!(WriteError::UnsupportedCompression)
-        }
-        _ => (),
-    }
-
+/// Returns the image data for use in Photoshop documents. The alpha
+/// channel is left out when the number of channels is 3 or fewer.
+pub fn psd_data(
+    image: &Image,
+    number_of_channels: u16,
+    compression: &ImageCompression,
+) -> anyhow::Result<Vec<u8>> {
+    validate_data(image)?;
     let data_length = (image.size.width * image.size.height) as usize;
     let mut red_channel = ColorChannel::new(ColorChannelType::Red, data_length);
     let mut green_channel = ColorChannel::new(ColorChannelType::Green, data_length);
     let mut blue_channel = ColorChannel::new(ColorChannelType::Blue, data_length);
     let mut alpha_channel = ColorChannel::new(ColorChannelType::Alpha, data_length);
 
+    let bytes_per_pixel = bytes_per_pixel(image);
+    for y_position in 0..image.size.height {
+        for x_position in 0..image.size.width {
+            let target_index = (y_position * image.size.width + x_position) as usize;
+            let source_index =
+                (y_position * image.bytes_per_row) as usize + x_position as usize * bytes_per_pixel;
+            let [red, green, blue, alpha] = rgba_components(image, source_index, bytes_per_pixel);
+            red_channel.data[target_index] = red;
+            green_channel.data[target_index] = green;
+            blue_channel.data[target_index] = blue;
+            alpha_channel.data[target_index] = alpha;
+        }
+    }
+
+    let mut channels = vec![red_channel, green_channel, blue_channel];
+    if number_of_channels > 3 {
+        channels.push(alpha_channel);
+    }
+    channels_psd_data(&channels, image.size.height, compression)
+}
+
+/// Returns the image data for a single grey channel, used by colour modes
+/// such as duotone that store their composite as greyscale.
+pub fn grayscale_psd_data(
+    image: &Image,
+    compression: &ImageCompression,
+) -> anyhow::Result<Vec<u8>> {
+    validate_data(image)?;
+    let data_length = (image.size.width * image.size.height) as usize;
+    let mut gray_channel = ColorChannel::new(ColorChannelType::Red, data_length);
+
+    let bytes_per_pixel = bytes_per_pixel(image);
+    for y_position in 0..image.size.height {
+        for x_position in 0..image.size.width {
+            let target_index = (y_position * image.size.width + x_position) as usize;
+            let source_index =
+                (y_position * image.bytes_per_row) as usize + x_position as usize * bytes_per_pixel;
+            let [red, green, blue, _] = rgba_components(image, source_index, bytes_per_pixel);
+            gray_channel.data[target_index] = luminance(red, green, blue);
+        }
+    }
+
+    channels_psd_data(&[gray_channel], image.size.height, compression)
+}
+
+/// Returns the image data for a single channel of indices into a
+/// palette, using the closest colour in the palette for each pixel.
+pub fn indexed_psd_data(
+    image: &Image,
+    palette: &[Color],
+    compression: &ImageCompression,
+) -> anyhow::Result<Vec<u8>> {
+    validate_data(image)?;
+    let data_length = (image.size.width * image.size.height) as usize;
+    let mut index_channel = ColorChannel::new(ColorChannelType::Red, data_length);
+
+    let bytes_per_pixel = bytes_per_pixel(image);
     for y_position in 0..image.size.height {
         for x_position in 0..image.size.width {
-            // For now we’re just assuming that the bytes
-            // are in RGBA order.
             let target_index = (y_position * image.size.width + x_position) as usize;
-            let source_index = ((y_position * image.bytes_per_row) + (x_position * 4)) as usize;
-            red_channel.data[target_index] = image.data[source_index];
-            green_channel.data[target_index] = image.data[source_index + 1];
-            blue_channel.data[target_index] = image.data[source_index + 2];
-            alpha_channel.data[target_index] = image.data[source_index + 3];
+            let source_index =
+                (y_position * image.bytes_per_row) as usize + x_position as usize * bytes_per_pixel;
+            let [red, green, blue, _] = rgba_components(image, source_index, bytes_per_pixel);
+            index_channel.data[target_index] = closest_palette_index(palette, red, green, blue);
+        }
+    }
+
+    channels_psd_data(&[index_channel], image.size.height, compression)
+}
+
+/// Returns the index of the palette colour closest to a colour.
+fn closest_palette_index(palette: &[Color], red: u8, green: u8, blue: u8) -> u8 {
+    let distance = |color: &Color| {
+        let difference = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        difference(color.red, red) + difference(color.green, green) + difference(color.blue, blue)
+    };
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, color)| distance(color))
+        .map_or(0, |(index, _)| index as u8)
+}
+
+/// Returns the number of bytes for each pixel, worked out from the
+/// row length: 1 for grey, 2 for grey and alpha, and 4 for RGBA.
+/// Anything else is taken to be RGBA.
+pub(crate) fn bytes_per_pixel(image: &Image) -> usize {
+    if image.size.width == 0 {
+        return 4;
+    }
+    match image.bytes_per_row / image.size.width {
+        1 => 1,
+        2 => 2,
+        _ => 4,
+    }
+}
+
+/// Returns the red, green, blue and alpha components of the pixel
+/// starting at an index. Grey is expanded to red, green and blue,
+/// and pixels without alpha are opaque.
+pub(crate) fn rgba_components(image: &Image, index: usize, bytes_per_pixel: usize) -> [u8; 4] {
+    let data = &image.data;
+    match bytes_per_pixel {
+        1 => [data[index], data[index], data[index], u8::MAX],
+        2 => [data[index], data[index], data[index], data[index + 1]],
+        _ => [
+            data[index],
+            data[index + 1],
+            data[index + 2],
+            data[index + 3],
+        ],
+    }
+}
+
+/// Checks that the image data holds every pixel, returning the
+/// position of the first pixel that doesn’t fit.
+pub(crate) fn validate_data(image: &Image) -> anyhow::Result<()> {
+    let bytes_per_pixel = bytes_per_pixel(image);
+    let row_length = image.size.width as usize * bytes_per_pixel;
+    let bytes_per_row = image.bytes_per_row as usize;
+    // Rows shorter than the pixels would overlap the next row.
+    if image.size.height > 0 && bytes_per_row < row_length {
+        let x_position = bytes_per_row / bytes_per_pixel;
+        anyhow::bail!(WriteError::InvalidImageData(x_position as u32, 0));
+    }
+
+    for y_position in 0..image.size.height {
+        let row_start = y_position as usize * bytes_per_row;
+        if row_start + row_length > image.data.len() {
+            let x_position = image.data.len().saturating_sub(row_start) / bytes_per_pixel;
+            anyhow::bail!(WriteError::InvalidImageData(x_position as u32, y_position));
         }
     }
 
+    Ok(())
+}
+
+/// Returns the grey value for a colour, using the Rec. 601 weights.
+pub(crate) fn luminance(red: u8, green: u8, blue: u8) -> u8 {
+    let value = 0.299 * red as f64 + 0.587 * green as f64 + 0.114 * blue as f64;
+    value.round().min(255.0) as u8
+}
+
+/// Returns the image data for a set of channels, in the order given.
+pub fn channels_psd_data(
+    channels: &[ColorChannel],
+    height: u32,
+    compression: &ImageCompression,
+) -> anyhow::Result<Vec<u8>> {
     let mut file_stream = FileStreamWriter::new();
-    file_stream.write_be(&compression.raw_value())?;
-    if compression == &ImageCompression::Rle {
-        let red = red_channel.rle_encoded_components(image.size.height)?;
-        let green = green_channel.rle_encoded_components(image.size.height)?;
-        let blue = blue_channel.rle_encoded_components(image.size.height)?;
-        let alpha = alpha_channel.rle_encoded_components(image.size.height)?;
+    if matches!(
+        compression,
+        ImageCompression::ZipWithoutPrediction | ImageCompression::ZipWithPrediction
+    ) {
+        // The channels are compressed together, one row after another.
+        let width = channels
+            .first()
+            .map_or(0, |channel| channel.data.len() / height.max(1) as usize);
+        let data: Vec<u8> = channels
+            .iter()
+            .flat_map(|channel| channel.data.iter().copied())
+            .collect();
+        file_stream.write_be(&compression.raw_value())?;
+        file_stream.write_bytes(&zip::encoded(&data, width, compression.uses_prediction())?)?;
+    } else if compression == &ImageCompression::Rle {
+        let components = match channels
+            .iter()
+            .map(|channel| channel.rle_encoded_components(height))
+            .collect::<error::Result<Vec<_>>>()
+        {
+            Ok(components) => components,
+            // Rows too long to record their encoded length are stored uncompressed.
+            Err(error) if color_channel::is_rle_row_overflow(&error) => {
+                return channels_psd_data(channels, height, &ImageCompression::RawData);
+            }
+            Err(error) => return Err(error.into()),
+        };
+        file_stream.write_be(&compression.raw_value())?;
         // Put all of the line lengths up front.
-        file_stream.write_bytes(&red.line_lengths)?;
-        file_stream.write_bytes(&green.line_lengths)?;
-        file_stream.write_bytes(&blue.line_lengths)?;
-        file_stream.write_bytes(&alpha.line_lengths)?;
+        for component in components.iter() {
+            file_stream.write_bytes(&component.line_lengths)?;
+        }
         // Then write all of the actual image data.
-        file_stream.write_bytes(&red.data)?;
-        file_stream.write_bytes(&green.data)?;
-        file_stream.write_bytes(&blue.data)?;
-        file_stream.write_bytes(&alpha.data)?;
+        for component in components.iter() {
+            file_stream.write_bytes(&component.data)?;
+        }
     } else {
-        file_stream.write_bytes(&red_channel.data)?;
-        file_stream.write_bytes(&green_channel.data)?;
-        file_stream.write_bytes(&blue_channel.data)?;
-        file_stream.write_bytes(&alpha_channel.data)?;
+        file_stream.write_be(&compression.raw_value())?;
+        for channel in channels.iter() {
+            file_stream.write_bytes(&channel.data)?;
+        }
     }
 
     let data = file_stream.data().to_vec();
     Ok(data)
 }
 
+// MARK: Decoding
+
+/// Reads the compression type followed by the data for a number of
+/// channels, laid out as in the image data section.
+pub(crate) fn decoded_channels(
+    reader: &mut Reader,
+    size: Size<u32>,
+    number_of_channels: usize,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    let width = size.width as usize;
+    let height = size.height as usize;
+
+    let raw_compression = reader.read_i16()?;
+    let compression = ImageCompression::from_value(raw_compression)
+        .ok_or(ReadError::UnsupportedCompression(raw_compression))?;
+
+    let mut channels = Vec::with_capacity(number_of_channels);
+    match compression {
+        ImageCompression::RawData => {
+            for _ in 0..number_of_channels {
+                channels.push(reader.read_bytes(width * height)?.to_vec());
+            }
+        }
+        ImageCompression::Rle => {
+            // All of the line lengths come first.
+            let mut line_lengths = Vec::with_capacity(number_of_channels * height);
+            for _ in 0..number_of_channels * height {
+                line_lengths.push(reader.read_u16()? as usize);
+            }
+            let mut line_lengths = line_lengths.into_iter();
+            for _ in 0..number_of_channels {
+                let mut channel = Vec::with_capacity(width * height);
+                for line_length in line_lengths.by_ref().take(height) {
+                    let row = reader.read_bytes(line_length)?;
+                    channel.append(&mut rle::decoded(row, width)?);
+                }
+                channels.push(channel);
+            }
+        }
+        ImageCompression::ZipWithoutPrediction | ImageCompression::ZipWithPrediction => {
+            let data = zip::decoded(
+                reader.read_bytes(reader.remaining())?,
+                width,
+                number_of_channels * width * height,
+                compression.uses_prediction(),
+            )?;
+            let channel_length = width * height;
+            for index in 0..number_of_channels {
+                channels.push(data[index * channel_length..(index + 1) * channel_length].to_vec());
+            }
+        }
+    }
+
+    Ok(channels)
+}
+
+/// Creates an image from separate colour channels. The image
+/// is opaque if there is no alpha channel.
+pub(crate) fn image_from_channels(
+    size: Size<u32>,
+    red: &[u8],
+    green: &[u8],
+    blue: &[u8],
+    alpha: Option<&[u8]>,
+) -> Image {
+    let mut image = Image::empty(size);
+
+    for y_position in 0..size.height {
+        for x_position in 0..size.width {
+            let source_index = (y_position * size.width + x_position) as usize;
+            let target_index = ((y_position * image.bytes_per_row) + (x_position * 4)) as usize;
+            image.data[target_index] = red[source_index];
+            image.data[target_index + 1] = green[source_index];
+            image.data[target_index + 2] = blue[source_index];
+            image.data[target_index + 3] = alpha.map_or(u8::MAX, |alpha| alpha[source_index]);
+        }
+    }
+
+    image
+}
+
+/// Returns an image from the `image` crate as RGBA, converting
+/// from whichever pixel format it uses.
+#[cfg(feature = "image-crate")]
+pub(crate) fn image_from_dynamic_image(dynamic_image: &::image::DynamicImage) -> Image {
+    let rgba_image = dynamic_image.to_rgba8();
+    let size = Size {
+        width: rgba_image.width(),
+        height: rgba_image.height(),
+    };
+    Image {
+        size,
+        bytes_per_row: size.width * 4,
+        data: rgba_image.into_raw(),
+    }
+}
+
+/// Returns the part of the image within a rectangle, as RGBA. The
+/// rectangle has to be within the image.
+pub(crate) fn cropped_image(image: &Image, rect: Rect<i32>) -> Image {
+    let mut output = Image::empty(rect.size.into());
+    let bytes_per_pixel = bytes_per_pixel(image);
+    for y_position in 0..output.size.height {
+        for x_position in 0..output.size.width {
+            let source_x = (rect.min_x() as u32 + x_position) as usize;
+            let source_y = rect.min_y() as u32 + y_position;
+            let source_index =
+                (source_y * image.bytes_per_row) as usize + source_x * bytes_per_pixel;
+            let index = (y_position * output.bytes_per_row + x_position * 4) as usize;
+            output.data[index..index + 4].copy_from_slice(&rgba_components(
+                image,
+                source_index,
+                bytes_per_pixel,
+            ));
+        }
+    }
+    output
+}
+
+/// Returns the image scaled down to a size using a box filter, where
+/// each pixel is the average of the pixels it covers. Colours are
+/// weighted by their alpha so that clear pixels don’t darken edges.
+pub(crate) fn scaled_image(image: &Image, size: Size<u32>) -> Image {
+    let mut output = Image::empty(size);
+    if image.size.width == 0 || image.size.height == 0 {
+        return output;
+    }
+
+    let bytes_per_pixel = bytes_per_pixel(image);
+    // The range of source positions that a target position covers.
+    let source_range = |position: u32, source_length: u32, target_length: u32| {
+        let start = (position as u64 * source_length as u64 / target_length as u64) as u32;
+        let end = ((position as u64 + 1) * source_length as u64).div_ceil(target_length as u64);
+        start..(end as u32).max(start + 1).min(source_length)
+    };
+
+    for y_position in 0..size.height {
+        let source_rows = source_range(y_position, image.size.height, size.height);
+        for x_position in 0..size.width {
+            let source_columns = source_range(x_position, image.size.width, size.width);
+
+            let mut totals = [0u64; 4];
+            let mut count = 0u64;
+            for source_y in source_rows.clone() {
+                for source_x in source_columns.clone() {
+                    let index = (source_y * image.bytes_per_row) as usize
+                        + source_x as usize * bytes_per_pixel;
+                    let [red, green, blue, alpha] = rgba_components(image, index, bytes_per_pixel);
+                    let alpha = alpha as u64;
+                    totals[0] += red as u64 * alpha;
+                    totals[1] += green as u64 * alpha;
+                    totals[2] += blue as u64 * alpha;
+                    totals[3] += alpha;
+                    count += 1;
+                }
+            }
+
+            let index = (y_position * output.bytes_per_row + x_position * 4) as usize;
+            // Fully clear pixels stay black.
+            for component in 0..3 {
+                if let Some(value) = (totals[component] + totals[3] / 2).checked_div(totals[3]) {
+                    output.data[index + component] = value as u8;
+                }
+            }
+            output.data[index + 3] = ((totals[3] + count / 2) / count) as u8;
+        }
+    }
+
+    output
+}
+
 #[cfg(test)]
 mod tests {
     use graphics::{Color, Image, Size};
 
+    use crate::color_channel::{ColorChannel, ColorChannelType};
+    use crate::error::WriteError;
     use crate::image_compression::ImageCompression;
 
     #[test]
@@ -79,7 +409,7 @@ mod tests {
                 height: 2,
             },
         );
-        let data = super::psd_data(&image, &ImageCompression::RawData).unwrap();
+        let data = super::psd_data(&image, 4, &ImageCompression::RawData).unwrap();
 
         // Compression type
         assert_eq!(data[0..=1], [0x00, 0x00]);
@@ -107,7 +437,7 @@ mod tests {
                 height: 2,
             },
         );
-        let data = super::psd_data(&image, &ImageCompression::RawData).unwrap();
+        let data = super::psd_data(&image, 4, &ImageCompression::RawData).unwrap();
 
         // Compression type
         assert_eq!(data[0..=1], [0x00, 0x00]);
@@ -125,6 +455,40 @@ mod tests {
         assert_eq!(data[14..=17], [0x99, 0x99, 0x99, 0x99]);
     }
 
+    #[test]
+    fn grayscale_raw_data() {
+        let color = Color::from_rgb_u32(0x24a4ee);
+        let image = Image::color(
+            &color,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        );
+        let data = super::grayscale_psd_data(&image, &ImageCompression::RawData).unwrap();
+
+        assert_eq!(data.len(), 6);
+
+        // Compression type
+        assert_eq!(data[0..=1], [0x00, 0x00]);
+
+        // Grey
+        assert_eq!(data[2..=5], [0x86, 0x86, 0x86, 0x86]);
+    }
+
+    #[test]
+    fn rle_row_overflow_data() {
+        let width = 66_000;
+        let mut channel = ColorChannel::new(ColorChannelType::Red, width);
+        channel.data = (0..width).map(|index| (index % 251) as u8).collect();
+
+        let data = super::channels_psd_data(&[channel.clone()], 1, &ImageCompression::Rle).unwrap();
+
+        // Compression type (raw, as the row is too long for RLE).
+        assert_eq!(data[0..=1], [0x00, 0x00]);
+        assert_eq!(data[2..], channel.data);
+    }
+
     #[test]
     fn rle_data() {
         let color = Color::from_rgb_u32(0x24a4ee); // A mid blue
@@ -135,7 +499,7 @@ mod tests {
                 height: 2,
             },
         );
-        let data = super::psd_data(&image, &ImageCompression::Rle).unwrap();
+        let data = super::psd_data(&image, 4, &ImageCompression::Rle).unwrap();
 
         // Compression type
         assert_eq!(data[0..=1], [0x00, 0x01]);
@@ -167,6 +531,82 @@ mod tests {
         assert_eq!(data[39..=41], [0x01, 0xff, 0xff]);
     }
 
+    #[test]
+    fn short_data() {
+        let mut image = Image::color(
+            &Color::RED,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        );
+        // Three and a half pixels.
+        image.data.truncate(14);
+
+        let error = super::psd_data(&image, 4, &ImageCompression::Rle).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<WriteError>(),
+            Some(WriteError::InvalidImageData(1, 1))
+        ));
+    }
+
+    #[test]
+    fn rle_data_without_alpha() {
+        let color = Color::from_rgb_u32(0x24a4ee);
+        let image = Image::color(
+            &color,
+            Size {
+                width: 2,
+                height: 2,
+            },
+        );
+        let data = super::psd_data(&image, 3, &ImageCompression::Rle).unwrap();
+
+        // Compression type, 6 line lengths (2 rows x 3 channels) and
+        // 6 rows of 3 bytes.
+        assert_eq!(data.len(), 2 + 12 + 18);
+
+        // All of the line lengths.
+        assert_eq!(data[2..14], [0x00, 0x03].repeat(6));
+
+        // Blue, straight after the red and green.
+        assert_eq!(data[26..=28], [0x01, 0xee, 0xee]);
+        assert_eq!(data[29..=31], [0x01, 0xee, 0xee]);
+    }
+
+    #[test]
+    fn decoded_channels() {
+        let color = Color::from_rgba_u32(0x24a4ee99);
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        let image = Image::color(&color, size);
+
+        for compression in [
+            ImageCompression::RawData,
+            ImageCompression::Rle,
+            ImageCompression::ZipWithoutPrediction,
+            ImageCompression::ZipWithPrediction,
+        ] {
+            let data = super::psd_data(&image, 4, &compression).unwrap();
+            let mut reader = crate::reader::Reader::new(&data);
+            let channels = super::decoded_channels(&mut reader, size, 4).unwrap();
+
+            assert_eq!(channels[0], vec![0x24; 4]);
+            assert_eq!(channels[3], vec![0x99; 4]);
+
+            let result = super::image_from_channels(
+                size,
+                &channels[0],
+                &channels[1],
+                &channels[2],
+                Some(&channels[3]),
+            );
+            assert_eq!(result, image);
+        }
+    }
+
     #[test]
     fn rle_data_with_alpha() {
         let color = Color::from_rgba_u32(0x24a4ee99); // A mid blue
@@ -177,7 +617,7 @@ mod tests {
                 height: 2,
             },
         );
-        let data = super::psd_data(&image, &ImageCompression::Rle).unwrap();
+        let data = super::psd_data(&image, 4, &ImageCompression::Rle).unwrap();
 
         // Compression type
         assert_eq!(data[0..=1], [0x00, 0x01]);
@@ -208,4 +648,25 @@ mod tests {
         assert_eq!(data[36..=38], [0x01, 0x99, 0x99]);
         assert_eq!(data[39..=41], [0x01, 0x99, 0x99]);
     }
+
+    #[test]
+    fn scaled_image() {
+        // A red pixel next to a clear one.
+        let mut image = Image::empty(Size {
+            width: 2,
+            height: 1,
+        });
+        image.data[0..4].copy_from_slice(&[0xff, 0x00, 0x00, 0xff]);
+
+        let result = super::scaled_image(
+            &image,
+            Size {
+                width: 1,
+                height: 1,
+            },
+        );
+
+        // The clear pixel only lowers the alpha.
+        assert_eq!(result.data, vec![0xff, 0x00, 0x00, 0x80]);
+    }
 }
@@ -23,6 +23,12 @@ impl ImageCompression {
         }
     }
 
+    /// Returns whether the compression is ZIP with prediction, where each
+    /// byte is stored as the difference from the one before it.
+    pub(crate) fn uses_prediction(&self) -> bool {
+        *self == ImageCompression::ZipWithPrediction
+    }
+
     /// Returns the raw value for the image compression.
     pub fn raw_value(&self) -> i16 {
         match self {
@@ -33,3 +39,26 @@ impl ImageCompression {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ImageCompression;
+
+    #[test]
+    fn raw_value_round_trip() {
+        let compressions = [
+            ImageCompression::RawData,
+            ImageCompression::Rle,
+            ImageCompression::ZipWithoutPrediction,
+            ImageCompression::ZipWithPrediction,
+        ];
+
+        for compression in compressions {
+            assert_eq!(
+                ImageCompression::from_value(compression.raw_value()),
+                Some(compression)
+            );
+        }
+        assert_eq!(ImageCompression::from_value(4), None);
+    }
+}
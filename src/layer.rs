@@ -1,19 +1,53 @@
+use std::io::Write;
 use std::mem;
 
 use file_stream::write::FileStreamWriter;
-use graphics::{Image, Rect};
+use graphics::{Image, Point, Rect, Size};
 
 use crate::blend_mode::BlendMode;
-use crate::color_channel::{ColorChannel, ColorChannelType};
+use crate::channel_source::{ChannelSource, SourcedChannels};
+use crate::color_channel::{ColorChannel, ColorChannelType, CompressedDataResult};
+use crate::composite;
 use crate::data;
 use crate::document;
+use crate::error::{self, LayerError, WriteError};
+use crate::file_format::FileFormat;
+use crate::image;
+use crate::image_compression::ImageCompression;
+use crate::layer_container::LayerContainer;
 use crate::string;
 
 use self::divider_type::DividerType;
 use self::group::GroupInfo;
 
+mod artboard;
+mod artboard_background;
+mod color_label;
 mod divider_type;
+mod drop_shadow;
 mod group;
+mod group_builder;
+mod knockout;
+mod layer_effects;
+mod layer_mask;
+pub(crate) mod parse;
+mod text_info;
+
+pub use artboard::Artboard;
+pub use artboard_background::ArtboardBackground;
+pub use color_label::ColorLabel;
+pub use drop_shadow::DropShadow;
+pub use group_builder::GroupBuilder;
+pub use knockout::Knockout;
+pub use layer_effects::LayerEffects;
+pub use layer_mask::LayerMask;
+pub use text_info::{FontSource, TextInfo};
+
+/// The reserved name for the background layer.
+const BACKGROUND_LAYER_NAME: &str = "Background";
+
+/// The name Photoshop gives to the marker at the end of a group.
+const GROUP_MARKER_NAME: &str = "</Layer group>";
 
 /// A layer in a Photoshop document.
 #[derive(Debug, Clone, PartialEq)]
@@ -32,16 +66,71 @@ pub struct Layer {
     pub opacity: u8,
     /// Whether or not the layer is hidden.
     pub is_hidden: bool,
+    /// Whether the layer’s pixel data doesn’t affect how it looks, as
+    /// for adjustment layers. Off by default.
+    pub pixel_data_is_irrelevant: bool,
+    /// Whether or not this is the locked background layer. Background
+    /// layers are always called “Background” and have no transparency.
+    pub is_background: bool,
     /// The layer’s name.
     pub name: Option<String>,
-    /// The layer image.
+    /// Whether the unicode name is padded with a null to four bytes,
+    /// matching what Photoshop writes. Off by default for compatibility.
+    pub pads_unicode_name: bool,
+    /// Whether the section divider setting of a group includes the
+    /// group’s blend mode, which some versions of Photoshop require.
+    /// Off by default.
+    pub writes_divider_blend_mode: bool,
+    /// The layer image. Images without any pixels are treated as no
+    /// image, and written as a clear image the size of the bounds.
     pub image: Option<Image>,
+    /// Whether interior effects such as inner glows are blended as a
+    /// group. Off by default.
+    pub blends_interior_effects_as_group: bool,
+    /// Whether clipped layers are blended as a group. On by default.
+    pub blends_clipped_layers_as_group: bool,
+    /// Whether the layer’s transparency shapes the layer and its
+    /// effects. On by default.
+    pub transparency_shapes_layer: bool,
+    /// Whether the layer mask hides the layer effects. Off by default.
+    pub layer_mask_hides_effects: bool,
+    /// The knockout setting for the layer.
+    pub knockout: Knockout,
+    /// The fill opacity, which unlike the opacity leaves the layer
+    /// effects as they are. From `0` to `255`.
+    pub fill_opacity: u8,
+    /// The channels that the layer doesn’t blend into, as set by the
+    /// advanced blending options. Each channel is listed once.
+    pub restricted_channels: Vec<ColorChannelType>,
+    /// The user supplied layer mask.
+    pub mask: Option<LayerMask>,
+    /// The layer’s unique identifier, which Photoshop uses to keep
+    /// track of layers between edits. Group markers have none.
+    pub id: Option<u32>,
+    /// The source of the layer’s name, such as `bgnd` for a background,
+    /// which keeps Photoshop’s automatic naming consistent.
+    pub name_source: Option<[u8; 4]>,
+    /// The metadata settings for the layer, such as those used by
+    /// animation tools. Stored as the opaque data of the `cust` block,
+    /// which is padded to an even length.
+    pub metadata: Option<Vec<u8>>,
+    /// The colour label shown in the layers panel. A group’s label is
+    /// written to both of its records.
+    pub color_label: ColorLabel,
+    /// The layer effects, such as a drop shadow.
+    pub effects: Option<LayerEffects>,
+    /// The artboard settings, which turn a group into an artboard.
+    /// Ignored for other types of layers.
+    pub artboard: Option<Artboard>,
     /// The data for the additional layer information.
     additional_layer_information: Option<Vec<u8>>,
     /// The type of divider this layer represents. Used for
     /// groups and group markers, and set to `other` for
     /// other types of layers.
     divider_type: DividerType,
+    /// Whether the channels are written in the order they’re in, rather
+    /// than the order Photoshop stores them. Set by `remap_channels`.
+    keeps_channel_order: bool,
 }
 
 /// The type of the layer.
@@ -51,6 +140,9 @@ pub enum LayerType {
     Image,
     /// A group layer.
     Group(GroupInfo),
+    /// A layer whose channel data is pulled from a source when it’s
+    /// written, rather than from an image.
+    Sourced(SourcedChannels),
 }
 
 // MARK: Creation
@@ -66,10 +158,29 @@ impl Layer {
             blend_mode: BlendMode::Normal,
             opacity: u8::MAX,
             is_hidden: false,
+            pixel_data_is_irrelevant: false,
+            is_background: false,
             name: None,
+            pads_unicode_name: false,
+            writes_divider_blend_mode: false,
             image: None,
+            blends_interior_effects_as_group: false,
+            blends_clipped_layers_as_group: true,
+            transparency_shapes_layer: true,
+            layer_mask_hides_effects: false,
+            knockout: Knockout::None,
+            fill_opacity: u8::MAX,
+            restricted_channels: Vec::new(),
+            mask: None,
+            id: None,
+            name_source: None,
+            metadata: None,
+            color_label: ColorLabel::None,
+            effects: None,
+            artboard: None,
             additional_layer_information: None,
             divider_type: DividerType::Other,
+            keeps_channel_order: false,
         }
     }
 
@@ -88,29 +199,219 @@ impl Layer {
             blend_mode: BlendMode::Normal,
             opacity: u8::MAX,
             is_hidden: false,
+            pixel_data_is_irrelevant: false,
+            is_background: false,
             name: None,
+            pads_unicode_name: false,
+            writes_divider_blend_mode: false,
             image: None,
+            blends_interior_effects_as_group: false,
+            blends_clipped_layers_as_group: true,
+            transparency_shapes_layer: true,
+            layer_mask_hides_effects: false,
+            knockout: Knockout::None,
+            fill_opacity: u8::MAX,
+            restricted_channels: Vec::new(),
+            mask: None,
+            id: None,
+            name_source: None,
+            metadata: None,
+            color_label: ColorLabel::None,
+            effects: None,
+            artboard: None,
             additional_layer_information: None,
             divider_type,
+            keeps_channel_order: false,
         }
     }
+
+    /// Creates a new group layer shown as an artboard.
+    pub fn artboard(child_layers: Vec<Layer>, artboard: Artboard) -> Self {
+        let mut layer = Layer::group(child_layers, true);
+        layer.artboard = Some(artboard);
+        layer
+    }
+
+    /// Creates a new Photoshop document layer that pulls its channel data
+    /// from a source when it’s written.
+    pub fn from_channel_source(bounds: Rect<i32>, source: impl ChannelSource + 'static) -> Self {
+        let mut layer = Layer::new(bounds);
+        let sourced_channels = SourcedChannels::new(source);
+        layer.number_of_channels = sourced_channels.source.channel_types().len() as i16;
+        layer.layer_type = LayerType::Sourced(sourced_channels);
+        layer
+    }
+
+    /// Creates a new Photoshop document layer showing an image from the
+    /// `image` crate, placed at an origin. Any pixel format is converted
+    /// to 8-bit RGBA.
+    #[cfg(feature = "image-crate")]
+    pub fn from_dynamic_image(
+        dynamic_image: &::image::DynamicImage,
+        origin: graphics::Point<i32>,
+    ) -> Self {
+        let image = image::image_from_dynamic_image(dynamic_image);
+        let bounds = Rect {
+            origin,
+            size: image.size.into(),
+        };
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(image);
+        layer
+    }
+
+    /// Creates the section divider record that marks the end of this group.
+    /// Records are stored from the bottom up, so this comes before the
+    /// group’s child layers. The marker takes the group’s blend mode, opacity
+    /// and visibility, but keeps the name Photoshop reserves for it.
+    fn group_marker(&self) -> Self {
+        let mut marker = Layer::new(Rect::zero());
+        marker.name = Some(GROUP_MARKER_NAME.to_string());
+        marker.blend_mode = self.blend_mode.clone();
+        marker.opacity = self.opacity;
+        marker.is_hidden = self.is_hidden;
+        marker.writes_divider_blend_mode = self.writes_divider_blend_mode;
+        marker.color_label = self.color_label;
+        marker.divider_type = DividerType::SectionDivider;
+        marker
+    }
 }
 
 // MARK: Updates
 
 impl Layer {
+    /// Runs a function on the layer with the document’s bounds if the
+    /// layer has none, as Procreate can’t handle zero width and height.
+    /// The layer’s own bounds are restored afterwards.
+    pub(crate) fn with_document_bounds<T>(
+        &mut self,
+        document_bounds: Rect<i32>,
+        function: impl FnOnce(&mut Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        if self.bounds != Rect::zero() {
+            return function(self);
+        }
+        self.bounds = document_bounds;
+        let result = function(self);
+        self.bounds = Rect::zero();
+        result
+    }
+
+    /// Sets the channels for the layer. These are written as they are,
+    /// rather than being derived from the layer image, though they’re
+    /// written in the order Photoshop stores them, with the alpha channel first.
+    pub fn set_channels(&mut self, channels: Vec<ColorChannel>) {
+        self.number_of_channels = channels.len() as i16;
+        self.channels = channels;
+        self.keeps_channel_order = false;
+    }
+
+    /// Puts the channels in the order given, leaving out any channels
+    /// whose type isn’t listed. The channels are then written in this
+    /// order rather than the order Photoshop stores them, for readers
+    /// with different conventions. The channel data is created from the
+    /// image first if needed.
+    pub fn remap_channels(&mut self, order: &[ColorChannelType]) -> error::Result<()> {
+        if self.channels.is_empty() {
+            self.update_channel_data()?;
+        }
+
+        let mut channels = Vec::new();
+        for color_type in order {
+            let Some(index) = self
+                .channels
+                .iter()
+                .position(|channel| channel.color_type == *color_type)
+            else {
+                return Err(LayerError::MissingChannel(color_type.clone()).into());
+            };
+            channels.push(self.channels.remove(index));
+        }
+
+        self.number_of_channels = channels.len() as i16;
+        self.channels = channels;
+        self.keeps_channel_order = true;
+        Ok(())
+    }
+
+    /// Adds a block of additional layer information, written as it is
+    /// after any blocks the crate writes itself. The data is padded to an
+    /// even length, which the length of the block includes.
+    pub fn add_raw_additional_info(&mut self, key: [u8; 4], data: Vec<u8>) -> error::Result<()> {
+        let mut data = data;
+        data::pad(&mut data, 2);
+        let mut file_stream = FileStreamWriter::new();
+        write_additional_layer_information(&mut file_stream, &key, &data)?;
+        self.additional_layer_information
+            .get_or_insert_with(Vec::new)
+            .extend_from_slice(file_stream.data());
+        Ok(())
+    }
+
+    /// Runs a function on the layer with a block of additional layer
+    /// information added, if there is one. The layer’s own additional
+    /// information is restored afterwards.
+    pub(crate) fn with_raw_additional_info<T>(
+        &mut self,
+        key: [u8; 4],
+        data: Option<Vec<u8>>,
+        function: impl FnOnce(&mut Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let Some(data) = data else {
+            return function(self);
+        };
+        let additional_layer_information = self.additional_layer_information.clone();
+        self.add_raw_additional_info(key, data)?;
+        let result = function(self);
+        self.additional_layer_information = additional_layer_information;
+        result
+    }
+
+    /// Fills the layer’s image and channels with text drawn by a font
+    /// source, for readers that show the pixels rather than laying out
+    /// the text. The bounds keep their origin and take the image’s size.
+    pub fn rasterize_text(
+        &mut self,
+        text_info: &TextInfo,
+        font_source: &impl FontSource,
+    ) -> error::Result<()> {
+        let image = text_info.rasterize(font_source)?;
+        self.bounds.size = image.size.into();
+        self.image = Some(image);
+        Ok(self.update_channel_data()?)
+    }
+
     /// Updates the channel data for the image.
-    fn update_channel_data(&mut self) {
-        // Procreate can’t handle empty images, so we create a clear
-        // image of the size of document.
-        if self.image.is_none() && self.bounds != Rect::zero() {
-            self.image = Some(Image::empty(self.bounds.size.into()));
+    fn update_channel_data(&mut self) -> anyhow::Result<()> {
+        // Images without any pixels have no content, so they’re
+        // replaced in the same way as a missing image.
+        if self
+            .image
+            .as_ref()
+            .is_some_and(|image| image.size.width == 0 || image.size.height == 0)
+        {
+            self.image = None;
         }
 
-        let Some(image) = self.image.as_ref() else {
-            panic!("No image for layer.")
+        // Procreate can’t handle empty images, so layers without one
+        // use a clear image of their bounds. Layers without bounds have
+        // empty channels.
+        let empty_image;
+        let image = match self.image.as_ref() {
+            Some(image) => image,
+            None => {
+                empty_image = Image::empty(self.bounds.size.into());
+                &empty_image
+            }
         };
 
+        // The rows are split using the bounds, so the image has to match them.
+        let bounds_size: Size<u32> = self.bounds.size.into();
+        if image.size != bounds_size {
+            anyhow::bail!(WriteError::BoundsMismatch);
+        }
+        image::validate_data(image)?;
+
         // Unlike some formats, this is never padded.
         let channel_data_length = (image.size.width * image.size.height) as usize;
         let mut red_channel = ColorChannel::new(ColorChannelType::Red, channel_data_length);
@@ -121,47 +422,377 @@ impl Layer {
         let width = image.size.width;
         let height = image.size.height;
 
+        // Grey images are expanded to red, green and blue.
+        let bytes_per_pixel = image::bytes_per_pixel(image);
         for y_position in 0..height {
             for x_position in 0..width {
-                // For now we’re just assuming that the bytes
-                // are in RGBA order.
                 let target_index = (y_position * width + x_position) as usize;
-                let source_index = ((y_position * image.bytes_per_row) + (x_position * 4)) as usize;
-                red_channel.data[target_index] = image.data[source_index];
-                green_channel.data[target_index] = image.data[source_index + 1];
-                blue_channel.data[target_index] = image.data[source_index + 2];
-                alpha_channel.data[target_index] = image.data[source_index + 3];
+                let source_index = (y_position * image.bytes_per_row) as usize
+                    + x_position as usize * bytes_per_pixel;
+                let [red, green, blue, alpha] =
+                    image::rgba_components(image, source_index, bytes_per_pixel);
+                red_channel.data[target_index] = red;
+                green_channel.data[target_index] = green;
+                blue_channel.data[target_index] = blue;
+                alpha_channel.data[target_index] = alpha;
+            }
+        }
+
+        // The background layer has no transparency.
+        let mut channels = vec![red_channel, green_channel, blue_channel];
+        if !self.is_background {
+            channels.push(alpha_channel);
+        }
+
+        if let Some(mask) = &self.mask {
+            let mask_size = mask.size();
+            if mask.data.len() != (mask_size.width * mask_size.height) as usize {
+                anyhow::bail!(WriteError::BoundsMismatch);
             }
+            let mut mask_channel =
+                ColorChannel::new(ColorChannelType::UserSuppliedLayerMask, mask.data.len());
+            mask_channel.data = mask.data.clone();
+            channels.push(mask_channel);
         }
 
-        // Convention seems to be to put the alpha channel first.
-        self.channels = vec![alpha_channel, red_channel, green_channel, blue_channel];
+        self.number_of_channels = channels.len() as i16;
+        self.channels = channels;
+        self.keeps_channel_order = false;
+        self.sort_channels();
+        Ok(())
+    }
+}
+
+impl Layer {
+    /// Updates the channel data for documents that have a single grey
+    /// channel in place of red, green and blue.
+    pub(crate) fn update_grayscale_channel_data(&mut self) -> anyhow::Result<()> {
+        self.update_channel_data()?;
+
+        let channel_data_length = self.channels[0].data.len();
+        // The grey channel uses the same identifier as the red channel.
+        let mut gray_channel = ColorChannel::new(ColorChannelType::Red, channel_data_length);
+        let color_channel = |color_type: ColorChannelType| {
+            self.channels
+                .iter()
+                .find(|channel| channel.color_type == color_type)
+                .map(|channel| &channel.data)
+        };
+        if let (Some(red), Some(green), Some(blue)) = (
+            color_channel(ColorChannelType::Red),
+            color_channel(ColorChannelType::Green),
+            color_channel(ColorChannelType::Blue),
+        ) {
+            for index in 0..channel_data_length {
+                gray_channel.data[index] = image::luminance(red[index], green[index], blue[index]);
+            }
+        }
+
+        self.channels.retain(|channel| {
+            channel.color_type == ColorChannelType::Alpha
+                || channel.color_type == ColorChannelType::UserSuppliedLayerMask
+        });
+        self.channels.push(gray_channel);
+        self.channels
+            .sort_by(|first, second| first.color_type.cmp(&second.color_type));
+        self.number_of_channels = self.channels.len() as i16;
+        Ok(())
+    }
+
+    /// Creates the channel data for the layer and its child layers, if
+    /// it hasn’t been already, returning each channel with its height.
+    /// Layers without bounds take the document’s bounds, as they do when
+    /// written. Sourced layers are skipped.
+    pub(crate) fn channels_with_heights(
+        &mut self,
+        is_grayscale: bool,
+        document_bounds: Rect<i32>,
+    ) -> anyhow::Result<Vec<(u32, &mut ColorChannel)>> {
+        let height = self.with_document_bounds(document_bounds, |layer| {
+            if layer.layer_type == LayerType::Image && layer.channels.is_empty() {
+                if is_grayscale {
+                    layer.update_grayscale_channel_data()?;
+                } else {
+                    layer.update_channel_data()?;
+                }
+            }
+            Ok(layer.bounds.height() as u32)
+        })?;
+
+        let mask_height = self.mask_height();
+        match &mut self.layer_type {
+            LayerType::Image => Ok(self
+                .channels
+                .iter_mut()
+                .map(|channel| {
+                    let height = channel_height(&channel.color_type, height, mask_height);
+                    (height, channel)
+                })
+                .collect()),
+            LayerType::Group(info) => {
+                let mut channels = Vec::new();
+                for layer in info.layers.iter_mut() {
+                    channels
+                        .append(&mut layer.channels_with_heights(is_grayscale, document_bounds)?);
+                }
+                Ok(channels)
+            }
+            LayerType::Sourced(_) => Ok(Vec::new()),
+        }
+    }
+}
+
+// MARK: Flattening
+
+impl Layer {
+    /// Flattens a group into a single image layer by compositing its
+    /// visible child layers. The new layer covers all of the child layers
+    /// and keeps the group’s name, blend mode, opacity and visibility.
+    pub fn flatten_group(&self) -> error::Result<Layer> {
+        let LayerType::Group(info) = &self.layer_type else {
+            return Err(LayerError::NotAGroup.into());
+        };
+        Ok(self.flattened_group(info))
+    }
+
+    /// Returns the group flattened into a single image layer.
+    fn flattened_group(&self, info: &GroupInfo) -> Layer {
+        // The union of the child layers’ bounds.
+        let bounds = info
+            .all_layers()
+            .iter()
+            .filter(|layer| layer.layer_type == LayerType::Image && layer.bounds != Rect::zero())
+            .map(|layer| layer.bounds)
+            .reduce(|union, bounds| {
+                let min_x = union.min_x().min(bounds.min_x());
+                let min_y = union.min_y().min(bounds.min_y());
+                let max_x = union.max_x().max(bounds.max_x());
+                let max_y = union.max_y().max(bounds.max_y());
+                Rect::new(min_x, min_y, max_x - min_x, max_y - min_y)
+            })
+            .unwrap_or(Rect::zero());
+
+        let mut layer = Layer::new(bounds);
+        layer.name = self.name.clone();
+        layer.blend_mode = self.blend_mode.clone();
+        layer.opacity = self.opacity;
+        layer.is_hidden = self.is_hidden;
+        if bounds != Rect::zero() {
+            layer.image = Some(composite::composited_image(
+                &info.layers,
+                bounds,
+                document::BlendColorSpace::Srgb,
+            ));
+        }
+        layer
+    }
+}
+
+// MARK: Exporting
+
+impl Layer {
+    /// Returns a document sized to the layer’s bounds, holding a copy of
+    /// the layer moved to the document’s origin, with the layer’s image
+    /// as the preview. Groups are flattened first, so that the document
+    /// covers all of their child layers.
+    pub fn into_document(&self) -> document::Document {
+        let mut layer = match &self.layer_type {
+            LayerType::Group(info) => self.flattened_group(info),
+            _ => self.clone(),
+        };
+
+        // The mask keeps its place relative to the layer.
+        let origin = layer.bounds.origin;
+        if let Some(mask) = &mut layer.mask {
+            mask.bounds.origin = Point {
+                x: mask.bounds.min_x() - origin.x,
+                y: mask.bounds.min_y() - origin.y,
+            };
+        }
+        layer.bounds.origin = Point::zero();
+
+        let size = Size {
+            width: layer.bounds.width().max(0) as u32,
+            height: layer.bounds.height().max(0) as u32,
+        };
+        let mut document = document::Document::rgb(size);
+        document.layers = vec![layer];
+        document.set_preview_from(|_| true);
+        document
+    }
+}
+
+// MARK: Properties
+
+impl Layer {
+    /// Returns whether the layer has nothing to show, either because it
+    /// has no image or because its alpha is zero everywhere. Groups and
+    /// sourced layers are never considered transparent.
+    pub fn is_transparent(&self) -> bool {
+        if let LayerType::Group(_) | LayerType::Sourced(_) = self.layer_type {
+            return false;
+        }
+
+        if !self.channels.is_empty() {
+            return self
+                .channels
+                .iter()
+                .find(|channel| channel.color_type == ColorChannelType::Alpha)
+                .is_some_and(|channel| channel.data.iter().all(|&value| value == 0));
+        }
+
+        let Some(image) = &self.image else {
+            return true;
+        };
+        let bytes_per_pixel = image::bytes_per_pixel(image);
+        (0..image.size.height).all(|y_position| {
+            (0..image.size.width).all(|x_position| {
+                let index = (y_position * image.bytes_per_row) as usize
+                    + x_position as usize * bytes_per_pixel;
+                let [_, _, _, alpha] = image::rgba_components(image, index, bytes_per_pixel);
+                alpha == 0
+            })
+        })
+    }
+
+    /// Returns the name written to the file, which is always
+    /// “Background” for the background layer.
+    fn record_name(&self) -> Option<String> {
+        if self.is_background {
+            return Some(BACKGROUND_LAYER_NAME.to_string());
+        }
+        self.name.clone()
     }
 }
 
 // MARK: Encoding
 
 impl Layer {
+    /// Returns the layers to write as records for this layer, from the
+    /// bottom up. A group is written as a section divider, followed by
+    /// its child layers and finally the group’s own folder record.
+    #[cfg(test)]
+    pub(crate) fn record_layers(&self) -> Vec<Layer> {
+        let LayerType::Group(info) = &self.layer_type else {
+            return vec![self.clone()];
+        };
+
+        let mut layers = vec![self.group_marker()];
+        for layer in info.layers.iter() {
+            layers.append(&mut layer.record_layers());
+        }
+        layers.push(self.clone());
+        layers
+    }
+
+    /// Calls a function with each layer to write as a record for this
+    /// layer, in the same order as `record_layers`. Other than the group
+    /// markers, these are the layers themselves rather than copies, so
+    /// that the channels they compress are kept. The child layers are
+    /// visited in reverse when `is_reversed` is set.
+    pub(crate) fn for_each_record_layer(
+        &mut self,
+        is_reversed: bool,
+        function: &mut impl FnMut(&mut Layer) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        if !matches!(self.layer_type, LayerType::Group(_)) {
+            return function(self);
+        }
+
+        function(&mut self.group_marker())?;
+        if let LayerType::Group(info) = &mut self.layer_type {
+            let layers: Box<dyn Iterator<Item = &mut Layer>> = if is_reversed {
+                Box::new(info.layers.iter_mut().rev())
+            } else {
+                Box::new(info.layers.iter_mut())
+            };
+            for layer in layers {
+                layer.for_each_record_layer(is_reversed, function)?;
+            }
+        }
+        function(self)
+    }
+
     /// Returns the image encoded per channel.
-    pub fn encoded_image(&mut self) -> anyhow::Result<Vec<u8>> {
-        let mut file_stream = FileStreamWriter::new();
+    pub fn encoded_image(&mut self) -> error::Result<Vec<u8>> {
+        Ok(self.encoded_image_for_format(FileFormat::Psd, &ImageCompression::Rle)?)
+    }
+
+    /// Writes the image encoded per channel, in the same layout as
+    /// `encoded_image`. Each channel is compressed and written before
+    /// the next, so that only one compressed channel is held in memory
+    /// at a time. Compressed data that was already cached is kept.
+    pub fn write_encoded_image<W: Write>(&mut self, writer: &mut W) -> error::Result<()> {
+        Ok(self.write_encoded_image_for_format(writer, FileFormat::Psd, &ImageCompression::Rle)?)
+    }
+
+    /// Returns the image encoded per channel, laid out for a file format.
+    /// Raw data is written uncompressed, and anything else uses RLE.
+    pub(crate) fn encoded_image_for_format(
+        &mut self,
+        format: FileFormat,
+        compression: &ImageCompression,
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.write_encoded_image_for_format(&mut data, format, compression)?;
+        Ok(data)
+    }
+
+    /// Writes the image encoded per channel, laid out for a file format,
+    /// one channel at a time.
+    fn write_encoded_image_for_format<W: Write>(
+        &mut self,
+        writer: &mut W,
+        format: FileFormat,
+        compression: &ImageCompression,
+    ) -> anyhow::Result<()> {
+        let height = self.bounds.size.height as u32;
+        if let LayerType::Sourced(sourced_channels) = &self.layer_type {
+            let channel_types = sourced_channels.source.channel_types();
+            for index in 0..channel_types.len() {
+                let compressed = sourced_channels.compressed_data(
+                    index,
+                    self.bounds.size.into(),
+                    compression,
+                )?;
+                writer.write_all(&compressed.compression.raw_value().to_be_bytes())?;
+                writer.write_all(&Self::formatted_channel_data(&compressed, height, format))?;
+            }
+            return Ok(());
+        }
+
         if self.channels.is_empty() {
-            self.update_channel_data();
+            self.update_channel_data()?;
         }
-        let height = self.bounds.size.height as u32;
+        self.sort_channels();
+        let mask_height = self.mask_height();
         for channel in self.channels.iter_mut() {
-            let Ok(compressed) = channel.compressed_data(height) else {
-                continue;
-            };
-            file_stream.write_be(&compressed.compression.raw_value())?;
-            file_stream.write_bytes(&compressed.data)?;
+            let is_cached = channel.compressed_data.is_some();
+            let height = channel_height(&channel.color_type, height, mask_height);
+            let compressed = channel.data_with_compression(height, compression)?;
+            writer.write_all(&compressed.compression.raw_value().to_be_bytes())?;
+            writer.write_all(&Self::formatted_channel_data(&compressed, height, format))?;
+            // Compressed data cached while writing is dropped straight away.
+            if !is_cached {
+                channel.compressed_data = None;
+            }
         }
 
-        Ok(file_stream.data().to_vec())
+        Ok(())
     }
 
     /// Returns the data for the layer record.
-    pub fn layer_record_data(&mut self) -> anyhow::Result<Vec<u8>> {
+    pub fn layer_record_data(&mut self) -> error::Result<Vec<u8>> {
+        Ok(self.layer_record_data_for_format(FileFormat::Psd, &ImageCompression::Rle)?)
+    }
+
+    /// Returns the data for the layer record, laid out for a file format.
+    /// The channel lengths match `encoded_image_for_format`.
+    pub(crate) fn layer_record_data_for_format(
+        &mut self,
+        format: FileFormat,
+        compression: &ImageCompression,
+    ) -> anyhow::Result<Vec<u8>> {
         let mut file_stream = FileStreamWriter::new();
 
         // The rectangle / bounds.
@@ -174,67 +805,274 @@ impl Layer {
         file_stream.write_be(&bottom)?;
         file_stream.write_be(&right)?;
 
-        // The number of channels.
-        file_stream.write_be(&self.number_of_channels)?;
-
-        if self.channels.is_empty() {
-            self.update_channel_data();
-        }
-
-        // The channel information.
-        for channel in self.channels.iter_mut() {
-            file_stream.write_be(&channel.color_type.raw_value())?;
-
-            // The size is the size of the data plus the compression type byte.
-            let Ok(result) = channel.compressed_data(self.bounds.height() as u32) else {
-                continue;
-            };
-            file_stream.write_be(&(result.data.len() as u32 + mem::size_of::<i16>() as u32))?;
+        let height = self.bounds.height() as u32;
+        if let LayerType::Sourced(sourced_channels) = &self.layer_type {
+            let channel_types = sourced_channels.source.channel_types();
+            self.number_of_channels = channel_types.len() as i16;
+            file_stream.write_be(&self.number_of_channels)?;
+
+            // The channels are compressed again when the image is written,
+            // so that they don’t need to be kept in memory.
+            for (index, channel_type) in channel_types.iter().enumerate() {
+                file_stream.write_be(&channel_type.raw_value())?;
+                let result = sourced_channels.compressed_data(
+                    index,
+                    self.bounds.size.into(),
+                    compression,
+                )?;
+                let data = Self::formatted_channel_data(&result, height, format);
+                format.write_length(&mut file_stream, data.len() + mem::size_of::<i16>())?;
+            }
+        } else {
+            if self.channels.is_empty() {
+                self.update_channel_data()?;
+            }
+            // The image data follows the same order.
+            self.sort_channels();
+
+            // The number of channels, which always matches the channels written.
+            self.number_of_channels = self.channels.len() as i16;
+            file_stream.write_be(&self.number_of_channels)?;
+
+            // The channel information.
+            let mask_height = self.mask_height();
+            for channel in self.channels.iter_mut() {
+                file_stream.write_be(&channel.color_type.raw_value())?;
+
+                // The size is the size of the data plus the compression type byte.
+                let height = channel_height(&channel.color_type, height, mask_height);
+                // Channels that can’t be encoded aren’t skipped, as the
+                // count has already been written.
+                let result = channel.data_with_compression(height, compression)?;
+                let data = Self::formatted_channel_data(&result, height, format);
+                format.write_length(&mut file_stream, data.len() + mem::size_of::<i16>())?;
+            }
         }
 
         file_stream.write_bytes(&document::constants::RESOURCE_SIGNATURE)?;
-        file_stream.write_bytes(&self.blend_mode.as_str().as_bytes())?;
+        file_stream.write_bytes(&self.blend_mode.key())?;
 
         file_stream.write_be(&self.opacity)?;
 
         // Clipping… still don’t know what it means.
         file_stream.write_be(&0u8)?;
 
-        // The flags… we only care about the visible flag (opposite to the documentation).
-        let flags: u8 = if self.is_hidden {
-            0b00000010
-        } else {
-            0b00000000
-        };
+        // The flags. The visible flag is set when the layer is hidden (opposite
+        // to the documentation), and the pixel data flag is only read when
+        // the flag before it is set.
+        let mut flags: u8 = 0;
+        if self.is_hidden {
+            flags |= 0b00000010;
+        }
+        if self.pixel_data_is_irrelevant {
+            flags |= 0b00011000;
+        }
         file_stream.write_be(&flags)?;
 
         // Filler.
         file_stream.write_be(&0u8)?;
 
         let mut extra_data_file_stream = FileStreamWriter::new();
-        // Layer mask data — there are no layer masks, so we just put
-        // a zero for the size of this section.
-        extra_data_file_stream.write_be(&0u32)?;
+        // Layer mask data, which is empty without a mask.
+        let mask_data = match &self.mask {
+            Some(mask) => mask.data()?,
+            None => Vec::new(),
+        };
+        extra_data_file_stream.write_be(&data::section_length(mask_data.len())?)?;
+        extra_data_file_stream.write_bytes(&mask_data)?;
 
         // Layer blending ranges — can this be zero too?
         extra_data_file_stream.write_be(&0u32)?;
 
-        let mut name_data = string::pascal::data_from_string(self.name.as_ref())?;
+        let name = self.record_name();
+        let mut name_data = string::pascal::data_from_string(name.as_ref())?;
         data::pad(&mut name_data, 4);
         extra_data_file_stream.write_bytes(&name_data)?;
 
-        let unicode_name_data = string::unicode::data_from_string(self.name.as_ref())?;
+        let unicode_name_data =
+            string::unicode::data_from_string(name.as_ref(), self.pads_unicode_name)?;
         extra_data_file_stream.write_bytes(&unicode_name_data)?;
 
+        // The section divider setting, for groups and group markers,
+        // optionally followed by the signature and the blend mode.
+        if self.divider_type != DividerType::Other {
+            let mut divider_data = self.divider_type.raw_value().to_be_bytes().to_vec();
+            if self.writes_divider_blend_mode {
+                divider_data.extend_from_slice(&document::constants::RESOURCE_SIGNATURE);
+                divider_data.extend_from_slice(&self.blend_mode.key());
+            }
+            write_additional_layer_information(
+                &mut extra_data_file_stream,
+                b"lsct",
+                &divider_data,
+            )?;
+        }
+
+        // The blending options, which are only written when they
+        // differ from the defaults.
+        let blending_flags = [
+            (b"infx", self.blends_interior_effects_as_group, false),
+            (b"clbl", self.blends_clipped_layers_as_group, true),
+            (b"tsly", self.transparency_shapes_layer, true),
+            (b"lmgm", self.layer_mask_hides_effects, false),
+        ];
+        for (key, value, default_value) in blending_flags {
+            if value != default_value {
+                // A boolean followed by three bytes of padding.
+                let flag_data = [value as u8, 0, 0, 0];
+                write_additional_layer_information(&mut extra_data_file_stream, key, &flag_data)?;
+            }
+        }
+
+        // The fill opacity, followed by three bytes of padding.
+        if self.fill_opacity != u8::MAX {
+            let fill_opacity_data = [self.fill_opacity, 0, 0, 0];
+            write_additional_layer_information(
+                &mut extra_data_file_stream,
+                b"iOpa",
+                &fill_opacity_data,
+            )?;
+        }
+
+        // The channel blending restrictions, as a list of channel ids.
+        if !self.restricted_channels.is_empty() {
+            let restrictions_data: Vec<u8> = self
+                .restricted_channels
+                .iter()
+                .flat_map(|channel_type| (channel_type.raw_value() as i32).to_be_bytes())
+                .collect();
+            write_additional_layer_information(
+                &mut extra_data_file_stream,
+                b"brst",
+                &restrictions_data,
+            )?;
+        }
+
+        // The knockout setting, followed by three bytes of padding.
+        if self.knockout != Knockout::None {
+            let knockout_data = [self.knockout.raw_value(), 0, 0, 0];
+            write_additional_layer_information(
+                &mut extra_data_file_stream,
+                b"knko",
+                &knockout_data,
+            )?;
+        }
+
+        // The layer name source setting.
+        if let Some(name_source) = &self.name_source {
+            write_additional_layer_information(&mut extra_data_file_stream, b"lnsr", name_source)?;
+        }
+
+        // The layer identifier.
+        if let Some(id) = self.id {
+            write_additional_layer_information(
+                &mut extra_data_file_stream,
+                b"lyid",
+                &id.to_be_bytes(),
+            )?;
+        }
+
+        // The metadata settings, written as they are, padded to an even length.
+        if let Some(metadata) = &self.metadata {
+            let mut metadata_data = metadata.clone();
+            data::pad(&mut metadata_data, 2);
+            write_additional_layer_information(
+                &mut extra_data_file_stream,
+                b"cust",
+                &metadata_data,
+            )?;
+        }
+
+        // The colour label, followed by six bytes of padding.
+        if self.color_label != ColorLabel::None {
+            let mut color_label_data = [0; 8];
+            color_label_data[0..2].copy_from_slice(&self.color_label.raw_value().to_be_bytes());
+            write_additional_layer_information(
+                &mut extra_data_file_stream,
+                b"lclr",
+                &color_label_data,
+            )?;
+        }
+
+        // The artboard, as a descriptor. The group marker has none.
+        if let (LayerType::Group(_), Some(artboard)) = (&self.layer_type, &self.artboard) {
+            write_additional_layer_information(
+                &mut extra_data_file_stream,
+                b"artb",
+                &artboard.data()?,
+            )?;
+        }
+
+        // The layer effects, as a descriptor.
+        if let Some(effects) = self.effects.as_ref().filter(|effects| !effects.is_empty()) {
+            write_additional_layer_information(
+                &mut extra_data_file_stream,
+                b"lfx2",
+                &effects.data()?,
+            )?;
+        }
+
         if let Some(layer_information) = &self.additional_layer_information {
             extra_data_file_stream.write_bytes(layer_information)?;
         }
 
-        file_stream.write_be(&(extra_data_file_stream.data().len() as u32))?;
+        file_stream.write_be(&data::section_length(extra_data_file_stream.data().len())?)?;
         file_stream.write_bytes(&extra_data_file_stream.data())?;
 
         Ok(file_stream.data().to_vec())
     }
+
+    /// Puts the channels in the order Photoshop stores them. Convention
+    /// is to put the alpha channel first, which is the order channel
+    /// types sort in. The sort is stable, so channels of the same type
+    /// keep their order. Remapped channels are left as they are.
+    fn sort_channels(&mut self) {
+        if self.keeps_channel_order {
+            return;
+        }
+        self.channels
+            .sort_by(|first, second| first.color_type.cmp(&second.color_type));
+    }
+
+    /// Returns the height of the layer mask, if there is one.
+    fn mask_height(&self) -> Option<u32> {
+        self.mask.as_ref().map(|mask| mask.size().height)
+    }
+
+    /// Returns the compressed data for a channel, laid out for a file format.
+    fn formatted_channel_data(
+        compressed: &CompressedDataResult,
+        height: u32,
+        format: FileFormat,
+    ) -> Vec<u8> {
+        if compressed.compression == ImageCompression::Rle {
+            format.rle_data(&compressed.data, height as usize)
+        } else {
+            compressed.data.clone()
+        }
+    }
+}
+
+/// Returns the number of rows in a channel. The mask channel covers
+/// the mask bounds, and every other channel covers the layer bounds.
+fn channel_height(color_type: &ColorChannelType, height: u32, mask_height: Option<u32>) -> u32 {
+    match (color_type, mask_height) {
+        (ColorChannelType::UserSuppliedLayerMask, Some(mask_height)) => mask_height,
+        _ => height,
+    }
+}
+
+/// Writes a block of additional layer information with its key.
+fn write_additional_layer_information(
+    file_stream: &mut FileStreamWriter,
+    key: &[u8; 4],
+    data: &[u8],
+) -> anyhow::Result<()> {
+    file_stream.write_bytes(&document::constants::RESOURCE_SIGNATURE)?;
+    file_stream.write_bytes(key)?;
+    file_stream.write_be(&data::section_length(data.len())?)?;
+    file_stream.write_bytes(data)?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -243,6 +1081,9 @@ mod tests {
 
     use graphics::{Color, Point};
 
+    use crate::error::Error;
+    use crate::reader::Reader;
+
     use super::*;
 
     #[test]
@@ -261,7 +1102,7 @@ mod tests {
 
         assert_eq!(layer.channels.len(), 0);
 
-        layer.update_channel_data();
+        layer.update_channel_data().unwrap();
 
         assert_eq!(layer.channels.len(), 4);
 
@@ -282,6 +1123,973 @@ mod tests {
         assert_eq!(alpha_channel.data, vec![0x91, 0x91, 0x91, 0x91]);
     }
 
+    #[test]
+    fn layer_without_bounds() {
+        let mut layer = Layer::new(Rect::zero());
+        layer.layer_record_data().unwrap();
+
+        // Each channel is empty, with only its compression method.
+        assert_eq!(layer.channels.len(), 4);
+        assert!(layer.channels.iter().all(|channel| channel.data.is_empty()));
+        assert_eq!(layer.encoded_image().unwrap(), vec![0x00; 8]);
+
+        // Documents need at least one pixel for their merged image.
+        let image = Image::empty(Size::zero());
+        let error = crate::Document::from_image(image).file_data().unwrap_err();
+        assert!(matches!(error, Error::Write(WriteError::InvalidImage)));
+    }
+
+    #[test]
+    fn update_grayscale_image_channel_data() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut gray_image = Image::empty(bounds.size.into());
+        gray_image.bytes_per_row = 2;
+        gray_image.data = vec![0x10, 0x20, 0x30, 0x40];
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(gray_image);
+
+        layer.update_channel_data().unwrap();
+
+        // The grey is copied to each colour channel, and is opaque.
+        assert_eq!(layer.channels[0].data, vec![0xff; 4]);
+        for channel in layer.channels[1..].iter() {
+            assert_eq!(channel.data, vec![0x10, 0x20, 0x30, 0x40]);
+        }
+
+        // Grey with alpha.
+        let mut gray_alpha_image = Image::empty(bounds.size.into());
+        gray_alpha_image.bytes_per_row = 4;
+        gray_alpha_image.data = vec![0x10, 0x80, 0x20, 0x81, 0x30, 0x82, 0x40, 0x83];
+        layer.image = Some(gray_alpha_image);
+
+        layer.update_channel_data().unwrap();
+
+        assert_eq!(layer.channels[0].data, vec![0x80, 0x81, 0x82, 0x83]);
+        for channel in layer.channels[1..].iter() {
+            assert_eq!(channel.data, vec![0x10, 0x20, 0x30, 0x40]);
+        }
+    }
+
+    #[test]
+    fn background_layer_record_data() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.name = Some("Paper".to_string());
+        layer.is_background = true;
+        layer.image = Some(Image::color(&Color::WHITE, bounds.size.into()));
+
+        let result = layer.layer_record_data().unwrap();
+
+        // Number of channels
+        assert_eq!(result[16..=17], [0x00, 0x03]);
+
+        // Channel type (red, green and blue, no alpha).
+        assert_eq!(result[18..=19], [0x00, 0x00]);
+        assert_eq!(result[24..=25], [0x00, 0x01]);
+        assert_eq!(result[30..=31], [0x00, 0x02]);
+
+        // Resource signature (8BIM), straight after the three channels.
+        assert_eq!(result[36..=39], [0x38, 0x42, 0x49, 0x4d]);
+
+        // Pascal name.
+        assert_eq!(result[60..=70], *b"\x0aBackground");
+
+        assert!(layer
+            .channels
+            .iter()
+            .all(|channel| channel.color_type != ColorChannelType::Alpha));
+    }
+
+    #[test]
+    fn group_record_layers() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.name = Some("Child".to_string());
+        let mut group = Layer::group(vec![layer], false);
+        group.name = Some("Group".to_string());
+
+        let result = group.record_layers();
+
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].divider_type, DividerType::SectionDivider);
+        assert_eq!(result[0].name, Some(GROUP_MARKER_NAME.to_string()));
+        assert_eq!(result[1].name, Some("Child".to_string()));
+        assert_eq!(result[2].divider_type, DividerType::ClosedFolder);
+
+        let mut marker = result[0].clone();
+        marker.bounds = bounds;
+        let record_data = marker.layer_record_data().unwrap();
+        // 8BIM lsct, with a length of 4 and the section divider type.
+        let section_data = [
+            0x38, 0x42, 0x49, 0x4d, 0x6c, 0x73, 0x63, 0x74, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+            0x00, 0x03,
+        ];
+        assert_eq!(record_data[record_data.len() - 16..], section_data);
+    }
+
+    #[test]
+    fn group_record_layers_with_divider_blend_mode() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut group = Layer::group(vec![Layer::new(bounds)], true);
+        group.blend_mode = BlendMode::PassThrough;
+        group.writes_divider_blend_mode = true;
+
+        let result = group.record_layers();
+
+        for (mut layer, divider_type) in [(result[0].clone(), 0x03), (result[2].clone(), 0x01)] {
+            layer.bounds = bounds;
+            let record_data = layer.layer_record_data().unwrap();
+            let position = record_data
+                .windows(8)
+                .position(|window| window == b"8BIMlsct")
+                .unwrap();
+            // 8BIM lsct, with a length of 12, the section divider type,
+            // then 8BIM and the pass through blend mode.
+            let section_data = [
+                0x38,
+                0x42,
+                0x49,
+                0x4d,
+                0x6c,
+                0x73,
+                0x63,
+                0x74,
+                0x00,
+                0x00,
+                0x00,
+                0x0c,
+                0x00,
+                0x00,
+                0x00,
+                divider_type,
+                0x38,
+                0x42,
+                0x49,
+                0x4d,
+                0x70,
+                0x61,
+                0x73,
+                0x73,
+            ];
+            assert_eq!(record_data[position..position + 24], section_data);
+
+            let mut reader = Reader::new(&record_data);
+            let (result, _) = Layer::from_record(&mut reader).unwrap();
+            assert!(result.writes_divider_blend_mode);
+            assert_eq!(result.blend_mode, BlendMode::PassThrough);
+        }
+    }
+
+    #[test]
+    fn set_channels() {
+        let bounds = Rect::new(0, 0, 2, 1);
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(Image::color(&Color::WHITE, bounds.size.into()));
+
+        let channel = |color_type: ColorChannelType, data: Vec<u8>| {
+            let mut channel = ColorChannel::new(color_type, data.len());
+            channel.data = data;
+            channel
+        };
+        layer.set_channels(vec![
+            channel(ColorChannelType::Red, vec![0x12, 0x34]),
+            channel(ColorChannelType::Green, vec![0x56, 0x78]),
+            channel(ColorChannelType::Blue, vec![0x9a, 0xbc]),
+        ]);
+
+        let record_data = layer.layer_record_data().unwrap();
+        let encoded_image = layer.encoded_image().unwrap();
+
+        // Number of channels.
+        assert_eq!(record_data[16..=17], [0x00, 0x03]);
+
+        // The channels are written raw, as they are, ignoring the image.
+        assert_eq!(
+            encoded_image,
+            [0x00, 0x00, 0x12, 0x34, 0x00, 0x00, 0x56, 0x78, 0x00, 0x00, 0x9a, 0xbc]
+        );
+    }
+
+    #[test]
+    fn mismatched_number_of_channels() {
+        let bounds = Rect::new(0, 0, 2, 1);
+        let mut layer = Layer::new(bounds);
+        layer.channels = [
+            ColorChannelType::Red,
+            ColorChannelType::Green,
+            ColorChannelType::Blue,
+        ]
+        .into_iter()
+        .map(|color_type| {
+            let mut channel = ColorChannel::new(color_type, 2);
+            channel.data = vec![0x12, 0x34];
+            channel
+        })
+        .collect();
+        assert_eq!(layer.number_of_channels, 4);
+
+        let record_data = layer.layer_record_data().unwrap();
+        let encoded_image = layer.encoded_image().unwrap();
+
+        // The count matches the channels, each with a length of 4 bytes.
+        assert_eq!(record_data[16..=17], [0x00, 0x03]);
+        for index in 0..3 {
+            let offset = 18 + index * 6;
+            assert_eq!(
+                record_data[offset + 2..offset + 6],
+                [0x00, 0x00, 0x00, 0x04]
+            );
+        }
+        assert_eq!(encoded_image.len(), 12);
+        assert_eq!(layer.number_of_channels, 3);
+    }
+
+    #[test]
+    fn set_channels_out_of_order() {
+        let bounds = Rect::new(0, 0, 2, 1);
+        let mut layer = Layer::new(bounds);
+
+        let channel = |color_type: ColorChannelType, data: Vec<u8>| {
+            let mut channel = ColorChannel::new(color_type, data.len());
+            channel.data = data;
+            channel
+        };
+        layer.set_channels(vec![
+            channel(ColorChannelType::Blue, vec![0x9a, 0xbc]),
+            channel(ColorChannelType::Alpha, vec![0xff, 0xee]),
+            channel(ColorChannelType::Red, vec![0x12, 0x34]),
+            channel(ColorChannelType::Green, vec![0x56, 0x78]),
+        ]);
+
+        let record_data = layer.layer_record_data().unwrap();
+        let encoded_image = layer.encoded_image().unwrap();
+
+        // Number of channels.
+        assert_eq!(record_data[16..=17], [0x00, 0x04]);
+
+        // The channel identifiers, each followed by a length of 4 bytes.
+        let identifiers: Vec<[u8; 2]> = (0..4)
+            .map(|index| {
+                let offset = 18 + index * 6;
+                [record_data[offset], record_data[offset + 1]]
+            })
+            .collect();
+        assert_eq!(
+            identifiers,
+            [[0xff, 0xff], [0x00, 0x00], [0x00, 0x01], [0x00, 0x02]]
+        );
+
+        // The image data follows the same order.
+        assert_eq!(
+            encoded_image,
+            [
+                0x00, 0x00, 0xff, 0xee, 0x00, 0x00, 0x12, 0x34, 0x00, 0x00, 0x56, 0x78, 0x00, 0x00,
+                0x9a, 0xbc
+            ]
+        );
+    }
+
+    #[test]
+    fn remap_channels() {
+        let bounds = Rect::new(0, 0, 1, 1);
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(Image::color(
+            &Color {
+                red: 0x12,
+                green: 0x34,
+                blue: 0x56,
+                alpha: 0x78,
+            },
+            bounds.size.into(),
+        ));
+
+        layer
+            .remap_channels(&[
+                ColorChannelType::Red,
+                ColorChannelType::Green,
+                ColorChannelType::Blue,
+                ColorChannelType::Alpha,
+            ])
+            .unwrap();
+        let encoded_image = layer.encoded_image().unwrap();
+
+        // Each channel is a single raw byte, in the order given.
+        assert_eq!(
+            encoded_image,
+            [0x00, 0x00, 0x12, 0x00, 0x00, 0x34, 0x00, 0x00, 0x56, 0x00, 0x00, 0x78]
+        );
+
+        let error = layer
+            .remap_channels(&[ColorChannelType::UserSuppliedLayerMask])
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Layer(LayerError::MissingChannel(
+                ColorChannelType::UserSuppliedLayerMask
+            ))
+        ));
+    }
+
+    #[test]
+    fn rasterize_text() {
+        struct StubFontSource;
+
+        impl FontSource for StubFontSource {
+            fn rasterize(&self, text_info: &TextInfo) -> anyhow::Result<Image> {
+                let size = Size {
+                    width: text_info.text.len() as u32,
+                    height: 1,
+                };
+                Ok(Image::color(&text_info.color, size))
+            }
+        }
+
+        let mut layer = Layer::new(Rect::new(3, 4, 0, 0));
+        let mut text_info = TextInfo::new("abc", "Helvetica", 12.0);
+        text_info.color = Color::RED;
+        layer.rasterize_text(&text_info, &StubFontSource).unwrap();
+
+        assert_eq!(layer.bounds, Rect::new(3, 4, 3, 1));
+        assert_eq!(layer.channels.len(), 4);
+        assert_eq!(layer.channels[0].color_type, ColorChannelType::Alpha);
+        assert_eq!(layer.channels[0].data, vec![0xff; 3]);
+        assert_eq!(layer.channels[1].color_type, ColorChannelType::Red);
+        assert_eq!(layer.channels[1].data, vec![0xff; 3]);
+        assert_eq!(layer.channels[2].data, vec![0x00; 3]);
+        assert_eq!(layer.channels[3].data, vec![0x00; 3]);
+    }
+
+    #[test]
+    fn mismatched_bounds() {
+        let mut layer = Layer::new(Rect::new(0, 0, 2, 2));
+        layer.image = Some(Image::color(
+            &Color::WHITE,
+            Size {
+                width: 3,
+                height: 1,
+            },
+        ));
+
+        let error = layer.layer_record_data().unwrap_err();
+        assert!(matches!(error, Error::Write(WriteError::BoundsMismatch)));
+        assert!(layer.channels.is_empty());
+    }
+
+    #[test]
+    fn group_blend_mode_and_opacity() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut group = Layer::group(vec![Layer::new(bounds)], true);
+        group.name = Some("Group".to_string());
+        group.blend_mode = BlendMode::Multiply;
+        group.opacity = 0x80;
+
+        let result = group.record_layers();
+
+        // Both the section divider and the folder record.
+        for record in [&result[0], &result[2]] {
+            let mut record = record.clone();
+            record.bounds = bounds;
+            let record_data = record.layer_record_data().unwrap();
+
+            // Blend mode (mul ).
+            assert_eq!(record_data[46..=49], [0x6d, 0x75, 0x6c, 0x20]);
+            // Opacity.
+            assert_eq!(record_data[50], 0x80);
+        }
+    }
+
+    #[test]
+    fn hidden_group() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let visible_layer = Layer::new(bounds);
+        let mut hidden_layer = Layer::new(bounds);
+        hidden_layer.is_hidden = true;
+        let mut group = Layer::group(vec![visible_layer, hidden_layer], false);
+        group.is_hidden = true;
+
+        let flags: Vec<u8> = group
+            .record_layers()
+            .into_iter()
+            .map(|mut record| {
+                record.bounds = bounds;
+                // Flags.
+                record.layer_record_data().unwrap()[52]
+            })
+            .collect();
+
+        // The section divider, the two child layers and the folder record.
+        assert_eq!(flags, vec![0b00000010, 0b00000000, 0b00000010, 0b00000010]);
+    }
+
+    #[test]
+    fn is_transparent() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        assert!(layer.is_transparent());
+
+        layer.image = Some(Image::color(&Color::CLEAR, bounds.size.into()));
+        assert!(layer.is_transparent());
+
+        layer.image = Some(Image::color(&Color::RED, bounds.size.into()));
+        assert!(!layer.is_transparent());
+
+        // Grey without alpha is opaque.
+        let mut gray_image = Image::empty(bounds.size.into());
+        gray_image.bytes_per_row = 2;
+        gray_image.data = vec![0x00; 4];
+        layer.image = Some(gray_image);
+        assert!(!layer.is_transparent());
+
+        // Grey with alpha.
+        let mut gray_alpha_image = Image::empty(bounds.size.into());
+        gray_alpha_image.bytes_per_row = 4;
+        gray_alpha_image.data = vec![0x10, 0x00, 0x20, 0x00, 0x30, 0x00, 0x40, 0x00];
+        layer.image = Some(gray_alpha_image.clone());
+        assert!(layer.is_transparent());
+
+        gray_alpha_image.data[7] = 0x01;
+        layer.image = Some(gray_alpha_image);
+        assert!(!layer.is_transparent());
+
+        layer.image = Some(Image::color(&Color::RED, bounds.size.into()));
+        layer.is_background = true;
+        layer.layer_record_data().unwrap();
+        assert!(!layer.is_transparent());
+    }
+
+    #[test]
+    fn transparency_shapes_layer() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        let default_data = layer.clone().layer_record_data().unwrap();
+
+        layer.transparency_shapes_layer = false;
+        let record_data = layer.layer_record_data().unwrap();
+
+        assert_eq!(record_data.len(), default_data.len() + 16);
+        // 8BIM tsly, with a length of 4 and a false boolean.
+        let flag_data = [
+            0x38, 0x42, 0x49, 0x4d, 0x74, 0x73, 0x6c, 0x79, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        assert_eq!(record_data[record_data.len() - 16..], flag_data);
+    }
+
+    #[test]
+    fn deep_knockout() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.knockout = Knockout::Deep;
+
+        let record_data = layer.layer_record_data().unwrap();
+
+        // 8BIM knko, with a length of 4 and the deep setting.
+        let knockout_data = [
+            0x38, 0x42, 0x49, 0x4d, 0x6b, 0x6e, 0x6b, 0x6f, 0x00, 0x00, 0x00, 0x04, 0x02, 0x00,
+            0x00, 0x00,
+        ];
+        assert_eq!(record_data[record_data.len() - 16..], knockout_data);
+    }
+
+    #[test]
+    fn into_document() {
+        let bounds = Rect::new(3, 5, 4, 2);
+        let mut layer = Layer::new(bounds);
+        layer.name = Some("Asset".to_string());
+        layer.image = Some(Image::color(&Color::RED, bounds.size.into()));
+
+        let mut document = layer.into_document();
+
+        assert_eq!(
+            document.size,
+            Size {
+                width: 4,
+                height: 2
+            }
+        );
+        assert_eq!(document.layers.len(), 1);
+        assert_eq!(document.layers[0].bounds, Rect::new(0, 0, 4, 2));
+        assert_eq!(document.layers[0].name, layer.name);
+        assert_eq!(document.preview_image, layer.image);
+        assert!(document.file_data().is_ok());
+    }
+
+    #[test]
+    fn restricted_channels() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.restricted_channels = vec![ColorChannelType::Red];
+
+        let record_data = layer.layer_record_data().unwrap();
+
+        // 8BIM brst, with a length of 4 and the red channel id.
+        let restrictions_data = [
+            0x38, 0x42, 0x49, 0x4d, 0x62, 0x72, 0x73, 0x74, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        assert_eq!(record_data[record_data.len() - 16..], restrictions_data);
+    }
+
+    #[test]
+    fn fill_opacity() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.fill_opacity = 128;
+
+        let record_data = layer.layer_record_data().unwrap();
+
+        // 8BIM iOpa, with a length of 4 and the fill opacity.
+        let fill_opacity_data = [
+            0x38, 0x42, 0x49, 0x4d, 0x69, 0x4f, 0x70, 0x61, 0x00, 0x00, 0x00, 0x04, 0x80, 0x00,
+            0x00, 0x00,
+        ];
+        assert_eq!(record_data[record_data.len() - 16..], fill_opacity_data);
+    }
+
+    #[test]
+    fn extra_data_length() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.name = Some("Extra".to_string());
+        layer.blends_interior_effects_as_group = true;
+        layer.knockout = Knockout::Shallow;
+        // An unknown key kept from a parsed document.
+        let unknown_data = vec![
+            0x38, 0x42, 0x49, 0x4d, 0x61, 0x62, 0x63, 0x64, 0x00, 0x00, 0x00, 0x04, 0x01, 0x02,
+            0x03, 0x04,
+        ];
+        layer.additional_layer_information = Some(unknown_data.clone());
+
+        let record_data = layer.layer_record_data().unwrap();
+
+        // The bounds, the channel information, then the blend mode,
+        // opacity, clipping, flags and filler.
+        let number_of_channels = i16::from_be_bytes([record_data[16], record_data[17]]) as usize;
+        let length_offset = 18 + number_of_channels * 6 + 12;
+        let length_data = &record_data[length_offset..length_offset + 4];
+        let extra_data = &record_data[length_offset + 4..];
+        assert_eq!(
+            u32::from_be_bytes(length_data.try_into().unwrap()) as usize,
+            extra_data.len()
+        );
+
+        // The empty mask and blending ranges, the padded name, then
+        // blocks of additional layer information that run to the end.
+        assert_eq!(extra_data[0..8], [0x00; 8]);
+        assert_eq!(
+            extra_data[8..16],
+            [0x05, 0x45, 0x78, 0x74, 0x72, 0x61, 0x00, 0x00]
+        );
+        let mut keys = Vec::new();
+        let mut position = 16;
+        while position < extra_data.len() {
+            assert_eq!(extra_data[position..position + 4], *b"8BIM");
+            keys.push(extra_data[position + 4..position + 8].to_vec());
+            let length_data = &extra_data[position + 8..position + 12];
+            position += 12 + u32::from_be_bytes(length_data.try_into().unwrap()) as usize;
+        }
+        assert_eq!(position, extra_data.len());
+        assert_eq!(
+            keys,
+            vec![
+                b"luni".to_vec(),
+                b"infx".to_vec(),
+                b"knko".to_vec(),
+                b"abcd".to_vec()
+            ]
+        );
+        assert_eq!(extra_data[extra_data.len() - 16..], unknown_data);
+    }
+
+    #[test]
+    fn add_raw_additional_info() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(Image::color(&Color::RED, bounds.size.into()));
+        layer
+            .add_raw_additional_info(*b"abcd", vec![0x01, 0x02, 0x03])
+            .unwrap();
+
+        // 8BIM abcd, with the length and data padded to an even length.
+        let expected_data = vec![
+            0x38, 0x42, 0x49, 0x4d, 0x61, 0x62, 0x63, 0x64, 0x00, 0x00, 0x00, 0x04, 0x01, 0x02,
+            0x03, 0x00,
+        ];
+        assert_eq!(
+            layer.additional_layer_information,
+            Some(expected_data.clone())
+        );
+
+        let mut document = crate::Document::rgb(bounds.size.into());
+        document.layers = vec![layer];
+        let data = document.file_data().unwrap();
+        let result = crate::Document::from_data(&data).unwrap();
+        assert_eq!(
+            result.layers[0].additional_layer_information,
+            Some(expected_data)
+        );
+    }
+
+    #[test]
+    fn layer_id() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.id = Some(0x1234);
+
+        let record_data = layer.layer_record_data().unwrap();
+
+        // 8BIM lyid, with a length of 4 and the identifier.
+        let id_data = [
+            0x38, 0x42, 0x49, 0x4d, 0x6c, 0x79, 0x69, 0x64, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+            0x12, 0x34,
+        ];
+        assert_eq!(record_data[record_data.len() - 16..], id_data);
+
+        let mut reader = Reader::new(&record_data);
+        let (result, _) = Layer::from_record(&mut reader).unwrap();
+        assert_eq!(result.id, Some(0x1234));
+    }
+
+    #[test]
+    fn pixel_data_is_irrelevant() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.pixel_data_is_irrelevant = true;
+
+        // The flags follow the bounds, the channels, the blend mode,
+        // the opacity and the clipping.
+        let flags_offset = 18 + layer.number_of_channels as usize * 6 + 10;
+        let record_data = layer.layer_record_data().unwrap();
+        assert_eq!(record_data[flags_offset], 0b00011000);
+
+        layer.is_hidden = true;
+        let record_data = layer.layer_record_data().unwrap();
+        assert_eq!(record_data[flags_offset], 0b00011010);
+
+        let mut reader = Reader::new(&record_data);
+        let (result, _) = Layer::from_record(&mut reader).unwrap();
+        assert!(result.pixel_data_is_irrelevant);
+        assert!(result.is_hidden);
+    }
+
+    #[test]
+    fn non_ascii_name() {
+        let mut layer = Layer::new(Rect::new(0, 0, 2, 2));
+        layer.name = Some("Café".to_string());
+
+        let record_data = layer.layer_record_data().unwrap();
+
+        // The Pascal name in MacRoman, padded to four bytes.
+        let pascal_name_data = [0x04, 0x43, 0x61, 0x66, 0x8e, 0x00, 0x00, 0x00];
+        assert!(record_data
+            .windows(pascal_name_data.len())
+            .any(|window| window == pascal_name_data));
+        // 8BIM luni, with a length of 12 and the name in UTF-16.
+        let unicode_name_data = [
+            0x38, 0x42, 0x49, 0x4d, 0x6c, 0x75, 0x6e, 0x69, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00,
+            0x00, 0x04, 0x00, 0x43, 0x00, 0x61, 0x00, 0x66, 0x00, 0xe9,
+        ];
+        assert!(record_data
+            .windows(unicode_name_data.len())
+            .any(|window| window == unicode_name_data));
+
+        let mut reader = Reader::new(&record_data);
+        let (result, _) = Layer::from_record(&mut reader).unwrap();
+        assert_eq!(result.name, Some("Café".to_string()));
+    }
+
+    #[test]
+    fn long_name() {
+        let name = "a".repeat(300);
+        let mut layer = Layer::new(Rect::new(0, 0, 2, 2));
+        layer.name = Some(name.clone());
+
+        let record_data = layer.layer_record_data().unwrap();
+
+        // The Pascal name is truncated to 255 bytes, which needs no
+        // padding, so the additional information follows it.
+        let mut pascal_name_data = vec![0xff];
+        pascal_name_data.extend(vec![b'a'; 255]);
+        pascal_name_data.push(0x38);
+        assert!(record_data
+            .windows(pascal_name_data.len())
+            .any(|window| window == pascal_name_data));
+
+        // The Unicode name keeps the whole name.
+        let mut reader = Reader::new(&record_data);
+        let (result, _) = Layer::from_record(&mut reader).unwrap();
+        assert_eq!(result.name, Some(name));
+    }
+
+    #[test]
+    fn name_source() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.name_source = Some(*b"bgnd");
+
+        let record_data = layer.layer_record_data().unwrap();
+
+        // 8BIM lnsr, with a length of 4 and the code.
+        let name_source_data = [
+            0x38, 0x42, 0x49, 0x4d, 0x6c, 0x6e, 0x73, 0x72, 0x00, 0x00, 0x00, 0x04, 0x62, 0x67,
+            0x6e, 0x64,
+        ];
+        assert_eq!(record_data[record_data.len() - 16..], name_source_data);
+
+        let mut reader = Reader::new(&record_data);
+        let (result, _) = Layer::from_record(&mut reader).unwrap();
+        assert_eq!(result.name_source, Some(*b"bgnd"));
+    }
+
+    #[cfg(feature = "image-crate")]
+    #[test]
+    fn from_dynamic_image() {
+        let mut rgba_image = ::image::RgbaImage::new(2, 1);
+        rgba_image.put_pixel(0, 0, ::image::Rgba([0x12, 0x34, 0x56, 0x78]));
+        rgba_image.put_pixel(1, 0, ::image::Rgba([0xff, 0x00, 0x00, 0xff]));
+        let dynamic_image = ::image::DynamicImage::ImageRgba8(rgba_image);
+
+        let mut layer = Layer::from_dynamic_image(&dynamic_image, Point { x: 3, y: 4 });
+
+        assert_eq!(layer.bounds, Rect::new(3, 4, 2, 1));
+        let image = layer.image.clone().unwrap();
+        assert_eq!(image.bytes_per_row, 8);
+        assert_eq!(
+            image.data,
+            vec![0x12, 0x34, 0x56, 0x78, 0xff, 0x00, 0x00, 0xff]
+        );
+
+        // Other pixel formats are converted to RGBA.
+        let gray_image = ::image::DynamicImage::ImageLuma8(
+            ::image::GrayImage::from_raw(1, 1, vec![0x80]).unwrap(),
+        );
+        let gray_layer = Layer::from_dynamic_image(&gray_image, Point::zero());
+        assert_eq!(gray_layer.image.unwrap().data, vec![0x80, 0x80, 0x80, 0xff]);
+
+        layer.update_channel_data().unwrap();
+        assert_eq!(layer.channels[0].color_type, ColorChannelType::Alpha);
+        assert_eq!(layer.channels[0].data, vec![0x78, 0xff]);
+    }
+
+    #[test]
+    fn unknown_blend_mode() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.blend_mode = BlendMode::Unknown([0xff, 0xfe, 0x00, 0x41]);
+
+        let record_data = layer.layer_record_data().unwrap();
+
+        let mut reader = Reader::new(&record_data);
+        let (result, _) = Layer::from_record(&mut reader).unwrap();
+        assert_eq!(result.blend_mode, layer.blend_mode);
+    }
+
+    #[test]
+    fn metadata() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.metadata = Some(vec![0x00, 0x00, 0x00, 0x10, 0xab, 0xcd]);
+
+        let record_data = layer.layer_record_data().unwrap();
+
+        // 8BIM cust, with a length of 6 and the metadata.
+        let metadata_data = [
+            0x38, 0x42, 0x49, 0x4d, 0x63, 0x75, 0x73, 0x74, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00,
+            0x00, 0x10, 0xab, 0xcd,
+        ];
+        assert_eq!(record_data[record_data.len() - 18..], metadata_data);
+
+        let mut reader = Reader::new(&record_data);
+        let (result, _) = Layer::from_record(&mut reader).unwrap();
+        assert_eq!(result.metadata, layer.metadata);
+    }
+
+    #[test]
+    fn odd_length_metadata() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.metadata = Some(vec![0xab, 0xcd, 0xef]);
+        layer.color_label = ColorLabel::Red;
+
+        let record_data = layer.layer_record_data().unwrap();
+
+        // 8BIM cust, with the length and data padded to an even length,
+        // so that the colour label that follows stays aligned.
+        let metadata_data = [
+            0x38, 0x42, 0x49, 0x4d, 0x63, 0x75, 0x73, 0x74, 0x00, 0x00, 0x00, 0x04, 0xab, 0xcd,
+            0xef, 0x00, 0x38, 0x42, 0x49, 0x4d, 0x6c, 0x63, 0x6c, 0x72,
+        ];
+        assert!(record_data
+            .windows(metadata_data.len())
+            .any(|window| window == metadata_data));
+
+        let mut reader = Reader::new(&record_data);
+        let (result, _) = Layer::from_record(&mut reader).unwrap();
+        assert_eq!(result.metadata, Some(vec![0xab, 0xcd, 0xef, 0x00]));
+        assert_eq!(result.color_label, ColorLabel::Red);
+    }
+
+    #[test]
+    fn group_color_label() {
+        let mut group = Layer::group(vec![Layer::new(Rect::new(0, 0, 2, 2))], true);
+        group.color_label = ColorLabel::Violet;
+
+        let record_layers = group.record_layers();
+        let mut folder = record_layers.last().unwrap().clone();
+        assert_eq!(folder.divider_type, DividerType::OpenFolder);
+        // Documents give records without bounds the document’s bounds.
+        folder.bounds = Rect::new(0, 0, 2, 2);
+        let record_data = folder.layer_record_data().unwrap();
+
+        // 8BIM lclr, with a length of 8 and the violet label.
+        let color_label_data = [
+            0x38, 0x42, 0x49, 0x4d, 0x6c, 0x63, 0x6c, 0x72, 0x00, 0x00, 0x00, 0x08, 0x00, 0x06,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(record_data[record_data.len() - 20..], color_label_data);
+        assert_eq!(record_layers[0].color_label, ColorLabel::Violet);
+
+        let mut reader = Reader::new(&record_data);
+        let (result, _) = Layer::from_record(&mut reader).unwrap();
+        assert_eq!(result.color_label, ColorLabel::Violet);
+    }
+
+    #[test]
+    fn artboard() {
+        let artboard = Artboard::new(Rect::new(10, 20, 300, 200));
+        let group = Layer::artboard(vec![Layer::new(Rect::new(10, 20, 2, 2))], artboard);
+
+        let record_layers = group.record_layers();
+        let mut folder = record_layers.last().unwrap().clone();
+        folder.bounds = Rect::new(10, 20, 300, 200);
+        let record_data = folder.layer_record_data().unwrap();
+
+        let position = record_data
+            .windows(8)
+            .position(|window| window == b"8BIMartb")
+            .unwrap();
+        // The descriptor version, then the artboard class.
+        let artboard_data = &record_data[position + 12..];
+        assert_eq!(artboard_data[0..4], [0, 0, 0, 16]);
+        assert_eq!(&artboard_data[12..20], b"artboard");
+
+        // The bounds, as top, left, bottom and right.
+        let mut bounds_data = b"classFloatRect".to_vec();
+        bounds_data.extend_from_slice(&[0, 0, 0, 4]);
+        for (key, value) in [
+            (b"Top ", 20.0),
+            (b"Left", 10.0),
+            (b"Btom", 220.0),
+            (b"Rght", 310.0),
+        ] {
+            bounds_data.extend_from_slice(&[0, 0, 0, 0]);
+            bounds_data.extend_from_slice(key);
+            bounds_data.extend_from_slice(b"doub");
+            bounds_data.extend_from_slice(&f64::to_be_bytes(value));
+        }
+        assert!(artboard_data
+            .windows(bounds_data.len())
+            .any(|window| window == bounds_data));
+
+        // The marker that ends the group isn’t an artboard.
+        let mut marker = record_layers[0].clone();
+        marker.bounds = Rect::new(10, 20, 300, 200);
+        let marker_data = marker.layer_record_data().unwrap();
+        assert!(!marker_data.windows(8).any(|window| window == b"8BIMartb"));
+    }
+
+    #[test]
+    fn drop_shadow() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        layer.effects = Some(LayerEffects {
+            drop_shadow: Some(DropShadow::default()),
+        });
+
+        let record_data = layer.layer_record_data().unwrap();
+
+        let position = record_data
+            .windows(8)
+            .position(|window| window == b"8BIMlfx2")
+            .unwrap();
+        let length_data = &record_data[position + 8..position + 12];
+        let length = u32::from_be_bytes(length_data.try_into().unwrap()) as usize;
+        assert_eq!(position + 12 + length, record_data.len());
+
+        // The object effects version, then the descriptor version.
+        let effects_data = &record_data[position + 12..];
+        assert_eq!(effects_data[0..8], [0, 0, 0, 0, 0, 0, 0, 16]);
+        // The shadow sub-descriptor, keyed and classed as DrSh.
+        let shadow_key = [0, 0, 0, 0, 0x44, 0x72, 0x53, 0x68, 0x4f, 0x62, 0x6a, 0x63];
+        assert!(effects_data
+            .windows(shadow_key.len())
+            .any(|window| window == shadow_key));
+    }
+
+    #[test]
+    fn flatten_group() {
+        let mut layer_0 = Layer::new(Rect::new(0, 0, 2, 1));
+        let size = Size {
+            width: 2,
+            height: 1,
+        };
+        layer_0.image = Some(Image::color(&Color::RED, size));
+        let mut layer_1 = Layer::new(Rect::new(1, 0, 2, 1));
+        layer_1.image = Some(Image::color(&Color::BLUE, size));
+        layer_1.opacity = 0x80;
+        let mut group = Layer::group(vec![layer_0, layer_1], true);
+        group.name = Some("Group".to_string());
+        group.blend_mode = BlendMode::Screen;
+
+        let result = group.flatten_group().unwrap();
+
+        assert_eq!(result.layer_type, LayerType::Image);
+        assert_eq!(result.name, Some("Group".to_string()));
+        assert_eq!(result.blend_mode, BlendMode::Screen);
+        assert_eq!(result.bounds, Rect::new(0, 0, 3, 1));
+        let image = result.image.unwrap();
+        assert_eq!(
+            image.data,
+            vec![
+                0xff, 0x00, 0x00, 0xff, // Red.
+                0x7f, 0x00, 0x80, 0xff, // Half blue over red.
+                0x00, 0x00, 0xff, 0x80, // Half blue.
+            ]
+        );
+
+        let error = Layer::new(Rect::zero()).flatten_group().unwrap_err();
+        assert!(matches!(error, Error::Layer(LayerError::NotAGroup)));
+    }
+
+    #[test]
+    fn short_image_data() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut layer = Layer::new(bounds);
+        let mut image = Image::color(&Color::WHITE, bounds.size.into());
+        image.data.truncate(8);
+        layer.image = Some(image);
+
+        let error = layer.encoded_image().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Write(WriteError::InvalidImageData(0, 1))
+        ));
+    }
+
+    #[test]
+    fn update_grayscale_channel_data() {
+        let bounds = Rect::new(0, 0, 2, 1);
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(Image::color(&Color::WHITE, bounds.size.into()));
+
+        layer.update_grayscale_channel_data().unwrap();
+
+        assert_eq!(layer.number_of_channels, 2);
+        assert_eq!(layer.channels[0].color_type, ColorChannelType::Alpha);
+        assert_eq!(layer.channels[1].color_type, ColorChannelType::Red);
+        assert_eq!(layer.channels[1].data, vec![0xff, 0xff]);
+    }
+
     #[test]
     fn encoded_image_2x2() {
         let bounds = Rect::new(0, 0, 2, 2);
@@ -396,6 +2204,48 @@ mod tests {
         assert_eq!(encoded_image[38..40], [0xfe, 0xe7]);
     }
 
+    #[test]
+    fn negative_origin() {
+        let bounds = Rect::new(-5, -3, 3, 2);
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(Image::color(
+            &Color::from_rgb_u32(0x50d1e7),
+            bounds.size.into(),
+        ));
+
+        let record_data = layer.layer_record_data().unwrap();
+        let encoded_image = layer.encoded_image().unwrap();
+
+        // The top, left, bottom and right, keeping their signs.
+        let rectangle_data = [
+            0xff, 0xff, 0xff, 0xfd, // Top
+            0xff, 0xff, 0xff, 0xfb, // Left
+            0xff, 0xff, 0xff, 0xff, // Bottom
+            0xff, 0xff, 0xff, 0xfe, // Right
+        ];
+        assert_eq!(record_data[0..16], rectangle_data);
+
+        // Each channel has the compression, a line length for each of the
+        // two rows, then two rows of three repeated bytes.
+        assert_eq!(record_data[16..18], [0x00, 0x04]);
+        for index in 0..4 {
+            let offset = 18 + index * 6;
+            assert_eq!(
+                record_data[offset + 2..offset + 6],
+                [0x00, 0x00, 0x00, 0x0a]
+            );
+
+            let channel_data = &encoded_image[index * 10..(index + 1) * 10];
+            assert_eq!(channel_data[0..6], [0x00, 0x01, 0x00, 0x02, 0x00, 0x02]);
+        }
+        assert_eq!(encoded_image.len(), 40);
+
+        let mut reader = Reader::new(&record_data);
+        let (result, channel_info) = Layer::from_record(&mut reader).unwrap();
+        assert_eq!(result.bounds, bounds);
+        assert!(channel_info.iter().all(|info| info.length == 10));
+    }
+
     #[test]
     fn channel_data() {
         let mut resources_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -469,6 +2319,31 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn write_encoded_image() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("tests/resources/clouds2.png");
+        let source_image = Image::open(&path).unwrap();
+
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: source_image.size.into(),
+        };
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(source_image);
+        let expected = layer.clone().encoded_image().unwrap();
+
+        let mut result = Vec::new();
+        layer.write_encoded_image(&mut result).unwrap();
+
+        assert_eq!(result, expected);
+        // The compressed channels aren’t kept once they’re written.
+        assert!(layer
+            .channels
+            .iter()
+            .all(|channel| channel.compressed_data.is_none()));
+    }
+
     #[test]
     fn clouds_layer_record_data() {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -507,12 +2382,12 @@ mod tests {
         // Channel type (red)
         assert_eq!(result[24..=25], [0x00, 0x00]);
         // Channel data length.
-        assert_eq!(result[26..=29], [0x00, 0x00, 0x00, 0x1b]);
+        assert_eq!(result[26..=29], [0x00, 0x00, 0x00, 0x1a]);
 
         // Channel type (green)
         assert_eq!(result[30..=31], [0x00, 0x01]);
         // Channel data length.
-        assert_eq!(result[32..=35], [0x00, 0x00, 0x00, 0x1b]);
+        assert_eq!(result[32..=35], [0x00, 0x00, 0x00, 0x1a]);
 
         // Channel type (blue)
         assert_eq!(result[36..=37], [0x00, 0x02]);
@@ -0,0 +1,80 @@
+use file_stream::write::FileStreamWriter;
+use graphics::{Color, Rect};
+
+use crate::descriptor::{Descriptor, DescriptorValue};
+use crate::endian::WriteBigEndian;
+
+use super::artboard_background::ArtboardBackground;
+
+/// The version of the artboard descriptor.
+const DESCRIPTOR_VERSION: u32 = 16;
+
+/// The settings that make a group an artboard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artboard {
+    /// The bounds of the artboard in the document.
+    pub bounds: Rect<i32>,
+    /// The background of the artboard.
+    pub background: ArtboardBackground,
+    /// The colour used when the background is `Other`. The alpha
+    /// component is ignored.
+    pub background_color: Color,
+}
+
+// MARK: Creation
+
+impl Artboard {
+    /// Creates a new artboard with a white background.
+    pub fn new(bounds: Rect<i32>) -> Self {
+        Self {
+            bounds,
+            background: ArtboardBackground::White,
+            background_color: Color::WHITE,
+        }
+    }
+}
+
+// MARK: Data
+
+impl Artboard {
+    /// Returns the data for the `artb` block, which is the descriptor
+    /// version followed by the artboard descriptor.
+    pub(crate) fn data(&self) -> anyhow::Result<Vec<u8>> {
+        let mut file_stream = FileStreamWriter::new();
+        file_stream.write_u32(DESCRIPTOR_VERSION)?;
+        file_stream.write_bytes(&self.descriptor().data()?)?;
+        Ok(file_stream.data().to_vec())
+    }
+
+    /// Returns the `artboard` descriptor.
+    fn descriptor(&self) -> Descriptor {
+        let bounds = Descriptor::new("classFloatRect")
+            .with("Top ", DescriptorValue::Double(self.bounds.min_y() as f64))
+            .with("Left", DescriptorValue::Double(self.bounds.min_x() as f64))
+            .with("Btom", DescriptorValue::Double(self.bounds.max_y() as f64))
+            .with("Rght", DescriptorValue::Double(self.bounds.max_x() as f64));
+        let color = Descriptor::new("RGBC")
+            .with(
+                "Rd  ",
+                DescriptorValue::Double(self.background_color.red as f64),
+            )
+            .with(
+                "Grn ",
+                DescriptorValue::Double(self.background_color.green as f64),
+            )
+            .with(
+                "Bl  ",
+                DescriptorValue::Double(self.background_color.blue as f64),
+            );
+
+        Descriptor::new("artboard")
+            .with("artboardRect", DescriptorValue::Descriptor(bounds))
+            .with("guideIndeces", DescriptorValue::List(Vec::new()))
+            .with("artboardPresetName", DescriptorValue::Text(String::new()))
+            .with("Clr ", DescriptorValue::Descriptor(color))
+            .with(
+                "artboardBackgroundType",
+                DescriptorValue::Integer(self.background.raw_value()),
+            )
+    }
+}
@@ -0,0 +1,36 @@
+/// The background of an artboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArtboardBackground {
+    /// A white background.
+    #[default]
+    White,
+    /// A black background.
+    Black,
+    /// A transparent background.
+    Transparent,
+    /// The background colour of the artboard.
+    Other,
+}
+
+impl ArtboardBackground {
+    /// Creates a new artboard background from a raw value.
+    pub fn from_value(value: i32) -> Option<Self> {
+        match value {
+            1 => Some(ArtboardBackground::White),
+            2 => Some(ArtboardBackground::Black),
+            3 => Some(ArtboardBackground::Transparent),
+            4 => Some(ArtboardBackground::Other),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw value for the artboard background.
+    pub fn raw_value(&self) -> i32 {
+        match self {
+            ArtboardBackground::White => 1,
+            ArtboardBackground::Black => 2,
+            ArtboardBackground::Transparent => 3,
+            ArtboardBackground::Other => 4,
+        }
+    }
+}
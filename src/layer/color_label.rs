@@ -0,0 +1,52 @@
+/// The colour label shown for a layer in the layers panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorLabel {
+    /// No colour.
+    #[default]
+    None,
+    /// Red.
+    Red,
+    /// Orange.
+    Orange,
+    /// Yellow.
+    Yellow,
+    /// Green.
+    Green,
+    /// Blue.
+    Blue,
+    /// Violet.
+    Violet,
+    /// Grey.
+    Gray,
+}
+
+impl ColorLabel {
+    /// Creates a new colour label from a raw value.
+    pub fn from_value(value: u16) -> Option<Self> {
+        match value {
+            0 => Some(ColorLabel::None),
+            1 => Some(ColorLabel::Red),
+            2 => Some(ColorLabel::Orange),
+            3 => Some(ColorLabel::Yellow),
+            4 => Some(ColorLabel::Green),
+            5 => Some(ColorLabel::Blue),
+            6 => Some(ColorLabel::Violet),
+            7 => Some(ColorLabel::Gray),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw value for the colour label.
+    pub fn raw_value(&self) -> u16 {
+        match self {
+            ColorLabel::None => 0,
+            ColorLabel::Red => 1,
+            ColorLabel::Orange => 2,
+            ColorLabel::Yellow => 3,
+            ColorLabel::Green => 4,
+            ColorLabel::Blue => 5,
+            ColorLabel::Violet => 6,
+            ColorLabel::Gray => 7,
+        }
+    }
+}
@@ -11,3 +11,21 @@ pub enum DividerType {
     /// A section divider, used to mark the end of a group.
     SectionDivider = 3,
 }
+
+impl DividerType {
+    /// Creates a new divider type from a raw value.
+    pub fn from_value(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(DividerType::Other),
+            1 => Some(DividerType::OpenFolder),
+            2 => Some(DividerType::ClosedFolder),
+            3 => Some(DividerType::SectionDivider),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw value for the divider type.
+    pub fn raw_value(&self) -> u32 {
+        self.clone() as u32
+    }
+}
@@ -0,0 +1,91 @@
+use graphics::Color;
+
+use crate::blend_mode::BlendMode;
+use crate::descriptor::{Descriptor, DescriptorValue};
+
+/// A drop shadow layer effect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropShadow {
+    /// The colour of the shadow. The alpha component is ignored.
+    pub color: Color,
+    /// The opacity of the shadow (from 0 to 255).
+    pub opacity: u8,
+    /// The angle of the light casting the shadow, in degrees.
+    pub angle: i32,
+    /// The distance of the shadow from the layer, in pixels.
+    pub distance: f64,
+    /// The blur size of the shadow, in pixels.
+    pub size: f64,
+    /// The blend mode for the shadow.
+    pub blend_mode: BlendMode,
+}
+
+// MARK: Creation
+
+impl DropShadow {
+    /// Creates a new drop shadow.
+    pub fn new(color: Color, opacity: u8, angle: i32, distance: f64, size: f64) -> Self {
+        Self {
+            color,
+            opacity,
+            angle,
+            distance,
+            size,
+            blend_mode: BlendMode::Multiply,
+        }
+    }
+}
+
+impl Default for DropShadow {
+    /// Photoshop’s default drop shadow.
+    fn default() -> Self {
+        Self::new(
+            Color {
+                red: 0,
+                green: 0,
+                blue: 0,
+                alpha: u8::MAX,
+            },
+            191,
+            120,
+            5.0,
+            5.0,
+        )
+    }
+}
+
+// MARK: Data
+
+impl DropShadow {
+    /// Returns the `DrSh` descriptor for the drop shadow.
+    pub(crate) fn descriptor(&self) -> Descriptor {
+        let color = Descriptor::new("RGBC")
+            .with("Rd  ", DescriptorValue::Double(self.color.red as f64))
+            .with("Grn ", DescriptorValue::Double(self.color.green as f64))
+            .with("Bl  ", DescriptorValue::Double(self.color.blue as f64));
+        let opacity = self.opacity as f64 / u8::MAX as f64 * 100.0;
+
+        Descriptor::new("DrSh")
+            .with("enab", DescriptorValue::Boolean(true))
+            .with(
+                "Md  ",
+                DescriptorValue::Enumerated(
+                    "BlnM".to_string(),
+                    self.blend_mode.descriptor_value().to_string(),
+                ),
+            )
+            .with("Clr ", DescriptorValue::Descriptor(color))
+            .with("Opct", DescriptorValue::UnitFloat(b"#Prc", opacity))
+            .with("uglg", DescriptorValue::Boolean(false))
+            .with(
+                "lagl",
+                DescriptorValue::UnitFloat(b"#Ang", self.angle as f64),
+            )
+            .with("Dstn", DescriptorValue::UnitFloat(b"#Pxl", self.distance))
+            .with("Ckmt", DescriptorValue::UnitFloat(b"#Pxl", 0.0))
+            .with("blur", DescriptorValue::UnitFloat(b"#Pxl", self.size))
+            .with("Nose", DescriptorValue::UnitFloat(b"#Prc", 0.0))
+            .with("AntA", DescriptorValue::Boolean(false))
+            .with("layerConceals", DescriptorValue::Boolean(true))
+    }
+}
@@ -0,0 +1,101 @@
+use super::Layer;
+
+/// Builds a group layer along with its child layers, so that trees of
+/// nested groups can be written out declaratively.
+#[derive(Debug, Clone)]
+pub struct GroupBuilder {
+    /// The name of the group.
+    name: Option<String>,
+    /// Whether the group is open in the layers panel.
+    is_open: bool,
+    /// The child layers, in the order given by the document’s layer order.
+    layers: Vec<Layer>,
+}
+
+// MARK: Creation
+
+impl GroupBuilder {
+    /// Creates a builder for an open group without a name or layers.
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            is_open: true,
+            layers: Vec::new(),
+        }
+    }
+}
+
+impl Default for GroupBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// MARK: Building
+
+impl GroupBuilder {
+    /// Sets the name of the group.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Sets whether the group is open in the layers panel.
+    pub fn open(mut self, is_open: bool) -> Self {
+        self.is_open = is_open;
+        self
+    }
+
+    /// Adds a child layer.
+    pub fn layer(mut self, layer: Layer) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    /// Adds a child group, built by a closure given a new builder.
+    pub fn group(mut self, build: impl FnOnce(GroupBuilder) -> GroupBuilder) -> Self {
+        self.layers.push(build(GroupBuilder::new()).build());
+        self
+    }
+
+    /// Returns the group layer.
+    pub fn build(self) -> Layer {
+        let mut group = Layer::group(self.layers, self.is_open);
+        group.name = self.name;
+        group
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graphics::Rect;
+
+    use super::*;
+
+    #[test]
+    fn build() {
+        let layer = |name: &str| {
+            let mut layer = Layer::new(Rect::new(0, 0, 2, 2));
+            layer.name = Some(name.to_string());
+            layer
+        };
+
+        let result = GroupBuilder::new()
+            .name("Outer")
+            .layer(layer("Background"))
+            .group(|group| {
+                group
+                    .name("Inner")
+                    .open(false)
+                    .layer(layer("First"))
+                    .layer(layer("Second"))
+            })
+            .build();
+
+        let mut inner_group = Layer::group(vec![layer("First"), layer("Second")], false);
+        inner_group.name = Some("Inner".to_string());
+        let mut outer_group = Layer::group(vec![layer("Background"), inner_group], true);
+        outer_group.name = Some("Outer".to_string());
+        assert_eq!(result, outer_group);
+    }
+}
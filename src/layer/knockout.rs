@@ -0,0 +1,32 @@
+/// The knockout setting for a layer, which lets it punch
+/// through the layers below it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Knockout {
+    /// No knockout.
+    None,
+    /// Knocks out to the bottom of the layer’s group.
+    Shallow,
+    /// Knocks out to the background layer.
+    Deep,
+}
+
+impl Knockout {
+    /// Creates a new knockout setting from a raw value.
+    pub fn from_value(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Knockout::None),
+            1 => Some(Knockout::Shallow),
+            2 => Some(Knockout::Deep),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw value for the knockout setting.
+    pub fn raw_value(&self) -> u8 {
+        match self {
+            Knockout::None => 0,
+            Knockout::Shallow => 1,
+            Knockout::Deep => 2,
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use file_stream::write::FileStreamWriter;
+
+use crate::descriptor::{Descriptor, DescriptorValue};
+use crate::endian::WriteBigEndian;
+
+use super::DropShadow;
+
+/// The version of the object based effects data.
+const OBJECT_EFFECTS_VERSION: u32 = 0;
+
+/// The version of the descriptor that follows.
+const DESCRIPTOR_VERSION: u32 = 16;
+
+/// The effects applied to a layer, written as a layer style.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LayerEffects {
+    /// The drop shadow, if there is one.
+    pub drop_shadow: Option<DropShadow>,
+}
+
+// MARK: Data
+
+impl LayerEffects {
+    /// Returns whether there are no effects to write.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.drop_shadow.is_none()
+    }
+
+    /// Returns the data for the `lfx2` additional layer information.
+    pub(crate) fn data(&self) -> anyhow::Result<Vec<u8>> {
+        let mut descriptor = Descriptor::new("null")
+            .with("Scl ", DescriptorValue::UnitFloat(b"#Prc", 100.0))
+            .with("masterFXSwitch", DescriptorValue::Boolean(true));
+        if let Some(drop_shadow) = &self.drop_shadow {
+            descriptor = descriptor.with(
+                "DrSh",
+                DescriptorValue::Descriptor(drop_shadow.descriptor()),
+            );
+        }
+
+        let mut file_stream = FileStreamWriter::new();
+        file_stream.write_u32(OBJECT_EFFECTS_VERSION)?;
+        file_stream.write_u32(DESCRIPTOR_VERSION)?;
+        file_stream.write_bytes(&descriptor.data()?)?;
+        Ok(file_stream.data().to_vec())
+    }
+}
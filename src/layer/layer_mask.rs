@@ -0,0 +1,203 @@
+use file_stream::write::FileStreamWriter;
+use graphics::{Rect, Size};
+
+use crate::data;
+use crate::reader::Reader;
+
+/// The flag for a disabled mask.
+const DISABLED_FLAG: u8 = 0b00000010;
+
+/// The flag for a mask with parameters applied, such as density.
+const PARAMETERS_APPLIED_FLAG: u8 = 0b00010000;
+
+/// The parameter flag for the user mask density.
+const USER_MASK_DENSITY_FLAG: u8 = 0b00000001;
+
+/// The parameter flag for the user mask feather.
+const USER_MASK_FEATHER_FLAG: u8 = 0b00000010;
+
+/// The shortest length of the layer mask data, which is padded to fit.
+const MINIMUM_DATA_LENGTH: usize = 20;
+
+/// A user supplied layer mask.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerMask {
+    /// The bounds of the mask, in document coordinates.
+    pub bounds: Rect<i32>,
+    /// The mask value outside of the bounds, 0 or 255.
+    pub default_color: u8,
+    /// Whether the mask is turned off.
+    pub is_disabled: bool,
+    /// The 8-bit mask values, one byte per pixel within the bounds.
+    pub data: Vec<u8>,
+    /// The density of the mask, from 0 to 255. Full density if not set.
+    pub density: Option<u8>,
+    /// The feather of the mask, in pixels. No feather if not set.
+    pub feather: Option<f64>,
+}
+
+// MARK: Creation
+
+impl LayerMask {
+    /// Creates a new layer mask covering the bounds.
+    pub fn new(bounds: Rect<i32>, data: Vec<u8>) -> Self {
+        Self {
+            bounds,
+            default_color: 0,
+            is_disabled: false,
+            data,
+            density: None,
+            feather: None,
+        }
+    }
+
+    /// Returns the size of the mask in pixels.
+    pub(crate) fn size(&self) -> Size<u32> {
+        Size {
+            width: self.bounds.width().max(0) as u32,
+            height: self.bounds.height().max(0) as u32,
+        }
+    }
+}
+
+// MARK: Encoding
+
+impl LayerMask {
+    /// Returns the layer mask data for the layer record, without
+    /// the length at the start.
+    pub(crate) fn data(&self) -> anyhow::Result<Vec<u8>> {
+        let mut file_stream = FileStreamWriter::new();
+        file_stream.write_be(&self.bounds.min_y())?;
+        file_stream.write_be(&self.bounds.min_x())?;
+        file_stream.write_be(&self.bounds.max_y())?;
+        file_stream.write_be(&self.bounds.max_x())?;
+        file_stream.write_be(&self.default_color)?;
+
+        let mut parameter_flags = 0;
+        if self.density.is_some() {
+            parameter_flags |= USER_MASK_DENSITY_FLAG;
+        }
+        if self.feather.is_some() {
+            parameter_flags |= USER_MASK_FEATHER_FLAG;
+        }
+
+        let mut flags = 0;
+        if self.is_disabled {
+            flags |= DISABLED_FLAG;
+        }
+        if parameter_flags != 0 {
+            flags |= PARAMETERS_APPLIED_FLAG;
+        }
+        file_stream.write_be(&flags)?;
+
+        // The parameters, in the order of their flags.
+        if parameter_flags != 0 {
+            file_stream.write_be(&parameter_flags)?;
+            if let Some(density) = self.density {
+                file_stream.write_be(&density)?;
+            }
+            if let Some(feather) = self.feather {
+                file_stream.write_be(&feather.to_bits())?;
+            }
+        }
+
+        let mut data = file_stream.data().to_vec();
+        if data.len() < MINIMUM_DATA_LENGTH {
+            data.resize(MINIMUM_DATA_LENGTH, 0);
+        } else {
+            data::pad(&mut data, 2);
+        }
+        Ok(data)
+    }
+}
+
+// MARK: Decoding
+
+impl LayerMask {
+    /// Reads the layer mask data from a layer record, returning nothing
+    /// if the section is empty. The mask values are read later, along
+    /// with the rest of the channel data.
+    pub(crate) fn from_reader(reader: &mut Reader) -> anyhow::Result<Option<Self>> {
+        if reader.remaining() == 0 {
+            return Ok(None);
+        }
+
+        let top = reader.read_i32()?;
+        let left = reader.read_i32()?;
+        let bottom = reader.read_i32()?;
+        let right = reader.read_i32()?;
+        let mut mask = LayerMask::new(Rect::new(left, top, right - left, bottom - top), Vec::new());
+        mask.default_color = reader.read_u8()?;
+
+        let flags = reader.read_u8()?;
+        mask.is_disabled = flags & DISABLED_FLAG != 0;
+        if flags & PARAMETERS_APPLIED_FLAG != 0 {
+            let parameter_flags = reader.read_u8()?;
+            if parameter_flags & USER_MASK_DENSITY_FLAG != 0 {
+                mask.density = Some(reader.read_u8()?);
+            }
+            if parameter_flags & USER_MASK_FEATHER_FLAG != 0 {
+                mask.feather = Some(f64::from_bits(u64::from_be_bytes(reader.read_array()?)));
+            }
+        }
+
+        Ok(Some(mask))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data() {
+        let mask = LayerMask::new(Rect::new(1, 2, 3, 4), vec![0xff; 12]);
+
+        let expected_data = vec![
+            0x00, 0x00, 0x00, 0x02, // Top
+            0x00, 0x00, 0x00, 0x01, // Left
+            0x00, 0x00, 0x00, 0x06, // Bottom
+            0x00, 0x00, 0x00, 0x04, // Right
+            0x00, // Default colour
+            0x00, // Flags
+            0x00, 0x00, // Padding
+        ];
+        assert_eq!(mask.data().unwrap(), expected_data);
+    }
+
+    #[test]
+    fn feather_data() {
+        let mut mask = LayerMask::new(Rect::new(0, 0, 2, 2), vec![0xff; 4]);
+        mask.feather = Some(2.5);
+
+        let data = mask.data().unwrap();
+
+        // The parameters applied flag.
+        assert_eq!(data[17], 0b00010000);
+        // The user mask feather parameter flag, then the double.
+        assert_eq!(data[18], 0b00000010);
+        assert_eq!(data[19..27], 2.5f64.to_be_bytes());
+        // Padded to an even length.
+        assert_eq!(data.len(), 28);
+
+        let result = LayerMask::from_reader(&mut Reader::new(&data))
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.bounds, mask.bounds);
+        assert_eq!(result.feather, Some(2.5));
+        assert_eq!(result.density, None);
+    }
+
+    #[test]
+    fn density_data() {
+        let mut mask = LayerMask::new(Rect::new(0, 0, 2, 2), vec![0xff; 4]);
+        mask.density = Some(0x80);
+        mask.is_disabled = true;
+
+        let data = mask.data().unwrap();
+
+        assert_eq!(data[17], 0b00010010);
+        assert_eq!(data[18..20], [0b00000001, 0x80]);
+        assert_eq!(data.len(), 20);
+    }
+}
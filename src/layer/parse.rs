@@ -0,0 +1,352 @@
+use graphics::{Rect, Size};
+
+use crate::blend_mode::BlendMode;
+use crate::color_channel::{ColorChannel, ColorChannelType};
+use crate::document::constants;
+use crate::error::ReadError;
+use crate::image;
+use crate::reader::Reader;
+
+use super::color_label::ColorLabel;
+use super::divider_type::DividerType;
+use super::group::GroupInfo;
+use super::knockout::Knockout;
+use super::layer_mask::LayerMask;
+use super::{Layer, LayerType};
+
+/// The channel information from a layer record, needed to read
+/// the channel data that follows all of the records.
+pub(crate) struct ChannelInfo {
+    /// The type of channel.
+    pub color_type: ColorChannelType,
+    /// The length of the channel data, including the compression.
+    pub length: usize,
+}
+
+// MARK: Decoding
+
+impl Layer {
+    /// Reads a layer record, returning the layer along with the
+    /// information needed to read its channel data.
+    pub(crate) fn from_record(reader: &mut Reader) -> anyhow::Result<(Self, Vec<ChannelInfo>)> {
+        // The rectangle / bounds.
+        let top = reader.read_i32()?;
+        let left = reader.read_i32()?;
+        let bottom = reader.read_i32()?;
+        let right = reader.read_i32()?;
+        let mut layer = Layer::new(Rect::new(left, top, right - left, bottom - top));
+
+        // The channel information.
+        layer.number_of_channels = reader.read_i16()?;
+        let mut channel_info = Vec::new();
+        for _ in 0..layer.number_of_channels {
+            let raw_color_type = reader.read_i16()?;
+            let length = reader.read_u32()? as usize;
+            channel_info.push(ChannelInfo {
+                color_type: ColorChannelType::from_value(raw_color_type)
+                    .unwrap_or(ColorChannelType::Unknown),
+                length,
+            });
+        }
+
+        if reader.read_array::<4>()? != constants::RESOURCE_SIGNATURE {
+            anyhow::bail!(ReadError::InvalidSignature);
+        }
+        let blend_mode_key = reader.read_array::<4>()?;
+        layer.blend_mode = BlendMode::from_bytes(&blend_mode_key);
+
+        layer.opacity = reader.read_u8()?;
+        // Clipping.
+        reader.skip(1)?;
+        let flags = reader.read_u8()?;
+        layer.is_hidden = flags & 0b00000010 != 0;
+        layer.pixel_data_is_irrelevant = flags & 0b00011000 == 0b00011000;
+        // Filler.
+        reader.skip(1)?;
+
+        let mut extra_data = reader.read_section()?;
+        layer.mask = LayerMask::from_reader(&mut extra_data.read_section()?)?;
+        // Layer blending ranges.
+        extra_data.read_section()?;
+
+        let name = extra_data.read_pascal_string(4)?;
+        layer.name = Some(name);
+
+        // The additional layer information. Anything that isn’t
+        // understood is kept so that it can be written back out.
+        let mut additional_layer_information = Vec::new();
+        while extra_data.remaining() >= 12 {
+            let signature = extra_data.read_array::<4>()?;
+            let key = extra_data.read_array::<4>()?;
+            let length = extra_data.read_u32()?;
+            let mut block = extra_data.section(length as usize)?;
+            match &key {
+                b"luni" => {
+                    let length = block.read_u32()?;
+                    let mut characters = Vec::new();
+                    for _ in 0..length {
+                        characters.push(block.read_u16()?);
+                    }
+                    layer.name = Some(String::from_utf16_lossy(&characters));
+                }
+                b"lsct" => {
+                    layer.divider_type =
+                        DividerType::from_value(block.read_u32()?).unwrap_or(DividerType::Other);
+                    // The extended form has the signature and the blend mode.
+                    if block.remaining() >= 8 {
+                        block.skip(4)?;
+                        let blend_mode_key = block.read_array::<4>()?;
+                        layer.blend_mode = BlendMode::from_bytes(&blend_mode_key);
+                        layer.writes_divider_blend_mode = true;
+                    }
+                }
+                b"infx" => layer.blends_interior_effects_as_group = block.read_u8()? != 0,
+                b"clbl" => layer.blends_clipped_layers_as_group = block.read_u8()? != 0,
+                b"tsly" => layer.transparency_shapes_layer = block.read_u8()? != 0,
+                b"lmgm" => layer.layer_mask_hides_effects = block.read_u8()? != 0,
+                b"iOpa" => layer.fill_opacity = block.read_u8()?,
+                b"brst" => {
+                    while block.remaining() >= 4 {
+                        let value = block.read_i32()?;
+                        if let Some(channel_type) = i16::try_from(value)
+                            .ok()
+                            .and_then(ColorChannelType::from_value)
+                        {
+                            layer.restricted_channels.push(channel_type);
+                        }
+                    }
+                }
+                b"knko" => {
+                    layer.knockout =
+                        Knockout::from_value(block.read_u8()?).unwrap_or(Knockout::None);
+                }
+                b"lnsr" => layer.name_source = Some(block.read_array::<4>()?),
+                b"lyid" => layer.id = Some(block.read_u32()?),
+                b"cust" => {
+                    layer.metadata = Some(block.read_bytes(length as usize)?.to_vec());
+                }
+                b"lclr" => {
+                    layer.color_label =
+                        ColorLabel::from_value(block.read_u16()?).unwrap_or(ColorLabel::None);
+                }
+                _ => {
+                    additional_layer_information.extend_from_slice(&signature);
+                    additional_layer_information.extend_from_slice(&key);
+                    additional_layer_information.extend_from_slice(&length.to_be_bytes());
+                    additional_layer_information
+                        .extend_from_slice(block.read_bytes(length as usize)?);
+                }
+            }
+        }
+        if !additional_layer_information.is_empty() {
+            layer.additional_layer_information = Some(additional_layer_information);
+        }
+
+        Ok((layer, channel_info))
+    }
+
+    /// Reads the channel data for the layer, creating the layer image
+    /// when the layer has red, green and blue channels.
+    pub(crate) fn read_channel_data(
+        &mut self,
+        reader: &mut Reader,
+        channel_info: &[ChannelInfo],
+    ) -> anyhow::Result<()> {
+        let size = Size {
+            width: self.bounds.width().max(0) as u32,
+            height: self.bounds.height().max(0) as u32,
+        };
+
+        self.channels.clear();
+        for info in channel_info {
+            // The mask channel covers the mask bounds rather than the layer.
+            let size = match &self.mask {
+                Some(mask) if info.color_type == ColorChannelType::UserSuppliedLayerMask => {
+                    mask.size()
+                }
+                _ => size,
+            };
+            let mut section = reader.section(info.length)?;
+            let data = if size.width == 0 || size.height == 0 {
+                Vec::new()
+            } else {
+                image::decoded_channels(&mut section, size, 1)?.remove(0)
+            };
+            if info.color_type == ColorChannelType::UserSuppliedLayerMask {
+                if let Some(mask) = &mut self.mask {
+                    mask.data = data.clone();
+                }
+            }
+            let mut channel = ColorChannel::new(info.color_type.clone(), data.len());
+            channel.data = data;
+            self.channels.push(channel);
+        }
+
+        let channel_data = |color_type: ColorChannelType| {
+            self.channels
+                .iter()
+                .find(|channel| channel.color_type == color_type)
+                .map(|channel| channel.data.as_slice())
+        };
+        self.image = match (
+            channel_data(ColorChannelType::Red),
+            channel_data(ColorChannelType::Green),
+            channel_data(ColorChannelType::Blue),
+        ) {
+            (Some(red), Some(green), Some(blue)) if !red.is_empty() => {
+                Some(image::image_from_channels(
+                    size,
+                    red,
+                    green,
+                    blue,
+                    channel_data(ColorChannelType::Alpha),
+                ))
+            }
+            _ => None,
+        };
+
+        Ok(())
+    }
+
+    /// Rebuilds the group structure from layers read in file order,
+    /// consuming the section dividers that mark the end of each group.
+    pub(crate) fn nested_layers(layers: Vec<Layer>) -> anyhow::Result<Vec<Layer>> {
+        // The layers at each level of nesting, with the document last.
+        let mut levels: Vec<Vec<Layer>> = vec![Vec::new()];
+
+        for mut layer in layers {
+            match layer.divider_type {
+                DividerType::SectionDivider => levels.push(Vec::new()),
+                DividerType::OpenFolder | DividerType::ClosedFolder => {
+                    if levels.len() < 2 {
+                        anyhow::bail!(ReadError::UnbalancedGroups);
+                    }
+                    let child_layers = levels.pop().unwrap_or_default();
+                    // Groups have no pixels of their own.
+                    layer.layer_type = LayerType::Group(GroupInfo::new(child_layers));
+                    layer.channels.clear();
+                    layer.image = None;
+                    if let Some(level) = levels.last_mut() {
+                        level.push(layer);
+                    }
+                }
+                DividerType::Other => {
+                    if let Some(level) = levels.last_mut() {
+                        level.push(layer);
+                    }
+                }
+            }
+        }
+
+        if levels.len() != 1 {
+            anyhow::bail!(ReadError::UnbalancedGroups);
+        }
+
+        Ok(levels.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use graphics::{Image, Point, Rect};
+
+    use crate::reader::Reader;
+
+    use super::*;
+
+    #[test]
+    fn from_record() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("tests/resources/tiny-clouds.png");
+        let source_image = Image::open(&path).unwrap();
+
+        let bounds = Rect {
+            origin: Point::zero(),
+            size: source_image.size.into(),
+        };
+        let mut layer = Layer::new(bounds);
+        layer.name = Some("Clouds ’24".to_string());
+        layer.opacity = 0x80;
+        layer.is_hidden = true;
+        layer.blend_mode = BlendMode::Screen;
+        layer.transparency_shapes_layer = false;
+        layer.fill_opacity = 0x40;
+        layer.restricted_channels = vec![ColorChannelType::Red, ColorChannelType::Blue];
+        layer.image = Some(source_image);
+
+        let record_data = layer.layer_record_data().unwrap();
+        let image_data = layer.encoded_image().unwrap();
+
+        let mut reader = Reader::new(&record_data);
+        let (mut result, channel_info) = Layer::from_record(&mut reader).unwrap();
+        assert_eq!(reader.remaining(), 0);
+
+        let mut reader = Reader::new(&image_data);
+        result
+            .read_channel_data(&mut reader, &channel_info)
+            .unwrap();
+        assert_eq!(reader.remaining(), 0);
+
+        assert_eq!(result.bounds, layer.bounds);
+        assert_eq!(result.name, layer.name);
+        assert_eq!(result.opacity, 0x80);
+        assert!(result.is_hidden);
+        assert_eq!(result.blend_mode, BlendMode::Screen);
+        assert!(!result.transparency_shapes_layer);
+        assert_eq!(result.fill_opacity, 0x40);
+        assert_eq!(result.restricted_channels, layer.restricted_channels);
+        assert_eq!(result.channels.len(), 4);
+        assert_eq!(result.image, layer.image);
+    }
+
+    #[test]
+    fn from_record_with_mask() {
+        let bounds = Rect::new(0, 0, 3, 2);
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(Image::empty(bounds.size.into()));
+        let mut mask = LayerMask::new(Rect::new(1, 0, 2, 1), vec![0x00, 0xff]);
+        mask.density = Some(0x80);
+        mask.feather = Some(1.5);
+        layer.mask = Some(mask);
+
+        let record_data = layer.layer_record_data().unwrap();
+        let image_data = layer.encoded_image().unwrap();
+
+        let mut reader = Reader::new(&record_data);
+        let (mut result, channel_info) = Layer::from_record(&mut reader).unwrap();
+        let mut reader = Reader::new(&image_data);
+        result
+            .read_channel_data(&mut reader, &channel_info)
+            .unwrap();
+        assert_eq!(reader.remaining(), 0);
+
+        assert_eq!(result.channels.len(), 5);
+        assert_eq!(result.mask, layer.mask);
+    }
+
+    #[test]
+    fn nested_layers_unbalanced() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let group = Layer::group(vec![Layer::new(bounds)], true);
+        let mut layers = group.record_layers();
+
+        // A group without its section divider.
+        let result = Layer::nested_layers(layers[1..].to_vec());
+        let error = result.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ReadError>(),
+            Some(ReadError::UnbalancedGroups)
+        ));
+
+        // A section divider without its group.
+        layers.pop();
+        let result = Layer::nested_layers(layers);
+        let error = result.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ReadError>(),
+            Some(ReadError::UnbalancedGroups)
+        ));
+    }
+}
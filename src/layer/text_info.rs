@@ -0,0 +1,48 @@
+use graphics::{Color, Image};
+
+use crate::error;
+
+/// A source of glyphs that draws text into an image, so that text
+/// layers can be baked into pixels for viewers that can’t lay out text,
+/// without the crate depending on a particular font engine.
+pub trait FontSource {
+    /// Returns the text drawn into an image just large enough to hold it.
+    fn rasterize(&self, text_info: &TextInfo) -> anyhow::Result<Image>;
+}
+
+/// The text and style of a text layer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextInfo {
+    /// The text, with lines separated by `\n`.
+    pub text: String,
+    /// The PostScript name of the font.
+    pub font_name: String,
+    /// The size of the font in points.
+    pub font_size: f64,
+    /// The colour of the text.
+    pub color: Color,
+}
+
+// MARK: Creation
+
+impl TextInfo {
+    /// Creates new text info in black.
+    pub fn new(text: &str, font_name: &str, font_size: f64) -> Self {
+        Self {
+            text: text.to_string(),
+            font_name: font_name.to_string(),
+            font_size,
+            color: Color::BLACK,
+        }
+    }
+}
+
+// MARK: Rendering
+
+impl TextInfo {
+    /// Returns the text drawn by a font source. Errors from the font
+    /// source are kept as their message.
+    pub fn rasterize(&self, font_source: &impl FontSource) -> error::Result<Image> {
+        Ok(font_source.rasterize(self)?)
+    }
+}
@@ -31,10 +31,65 @@ pub trait LayerContainer {
         for layer in self.layers() {
             output.push(layer);
             if let LayerType::Group(info) = &layer.layer_type {
-                output.append(&mut info.layers());
+                output.append(&mut info.all_layers());
             };
         }
 
         output
     }
+
+    /// Returns an iterator over all the layers, recursing into any
+    /// groups, along with how deeply each layer is nested. Layers
+    /// directly in this container have a depth of zero.
+    fn iter_with_depth(&self) -> impl Iterator<Item = (usize, &Layer)> {
+        let mut output = Vec::new();
+
+        for layer in self.layers() {
+            output.push((0, layer));
+            if let LayerType::Group(info) = &layer.layer_type {
+                output.extend(
+                    info.iter_with_depth()
+                        .map(|(depth, layer)| (depth + 1, layer)),
+                );
+            };
+        }
+
+        output.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graphics::Rect;
+
+    use crate::document::Document;
+
+    use super::*;
+
+    #[test]
+    fn iter_with_depth() {
+        let bounds = Rect::new(0, 0, 2, 2);
+        let mut child_0 = Layer::new(bounds);
+        child_0.name = Some("Child 0".to_string());
+        let mut child_1 = Layer::new(bounds);
+        child_1.name = Some("Child 1".to_string());
+        let mut group = Layer::group(vec![child_0, child_1], true);
+        group.name = Some("Group".to_string());
+
+        let mut document = Document::new();
+        document.layers = vec![group];
+
+        let result: Vec<(usize, Option<String>)> = document
+            .iter_with_depth()
+            .map(|(depth, layer)| (depth, layer.name.clone()))
+            .collect();
+        assert_eq!(
+            result,
+            vec![
+                (0, Some("Group".to_string())),
+                (1, Some("Child 0".to_string())),
+                (1, Some("Child 1".to_string())),
+            ]
+        );
+    }
 }
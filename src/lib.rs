@@ -1,18 +1,35 @@
 mod blend_mode;
+mod channel_source;
 mod color_channel;
 mod color_mode;
 pub mod color_space;
+mod composite;
 mod data;
+mod descriptor;
 mod document;
+mod endian;
 pub mod error;
+mod file_format;
 pub mod file_stream;
 mod image;
 mod image_compression;
 mod layer;
 mod layer_container;
-mod rle;
+mod psd_writer;
+mod reader;
+pub mod rle;
 mod string;
+mod validation;
+mod zip;
 
 pub use blend_mode::*;
+pub use channel_source::{ChannelSource, SourcedChannels};
+pub use color_channel::{ColorChannel, ColorChannelType};
+pub use color_mode::ColorMode;
 pub use document::*;
+pub use file_format::FileFormat;
+pub use image_compression::ImageCompression;
 pub use layer::*;
+pub use layer_container::LayerContainer;
+pub use psd_writer::PsdWriter;
+pub use validation::validate_bytes;
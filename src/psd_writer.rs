@@ -0,0 +1,164 @@
+use file_stream::write::FileStreamWriter;
+
+use crate::data;
+use crate::document::image_resources;
+use crate::endian::WriteBigEndian;
+use crate::error::{self, WriteError};
+
+/// A low-level writer for assembling Photoshop data by hand, for
+/// sections that `Document` doesn’t model. Sections that start with
+/// their length can be nested, and each length is filled in when its
+/// section ends.
+pub struct PsdWriter {
+    /// The data written so far, followed by the data for each length
+    /// section that hasn’t ended yet, from the outermost in.
+    streams: Vec<FileStreamWriter>,
+}
+
+// MARK: Creation
+
+impl PsdWriter {
+    /// Creates a new writer with no data.
+    pub fn new() -> Self {
+        Self {
+            streams: vec![FileStreamWriter::new()],
+        }
+    }
+}
+
+impl Default for PsdWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// MARK: Writing
+
+impl PsdWriter {
+    /// Writes bytes as they are.
+    pub fn write_bytes(&mut self, data: &[u8]) -> error::Result<()> {
+        self.stream().write_bytes(data)?;
+        Ok(())
+    }
+
+    /// Writes a big endian signed 16-bit integer.
+    pub fn write_i16(&mut self, value: i16) -> error::Result<()> {
+        Ok(self.stream().write_i16(value)?)
+    }
+
+    /// Writes a big endian unsigned 16-bit integer.
+    pub fn write_u16(&mut self, value: u16) -> error::Result<()> {
+        Ok(self.stream().write_u16(value)?)
+    }
+
+    /// Writes a big endian unsigned 32-bit integer.
+    pub fn write_u32(&mut self, value: u32) -> error::Result<()> {
+        Ok(self.stream().write_u32(value)?)
+    }
+
+    /// Starts a section that begins with its length as four bytes. The
+    /// length covers everything written until the section ends.
+    pub fn begin_length_section(&mut self) {
+        self.streams.push(FileStreamWriter::new());
+    }
+
+    /// Ends the innermost length section, writing its length followed
+    /// by its data.
+    pub fn end_length_section(&mut self) -> error::Result<()> {
+        // The first stream is the data outside of any section.
+        if self.streams.len() < 2 {
+            return Err(WriteError::UnbalancedLengthSections.into());
+        }
+        let section = self.streams.remove(self.streams.len() - 1);
+        let section_data = section.data();
+        let length = data::section_length(section_data.len())?;
+        self.stream().write_u32(length)?;
+        self.stream().write_bytes(section_data)?;
+        Ok(())
+    }
+
+    /// Writes an image resource block with a name, which can be empty.
+    /// The name and the data are padded to even lengths.
+    pub fn write_8bim_resource(
+        &mut self,
+        identifier: i16,
+        name: &str,
+        data: &[u8],
+    ) -> error::Result<()> {
+        image_resources::write_named_image_resource(self.stream(), identifier, name, data)?;
+        Ok(())
+    }
+
+    /// Returns the data written, or an error if a length section
+    /// hasn’t ended.
+    pub fn into_data(self) -> error::Result<Vec<u8>> {
+        match self.streams.as_slice() {
+            [stream] => Ok(stream.data().to_vec()),
+            _ => Err(WriteError::UnbalancedLengthSections.into()),
+        }
+    }
+
+    /// Returns the stream for the innermost section.
+    fn stream(&mut self) -> &mut FileStreamWriter {
+        let index = self.streams.len() - 1;
+        &mut self.streams[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+
+    use super::*;
+
+    #[test]
+    fn nested_length_sections() {
+        let mut writer = PsdWriter::new();
+        writer.write_bytes(&[0xaa]).unwrap();
+        writer.begin_length_section();
+        writer.write_u16(0x0102).unwrap();
+        writer.begin_length_section();
+        writer.write_bytes(&[0x03]).unwrap();
+        writer.end_length_section().unwrap();
+        writer.end_length_section().unwrap();
+
+        let expected_data = [
+            0xaa, // Before the sections
+            0x00, 0x00, 0x00, 0x07, // The length of the outer section
+            0x01, 0x02, // The outer section
+            0x00, 0x00, 0x00, 0x01, // The length of the inner section
+            0x03, // The inner section
+        ];
+        assert_eq!(writer.into_data().unwrap(), expected_data);
+    }
+
+    #[test]
+    fn unbalanced_length_sections() {
+        let mut writer = PsdWriter::new();
+        let error = writer.end_length_section().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Write(WriteError::UnbalancedLengthSections)
+        ));
+
+        writer.begin_length_section();
+        let error = writer.into_data().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Write(WriteError::UnbalancedLengthSections)
+        ));
+    }
+
+    #[test]
+    fn write_8bim_resource() {
+        let mut writer = PsdWriter::new();
+        writer.write_8bim_resource(0x0400, "", &[0x00]).unwrap();
+
+        // 8BIM, the identifier, an empty name, the length and the
+        // data padded to an even length.
+        let expected_data = [
+            0x38, 0x42, 0x49, 0x4d, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+        ];
+        assert_eq!(writer.into_data().unwrap(), expected_data);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::error::ReadError;
+use crate::string;
+
+/// Reads big endian values from Photoshop data, keeping track
+/// of the current position.
+pub(crate) struct Reader<'a> {
+    /// The data being read.
+    data: &'a [u8],
+    /// The position of the next byte to read.
+    position: usize,
+}
+
+// MARK: Creation
+
+impl<'a> Reader<'a> {
+    /// Creates a new reader for some data.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+}
+
+// MARK: Reading
+
+impl<'a> Reader<'a> {
+    /// Returns the number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    /// Reads a number of bytes.
+    pub fn read_bytes(&mut self, length: usize) -> anyhow::Result<&'a [u8]> {
+        if length > self.remaining() {
+            anyhow::bail!(ReadError::UnexpectedEnd(self.position));
+        }
+        let bytes = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    /// Reads a fixed number of bytes.
+    pub fn read_array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+
+    /// Skips over a number of bytes.
+    pub fn skip(&mut self, length: usize) -> anyhow::Result<()> {
+        self.read_bytes(length)?;
+        Ok(())
+    }
+
+    /// Returns a reader for the next section of data, and skips over it.
+    pub fn section(&mut self, length: usize) -> anyhow::Result<Reader<'a>> {
+        Ok(Reader::new(self.read_bytes(length)?))
+    }
+
+    /// Reads an unsigned byte.
+    pub fn read_u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    /// Reads a big endian signed 16-bit integer.
+    pub fn read_i16(&mut self) -> anyhow::Result<i16> {
+        Ok(i16::from_be_bytes(self.read_array()?))
+    }
+
+    /// Reads a big endian unsigned 16-bit integer.
+    pub fn read_u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    /// Reads a big endian signed 32-bit integer.
+    pub fn read_i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_be_bytes(self.read_array()?))
+    }
+
+    /// Reads a big endian unsigned 32-bit integer.
+    pub fn read_u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    /// Reads a length-prefixed section, with the length as a
+    /// big endian unsigned 32-bit integer.
+    pub fn read_section(&mut self) -> anyhow::Result<Reader<'a>> {
+        let length = self.read_u32()? as usize;
+        self.section(length)
+    }
+
+    /// Reads a Pascal string, skipping any padding so that the total
+    /// length is a multiple of `padding` bytes.
+    pub fn read_pascal_string(&mut self, padding: usize) -> anyhow::Result<String> {
+        let length = self.read_u8()? as usize;
+        let bytes = self.read_bytes(length)?;
+        let total_length = length + 1;
+        let padded_length = total_length.div_ceil(padding) * padding;
+        self.skip(padded_length - total_length)?;
+        Ok(string::pascal::string_from_data(bytes))
+    }
+
+    /// Reads a Unicode string, stored as the number of UTF-16 code
+    /// units followed by the code units.
+    pub fn read_unicode_string(&mut self) -> anyhow::Result<String> {
+        let length = self.read_u32()?;
+        let mut characters = Vec::new();
+        for _ in 0..length {
+            characters.push(self.read_u16()?);
+        }
+        Ok(String::from_utf16_lossy(&characters))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ReadError;
+
+    use super::Reader;
+
+    #[test]
+    fn read_values() {
+        let data = vec![0xff, 0xfe, 0x00, 0x00, 0x01, 0x00, 0x2a];
+        let mut reader = Reader::new(&data);
+
+        assert_eq!(reader.read_i16().unwrap(), -2);
+        assert_eq!(reader.read_u32().unwrap(), 256);
+        assert_eq!(reader.read_u8().unwrap(), 0x2a);
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn read_past_end() {
+        let data = vec![0x00, 0x01];
+        let mut reader = Reader::new(&data);
+
+        let error = reader.read_u32().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ReadError>(),
+            Some(ReadError::UnexpectedEnd(0))
+        ));
+    }
+
+    #[test]
+    fn read_pascal_string() {
+        let data = vec![0x02, 0x4c, 0x31, 0x00, 0xff];
+        let mut reader = Reader::new(&data);
+
+        assert_eq!(reader.read_pascal_string(4).unwrap(), "L1");
+        assert_eq!(reader.remaining(), 1);
+    }
+
+    #[test]
+    fn read_unicode_string() {
+        let data = vec![0x00, 0x00, 0x00, 0x02, 0x00, 0x50, 0x20, 0x19, 0xff];
+        let mut reader = Reader::new(&data);
+
+        assert_eq!(reader.read_unicode_string().unwrap(), "P’");
+        assert_eq!(reader.remaining(), 1);
+    }
+}
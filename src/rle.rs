@@ -1,87 +1,142 @@
+//! PackBits run-length encoding, as used for the image data in
+//! Photoshop documents and some other Apple formats.
+
 // PackBits technical note: https://web.archive.org/web/20080705155158/http://developer.apple.com/technotes/tn/tn1023.html
 
-/// Returns the data encoded using the RLE algorithm.
+use crate::error::{self, ReadError};
+
+/// The most bytes a single run or literal can hold.
+const MAXIMUM_RUN_LENGTH: usize = 128;
+
+/// The fewest repeated bytes that are always written as a run. A byte
+/// repeated only twice is written as a run when it’s next to another run,
+/// and otherwise stays within a literal.
+const MINIMUM_REPEAT_LENGTH: usize = 3;
+
+/// The number of bytes in a pair.
+const PAIR_LENGTH: usize = 2;
+
+/// Returns the data encoded using the RLE algorithm, making the same
+/// choices between runs and literals as Photoshop.
+///
+/// A byte repeated three or more times is written as a run. A byte
+/// repeated twice is written as a run when it follows a run or comes just
+/// before one, and otherwise stays within a literal. Runs and literals
+/// hold at most 128 bytes. A literal ends before a run, unless the run
+/// starts in the last two bytes of a full literal, in which case the run
+/// is split.
+///
+/// ```
+/// let encoded = psd::rle::encoded(&[0xaa, 0xaa, 0xaa, 0x80, 0x00]);
+/// assert_eq!(encoded, vec![0xfe, 0xaa, 0x01, 0x80, 0x00]);
+/// ```
 pub fn encoded(source: &[u8]) -> Vec<u8> {
     let mut output = Vec::new();
-
-    let mut repeat_count = 0;
-    let mut non_repeating_run = Vec::new();
-    let mut previous_byte: Option<u8> = None;
-    let mut previous_repeat_count = 0;
-
-    for (index, byte) in source.iter().enumerate() {
-        let byte = *byte;
-        let is_end = index == source.len() - 1;
-
-        if Some(byte) == previous_byte && previous_repeat_count != 128 {
-            repeat_count += 1;
-        } else {
-            repeat_count = 1;
+    let mut index = 0;
+    let mut follows_run = false;
+
+    while index < source.len() {
+        let byte = source[index];
+        let repeat_count = run_length(&source[index..]);
+        let is_run = repeat_count >= MINIMUM_REPEAT_LENGTH
+            || repeat_count == PAIR_LENGTH
+                && (follows_run
+                    || run_length(&source[index + PAIR_LENGTH..]) >= MINIMUM_REPEAT_LENGTH);
+
+        // A run of the same byte, with a header of 1 minus the count.
+        if is_run {
+            output.push((1 - repeat_count as isize) as u8);
+            output.push(byte);
+            index += repeat_count;
+            follows_run = true;
+            continue;
         }
 
-        non_repeating_run.push(byte);
-
-        // Ending a non-repeating run due to too many repeats.
-        if repeat_count == 3 {
-            let length = non_repeating_run.len();
-            // Drop the last 3 elements
-            if length >= 3 {
-                non_repeating_run.truncate(length - 3);
-            } else {
-                non_repeating_run.clear();
-            }
-            if non_repeating_run.is_empty() == false {
-                output.push((non_repeating_run.len() - 1) as u8);
-                output.append(&mut non_repeating_run);
+        // A literal, which ends where the next run starts. A run is only
+        // noticed once its first three bytes fit within the literal.
+        let start = index;
+        while index < source.len() && index - start < MAXIMUM_RUN_LENGTH {
+            let fits_run = index - start <= MAXIMUM_RUN_LENGTH - MINIMUM_REPEAT_LENGTH;
+            if fits_run && run_length(&source[index..]) >= MINIMUM_REPEAT_LENGTH {
+                break;
             }
-            non_repeating_run.clear();
+            index += 1;
         }
+        output.push((index - start - 1) as u8);
+        output.extend_from_slice(&source[start..index]);
+        follows_run = false;
+    }
 
-        // Ending a non-repeating run because the data size
-        // got too high.
-        if non_repeating_run.len() == 128 {
-            output.push((non_repeating_run.len() - 1) as u8);
-            output.append(&mut non_repeating_run);
-            non_repeating_run.clear();
-            repeat_count = 0;
-        }
+    output
+}
 
-        // Ending a repeating run because the repeating
-        // value has stopped repeating.
-        if previous_repeat_count > 2 && repeat_count == 1 {
-            let Some(previous_byte) = previous_byte else {
-                break;
-            };
-            output.push(u8::MAX - (previous_repeat_count - 1) as u8 + 1);
-            output.push(previous_byte);
-            non_repeating_run = vec![byte];
-        }
+/// Returns the number of times the first byte of the data is repeated,
+/// up to the most bytes a run can hold.
+fn run_length(source: &[u8]) -> usize {
+    let Some(&byte) = source.first() else {
+        return 0;
+    };
+    source
+        .iter()
+        .take(MAXIMUM_RUN_LENGTH)
+        .take_while(|&&other| other == byte)
+        .count()
+}
 
-        // Handle the end of the data.
-        if is_end {
-            // Write the final repeat.
-            if repeat_count >= 3 {
-                output.push(u8::MAX - (repeat_count - 1) + 1);
-                output.push(byte);
-            }
-            // Write the final non-repeating run.
-            else if non_repeating_run.is_empty() == false {
-                output.push((non_repeating_run.len() - 1) as u8);
-                output.append(&mut non_repeating_run);
-            }
+/// Returns the data decoded using the RLE algorithm, stopping
+/// once `length` bytes have been decoded.
+///
+/// ```
+/// let decoded = psd::rle::decoded(&[0xfe, 0xaa, 0x01, 0x80, 0x00], 5).unwrap();
+/// assert_eq!(decoded, vec![0xaa, 0xaa, 0xaa, 0x80, 0x00]);
+/// ```
+pub fn decoded(source: &[u8], length: usize) -> error::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(length);
+    let mut index = 0;
+
+    while output.len() < length {
+        let Some(&header) = source.get(index) else {
+            return Err(ReadError::InvalidRleData.into());
+        };
+        let header = header as i8;
+        index += 1;
+
+        if header >= 0 {
+            // A run of literal bytes.
+            let count = header as usize + 1;
+            let Some(bytes) = source.get(index..index + count) else {
+                return Err(ReadError::InvalidRleData.into());
+            };
+            output.extend_from_slice(bytes);
+            index += count;
+        } else if header != i8::MIN {
+            // A repeated byte.
+            let count = 1 - header as isize;
+            let Some(&byte) = source.get(index) else {
+                return Err(ReadError::InvalidRleData.into());
+            };
+            output.extend(std::iter::repeat_n(byte, count as usize));
+            index += 1;
         }
+        // -128 is a no-op.
+    }
 
-        previous_byte = Some(byte);
-        previous_repeat_count = repeat_count;
+    if output.len() != length {
+        return Err(ReadError::InvalidRleData.into());
     }
 
-    output
+    Ok(output)
 }
 
 // MARK: Test
 
 #[cfg(test)]
 mod tests {
+    use std::path::PathBuf;
+
+    use crate::error::{Error, ReadError};
+    use crate::reader::Reader;
+
     #[test]
     fn encode() {
         let original_bytes = vec![
@@ -119,6 +174,29 @@ mod tests {
         assert_eq!(encoded_data[14], 0xAA);
     }
 
+    #[test]
+    fn decode() {
+        let original_bytes = vec![
+            0xAA, 0xAA, 0xAA, 0x80, 0x00, 0x2A, 0xAA, 0xAA, 0xAA, 0xAA, 0x80, 0x00, 0x2A, 0x22,
+            0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA,
+        ];
+
+        let encoded_data = super::encoded(&original_bytes);
+        let decoded_data = super::decoded(&encoded_data, original_bytes.len()).unwrap();
+
+        assert_eq!(decoded_data, original_bytes);
+    }
+
+    #[test]
+    fn decode_truncated() {
+        let encoded_data = vec![0x03, 0xAA, 0xAA];
+        let result = super::decoded(&encoded_data, 4);
+        assert!(matches!(
+            result,
+            Err(Error::Read(ReadError::InvalidRleData))
+        ));
+    }
+
     #[test]
     fn tiny_encode() {
         let original_data = vec![0xac, 0x00];
@@ -135,24 +213,24 @@ mod tests {
 
         let encoded_data = super::encoded(&original_data);
 
-        // Expecting: FC 73 01 42 42 80 FD 73 FE 42 80 FE 73 FD 42 80 FE 73 FD 42 80
+        // Expecting: FC 73 FF 42 FD 73 FE 42
 
         // (-(-4)+1) = 5 bytes of 0x73
         assert_eq!(encoded_data[0], 0xFC);
         assert_eq!(encoded_data[1], 0x73);
 
-        // (1)+1 = 2 bytes of discrete data (even though it is a small repeat)
-        assert_eq!(encoded_data[2], 0x01);
+        // (-(-1)+1) = 2 bytes of 0x42, which is a run because it follows one
+        assert_eq!(encoded_data[2], 0xFF);
         assert_eq!(encoded_data[3], 0x42);
-        assert_eq!(encoded_data[4], 0x42);
 
         // (-(-3)+1) = 4 bytes of 0x73
-        assert_eq!(encoded_data[5], 0xFD);
-        assert_eq!(encoded_data[6], 0x73);
+        assert_eq!(encoded_data[4], 0xFD);
+        assert_eq!(encoded_data[5], 0x73);
 
-        // (-(-2)+1) = 3 bytes of 0x73
-        assert_eq!(encoded_data[7], 0xFE);
-        assert_eq!(encoded_data[8], 0x42);
+        // (-(-2)+1) = 3 bytes of 0x42
+        assert_eq!(encoded_data[6], 0xFE);
+        assert_eq!(encoded_data[7], 0x42);
+        assert_eq!(encoded_data.len(), 8);
     }
 
     #[test]
@@ -227,6 +305,153 @@ mod tests {
         assert_eq!(encoded_data[3], 0xFF);
     }
 
+    #[test]
+    fn rle_encode_alternating_pairs() {
+        let original_data = vec![0x11, 0x11, 0x22, 0x22, 0x11, 0x11, 0x22, 0x22];
+
+        let encoded_data = super::encoded(&original_data);
+
+        // Pairs stay within a single literal.
+        let expected_data = vec![0x07, 0x11, 0x11, 0x22, 0x22, 0x11, 0x11, 0x22, 0x22];
+        assert_eq!(encoded_data, expected_data);
+    }
+
+    #[test]
+    fn rle_encode_repeat_of_three_within_literal() {
+        let original_data = vec![0x01, 0x02, 0x05, 0x05, 0x05, 0x03];
+
+        let encoded_data = super::encoded(&original_data);
+
+        // The literal is split around the run.
+        let expected_data = vec![0x01, 0x01, 0x02, 0xFE, 0x05, 0x00, 0x03];
+        assert_eq!(encoded_data, expected_data);
+    }
+
+    #[test]
+    fn rle_encode_literal_of_128() {
+        let original_data: Vec<u8> = (0..=128).collect();
+
+        let encoded_data = super::encoded(&original_data);
+
+        // A full literal, followed by a literal for the last byte.
+        assert_eq!(encoded_data.len(), 131);
+        assert_eq!(encoded_data[0], 0x7F);
+        assert_eq!(encoded_data[1..129], original_data[0..128]);
+        assert_eq!(encoded_data[129..], [0x00, 0x80]);
+    }
+
+    #[test]
+    fn rle_encode_literal_ending_before_run() {
+        let mut original_data: Vec<u8> = (0..125).collect();
+        original_data.extend_from_slice(&[0xC8, 0xC8, 0xC8]);
+
+        let encoded_data = super::encoded(&original_data);
+
+        // The literal stops short of 128 bytes so the run isn’t split.
+        assert_eq!(encoded_data.len(), 128);
+        assert_eq!(encoded_data[0], 0x7C);
+        assert_eq!(encoded_data[1..126], original_data[0..125]);
+        assert_eq!(encoded_data[126..], [0xFE, 0xC8]);
+    }
+
+    #[test]
+    fn rle_encode_run_at_end_of_full_literal() {
+        let mut original_data: Vec<u8> = (0..127).collect();
+        original_data.extend_from_slice(&[0xC8, 0xC8, 0xC8]);
+
+        let encoded_data = super::encoded(&original_data);
+
+        // Only the first byte of the run fits within the literal, so the
+        // literal is filled and the rest of the run follows as a pair.
+        assert_eq!(encoded_data.len(), 132);
+        assert_eq!(encoded_data[0], 0x7F);
+        assert_eq!(encoded_data[1..129], original_data[0..128]);
+        assert_eq!(encoded_data[129..], [0x01, 0xC8, 0xC8]);
+    }
+
+    #[test]
+    fn rle_encode_pairs_next_to_runs() {
+        // A pair between two runs.
+        let original_data = vec![0xD9, 0xD9, 0xD9, 0xDD, 0xDD, 0x6C, 0x6C, 0x6C];
+        let expected_data = vec![0xFE, 0xD9, 0xFF, 0xDD, 0xFE, 0x6C];
+        assert_eq!(super::encoded(&original_data), expected_data);
+
+        // A pair at the start, just before a run.
+        let original_data = vec![0x00, 0x00, 0xFF, 0xFF, 0xFF];
+        let expected_data = vec![0xFF, 0x00, 0xFE, 0xFF];
+        assert_eq!(super::encoded(&original_data), expected_data);
+
+        // A pair within a literal stays in the literal, even before a run.
+        let original_data = vec![0xDD, 0x6C, 0x6C, 0xD9, 0xD9, 0xD9];
+        let expected_data = vec![0x02, 0xDD, 0x6C, 0x6C, 0xFE, 0xD9];
+        assert_eq!(super::encoded(&original_data), expected_data);
+    }
+
+    #[test]
+    fn rle_encode_photoshop_rows() {
+        // Rows from the merged image and the layers of foldered.psd,
+        // which was saved by Photoshop, along with their encoded data.
+        let rows: [(&[u8], usize); 4] = [
+            (
+                &[0xf0, 0xd9, 0xbf, 0x6c, 0xfc, 0xdd, 0xff, 0x6c, 0xe4, 0xd9],
+                119,
+            ),
+            (
+                &[
+                    0xea, 0xd9, 0xec, 0x6c, 0xf4, 0xd9, 0xf0, 0x6c, 0x00, 0xdd, 0xf1, 0x6c, 0x04,
+                    0xdd, 0x6c, 0x6c, 0xd9, 0xdd, 0xea, 0xd9,
+                ],
+                119,
+            ),
+            (
+                &[
+                    0xc7, 0xd9, 0xf1, 0x6c, 0x00, 0xdd, 0xf1, 0x6c, 0xff, 0xdd, 0x01, 0xd9, 0xdd,
+                    0xe9, 0xd9,
+                ],
+                119,
+            ),
+            (&[0xff, 0x00, 0xd1, 0xff, 0xe6, 0x00], 77),
+        ];
+
+        for (encoded_row, width) in rows {
+            let row = super::decoded(encoded_row, width).unwrap();
+            assert_eq!(super::encoded(&row), encoded_row);
+        }
+    }
+
+    #[test]
+    fn rle_encode_photoshop_merged_images() {
+        for file_name in ["foldered.psd", "gradient.psd"] {
+            let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            path.push("tests/resources");
+            path.push(file_name);
+            let data = std::fs::read(path).unwrap();
+
+            // Skip the header to the number of channels and the size,
+            // then the colour mode data, image resources, and layer
+            // and mask information sections.
+            let mut reader = Reader::new(&data);
+            reader.skip(12).unwrap();
+            let number_of_channels = reader.read_u16().unwrap() as usize;
+            let height = reader.read_u32().unwrap() as usize;
+            let width = reader.read_u32().unwrap() as usize;
+            reader.skip(4).unwrap();
+            for _ in 0..3 {
+                reader.read_section().unwrap();
+            }
+            assert_eq!(reader.read_i16().unwrap(), 1);
+
+            let row_lengths: Vec<usize> = (0..number_of_channels * height)
+                .map(|_| reader.read_u16().unwrap() as usize)
+                .collect();
+            for row_length in row_lengths {
+                let encoded_row = reader.read_bytes(row_length).unwrap();
+                let row = super::decoded(encoded_row, width).unwrap();
+                assert_eq!(super::encoded(&row), encoded_row);
+            }
+        }
+    }
+
     #[test]
     fn rle_encode_long_non_repeating_values() {
         let original_data = vec![
@@ -249,6 +474,8 @@ mod tests {
 
         let encoded_data = super::encoded(&original_data);
 
+        // As in Photoshop, the literal fills up to 128 bytes and splits
+        // the run of four 0x76 bytes, as the run starts in its last two bytes.
         assert_eq!(encoded_data[0], 0x7F);
         assert_eq!(encoded_data[129], 0x1D);
 
@@ -1,14 +1,141 @@
-/// Returns the Pascal UCSD string data from a string.
+/// The characters for the bytes from 0x80 to 0xFF in MacRoman, the
+/// legacy encoding Photoshop uses for Pascal strings.
+const MAC_ROMAN_CHARACTERS: [char; 128] = [
+    'Ä', 'Å', 'Ç', 'É', 'Ñ', 'Ö', 'Ü', 'á', 'à', 'â', 'ä', 'ã', 'å', 'ç', 'é', 'è', //
+    'ê', 'ë', 'í', 'ì', 'î', 'ï', 'ñ', 'ó', 'ò', 'ô', 'ö', 'õ', 'ú', 'ù', 'û', 'ü', //
+    '†', '°', '¢', '£', '§', '•', '¶', 'ß', '®', '©', '™', '´', '¨', '≠', 'Æ', 'Ø', //
+    '∞', '±', '≤', '≥', '¥', 'µ', '∂', '∑', '∏', 'π', '∫', 'ª', 'º', 'Ω', 'æ', 'ø', //
+    '¿', '¡', '¬', '√', 'ƒ', '≈', '∆', '«', '»', '…', '\u{a0}', 'À', 'Ã', 'Õ', 'Œ', 'œ', //
+    '–', '—', '“', '”', '‘', '’', '÷', '◊', 'ÿ', 'Ÿ', '⁄', '€', '‹', '›', 'ﬁ', 'ﬂ', //
+    '‡', '·', '‚', '„', '‰', 'Â', 'Ê', 'Á', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', 'Ó', 'Ô', //
+    '\u{f8ff}', 'Ò', 'Ú', 'Û', 'Ù', 'ı', 'ˆ', '˜', '¯', '˘', '˙', '˚', '¸', '˝', '˛', 'ˇ', //
+];
+
+/// The accented letters in MacRoman, as the base letter, the combining
+/// mark that follows it in decomposed text, and the precomposed letter.
+const COMPOSITIONS: [(char, char, char); 52] = [
+    ('A', '\u{300}', 'À'),
+    ('A', '\u{301}', 'Á'),
+    ('A', '\u{302}', 'Â'),
+    ('A', '\u{303}', 'Ã'),
+    ('A', '\u{308}', 'Ä'),
+    ('A', '\u{30a}', 'Å'),
+    ('C', '\u{327}', 'Ç'),
+    ('E', '\u{300}', 'È'),
+    ('E', '\u{301}', 'É'),
+    ('E', '\u{302}', 'Ê'),
+    ('E', '\u{308}', 'Ë'),
+    ('I', '\u{300}', 'Ì'),
+    ('I', '\u{301}', 'Í'),
+    ('I', '\u{302}', 'Î'),
+    ('I', '\u{308}', 'Ï'),
+    ('N', '\u{303}', 'Ñ'),
+    ('O', '\u{300}', 'Ò'),
+    ('O', '\u{301}', 'Ó'),
+    ('O', '\u{302}', 'Ô'),
+    ('O', '\u{303}', 'Õ'),
+    ('O', '\u{308}', 'Ö'),
+    ('U', '\u{300}', 'Ù'),
+    ('U', '\u{301}', 'Ú'),
+    ('U', '\u{302}', 'Û'),
+    ('U', '\u{308}', 'Ü'),
+    ('Y', '\u{308}', 'Ÿ'),
+    ('a', '\u{300}', 'à'),
+    ('a', '\u{301}', 'á'),
+    ('a', '\u{302}', 'â'),
+    ('a', '\u{303}', 'ã'),
+    ('a', '\u{308}', 'ä'),
+    ('a', '\u{30a}', 'å'),
+    ('c', '\u{327}', 'ç'),
+    ('e', '\u{300}', 'è'),
+    ('e', '\u{301}', 'é'),
+    ('e', '\u{302}', 'ê'),
+    ('e', '\u{308}', 'ë'),
+    ('i', '\u{300}', 'ì'),
+    ('i', '\u{301}', 'í'),
+    ('i', '\u{302}', 'î'),
+    ('i', '\u{308}', 'ï'),
+    ('n', '\u{303}', 'ñ'),
+    ('o', '\u{300}', 'ò'),
+    ('o', '\u{301}', 'ó'),
+    ('o', '\u{302}', 'ô'),
+    ('o', '\u{303}', 'õ'),
+    ('o', '\u{308}', 'ö'),
+    ('u', '\u{300}', 'ù'),
+    ('u', '\u{301}', 'ú'),
+    ('u', '\u{302}', 'û'),
+    ('u', '\u{308}', 'ü'),
+    ('y', '\u{308}', 'ÿ'),
+];
+
+/// The byte written for characters that MacRoman can’t represent.
+const REPLACEMENT_BYTE: u8 = b'?';
+
+/// The range of the combining diacritical marks.
+const COMBINING_MARKS: std::ops::RangeInclusive<char> = '\u{300}'..='\u{36f}';
+
+/// Returns the Pascal UCSD string data from a string. The string is
+/// written in MacRoman, with letters followed by combining marks
+/// composed where possible, other combining marks dropped, and anything
+/// else MacRoman can’t represent replaced with a question mark. Like
+/// Photoshop, strings longer than 255 bytes are truncated.
 pub fn data_from_string(string: Option<&String>) -> anyhow::Result<Vec<u8>> {
     let Some(string) = string else {
         return Ok(vec![0x00, 0x00]);
     };
 
-    let mut bytes = string.as_bytes().to_vec();
+    let mut characters: Vec<char> = Vec::new();
+    for character in string.chars() {
+        if !COMBINING_MARKS.contains(&character) {
+            characters.push(character);
+            continue;
+        }
+        let composition = characters.last().and_then(|&base| {
+            COMPOSITIONS
+                .iter()
+                .find(|(letter, mark, _)| *letter == base && *mark == character)
+        });
+        if let Some((_, _, composed)) = composition {
+            characters.pop();
+            characters.push(*composed);
+        }
+    }
+
+    let mut bytes: Vec<u8> = characters
+        .into_iter()
+        .map(mac_roman_byte)
+        .take(u8::MAX as usize)
+        .collect();
     bytes.insert(0, bytes.len() as u8);
     Ok(bytes)
 }
 
+/// Returns the string for Pascal string bytes in MacRoman.
+pub fn string_from_data(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| {
+            if byte.is_ascii() {
+                byte as char
+            } else {
+                MAC_ROMAN_CHARACTERS[byte as usize - 0x80]
+            }
+        })
+        .collect()
+}
+
+/// Returns the MacRoman byte for a character, or the replacement byte
+/// when there isn’t one.
+fn mac_roman_byte(character: char) -> u8 {
+    if character.is_ascii() {
+        return character as u8;
+    }
+    MAC_ROMAN_CHARACTERS
+        .iter()
+        .position(|&mac_roman_character| mac_roman_character == character)
+        .map_or(REPLACEMENT_BYTE, |index| index as u8 + 0x80)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::data;
@@ -22,4 +149,33 @@ mod tests {
 
         assert_eq!(result, expected_data);
     }
+
+    #[test]
+    fn data_from_non_ascii_string() {
+        // é is 0x8E in MacRoman.
+        let expected_data = vec![0x04, 0x43, 0x61, 0x66, 0x8e];
+
+        let result = super::data_from_string(Some(&"Café".to_string())).unwrap();
+        assert_eq!(result, expected_data);
+        assert_eq!(super::string_from_data(&result[1..]), "Café");
+
+        // An e followed by a combining acute accent.
+        let result = super::data_from_string(Some(&"Cafe\u{301}".to_string())).unwrap();
+        assert_eq!(result, expected_data);
+
+        // Characters without a MacRoman equivalent are replaced.
+        let result = super::data_from_string(Some(&"日本".to_string())).unwrap();
+        assert_eq!(result, vec![0x02, 0x3f, 0x3f]);
+    }
+
+    #[test]
+    fn data_from_long_string() {
+        let string = "é".repeat(300);
+
+        let result = super::data_from_string(Some(&string)).unwrap();
+
+        assert_eq!(result.len(), 256);
+        assert_eq!(result[0], 0xff);
+        assert!(result[1..].iter().all(|&byte| byte == 0x8e));
+    }
 }
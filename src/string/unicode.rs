@@ -1,9 +1,12 @@
 use file_stream::write::FileStreamWriter;
 
-use crate::document;
+use crate::{data, document};
 
-/// Returns the unicode string data from a string.
-pub fn data_from_string(string: Option<&String>) -> anyhow::Result<Vec<u8>> {
+/// Returns the unicode string data from a string. When `is_padded` is set,
+/// null code units are appended until the string data is a multiple of four
+/// bytes, which is what Photoshop writes. The string length never includes
+/// the nulls.
+pub fn data_from_string(string: Option<&String>, is_padded: bool) -> anyhow::Result<Vec<u8>> {
     let mut file_stream = FileStreamWriter::new();
     file_stream.write_bytes(&document::constants::RESOURCE_SIGNATURE)?;
     file_stream.write_bytes("luni".as_bytes())?;
@@ -20,11 +23,16 @@ pub fn data_from_string(string: Option<&String>) -> anyhow::Result<Vec<u8>> {
         string_file_stream.write_be(&character)?;
     }
 
-    // Write the length of the string data.
-    file_stream.write_be(&(string_file_stream.data().len() as u32))?;
-    file_stream.write_bytes(string_file_stream.data())?;
+    // The documentation says to pad, but this seems to make it less compatible,
+    // so it is only done on request.
+    let mut string_data = string_file_stream.data().to_vec();
+    if is_padded {
+        data::pad(&mut string_data, 4);
+    }
 
-    // The documentation says to pad, but this seems to make it less compatible.
+    // Write the length of the string data.
+    file_stream.write_be(&(string_data.len() as u32))?;
+    file_stream.write_bytes(&string_data)?;
 
     Ok(file_stream.data().to_vec())
 }
@@ -45,7 +53,7 @@ mod tests {
             0x00, 0x72, // .r
         ];
 
-        let result = super::data_from_string(Some(&"Layer".to_string())).unwrap();
+        let result = super::data_from_string(Some(&"Layer".to_string()), false).unwrap();
 
         assert_eq!(result, expected_data);
     }
@@ -66,7 +74,47 @@ mod tests {
             0x00, 0x77, // .w
         ];
 
-        let result = super::data_from_string(Some(&"Yel’low".to_string())).unwrap();
+        let result = super::data_from_string(Some(&"Yel’low".to_string()), false).unwrap();
+
+        assert_eq!(result, expected_data);
+    }
+
+    #[test]
+    fn padded_data_from_string() {
+        // Taken from a file saved by Photoshop.
+        let expected_data = vec![
+            0x38, 0x42, 0x49, 0x4D, // 8BIM
+            0x6C, 0x75, 0x6E, 0x69, // luni
+            0x00, 0x00, 0x00, 0x10, // Length of bytes
+            0x00, 0x00, 0x00, 0x05, // Length of string
+            0x00, 0x50, // .P
+            0x00, 0x65, // .e
+            0x00, 0x64, // .d
+            0x00, 0x72, // .r
+            0x00, 0x6F, // .o
+            0x00, 0x00, // Null
+        ];
+
+        let result = super::data_from_string(Some(&"Pedro".to_string()), true).unwrap();
+
+        assert_eq!(result, expected_data);
+    }
+
+    #[test]
+    fn padded_data_from_even_string() {
+        // Strings that are already aligned don’t get a null.
+        let expected_data = vec![
+            0x38, 0x42, 0x49, 0x4D, // 8BIM
+            0x6C, 0x75, 0x6E, 0x69, // luni
+            0x00, 0x00, 0x00, 0x0C, // Length of bytes
+            0x00, 0x00, 0x00, 0x04, // Length of string
+            0x00, 0x50, // .P
+            0x00, 0x65, // .e
+            0x00, 0x64, // .d
+            0x00, 0x72, // .r
+        ];
+
+        let result = super::data_from_string(Some(&"Pedr".to_string()), true).unwrap();
 
         assert_eq!(result, expected_data);
     }
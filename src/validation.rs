@@ -0,0 +1,169 @@
+use crate::document::{constants, Document};
+use crate::error::ValidationError;
+use crate::image_compression::ImageCompression;
+use crate::layer::Layer;
+use crate::reader::Reader;
+
+/// The most bytes the layer information can be padded by.
+const MAXIMUM_LAYER_INFO_PADDING: usize = 3;
+
+/// Checks that the data for a Photoshop file is consistent, as a
+/// self-check before files are shared. The signature and version are
+/// checked, along with the length of each section, image resource and
+/// layer record, and the file is then read in full to catch anything else.
+/// Only standard documents (PSD) can be checked, so large documents (PSB)
+/// are reported as `ValidationError::UnsupportedVersion`.
+pub fn validate_bytes(data: &[u8]) -> Result<(), ValidationError> {
+    let mut reader = Reader::new(data);
+
+    // HEADER SECTION
+    let overrun = |_| ValidationError::Overrun("header");
+    if reader.read_array::<4>().map_err(overrun)? != constants::FILE_SIGNATURE {
+        return Err(ValidationError::InvalidSignature);
+    }
+    let version = reader.read_i16().map_err(overrun)?;
+    if version != constants::VERSION_NUMBER {
+        return Err(ValidationError::UnsupportedVersion(version));
+    }
+    // The padding, channels, size, depth and colour mode.
+    reader.skip(20).map_err(overrun)?;
+
+    // COLOUR MODE DATA SECTION
+    read_section(&mut reader, "colour mode data section")?;
+
+    // IMAGE RESOURCES SECTION
+    let mut image_resources = read_section(&mut reader, "image resources section")?;
+    while image_resources.remaining() > 0 {
+        let overrun = |_| ValidationError::Overrun("image resource");
+        if image_resources.read_array::<4>().map_err(overrun)? != constants::RESOURCE_SIGNATURE {
+            return Err(ValidationError::InvalidResourceSignature);
+        }
+        image_resources.read_i16().map_err(overrun)?;
+        image_resources.read_pascal_string(2).map_err(overrun)?;
+        let length = image_resources.read_u32().map_err(overrun)? as usize;
+        // The data is padded to an even length.
+        image_resources.skip(length + length % 2).map_err(overrun)?;
+    }
+
+    // LAYER AND MASK INFORMATION SECTION
+    let mut layer_and_mask_info = read_section(&mut reader, "layer and mask information section")?;
+    if layer_and_mask_info.remaining() > 0 {
+        let mut layer_info = read_section(&mut layer_and_mask_info, "layer information")?;
+        if layer_info.remaining() > 0 {
+            let overrun = |_| ValidationError::Overrun("layer record");
+            let number_of_layers = layer_info.read_i16().map_err(overrun)?.unsigned_abs();
+            let mut channel_data_length = 0;
+            for _ in 0..number_of_layers {
+                let (_, channel_info) = Layer::from_record(&mut layer_info).map_err(overrun)?;
+                channel_data_length += channel_info.iter().map(|info| info.length).sum::<usize>();
+            }
+            layer_info
+                .skip(channel_data_length)
+                .map_err(|_| ValidationError::Overrun("layer channel data"))?;
+            if layer_info.remaining() > MAXIMUM_LAYER_INFO_PADDING {
+                return Err(ValidationError::UnusedData(
+                    "layer information",
+                    layer_info.remaining(),
+                ));
+            }
+        }
+    }
+
+    // IMAGE DATA SECTION
+    if reader.remaining() > 0 {
+        let compression = reader
+            .read_i16()
+            .map_err(|_| ValidationError::Overrun("image data section"))?;
+        if ImageCompression::from_value(compression).is_none() {
+            return Err(ValidationError::UnsupportedCompression(compression));
+        }
+    }
+
+    Document::from_data(data)
+        .map(|_| ())
+        .map_err(|error| ValidationError::Unreadable(error.to_string()))
+}
+
+/// Reads a section that starts with its length, checking that it
+/// doesn’t run past the end of the data.
+fn read_section<'a>(
+    reader: &mut Reader<'a>,
+    name: &'static str,
+) -> Result<Reader<'a>, ValidationError> {
+    reader
+        .read_section()
+        .map_err(|_| ValidationError::Overrun(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use graphics::{Color, Image, Rect, Size};
+
+    use super::*;
+
+    /// The length of the header section.
+    const HEADER_LENGTH: usize = 26;
+
+    /// Returns the data for a document with a layer and a preview image.
+    fn file_data() -> Vec<u8> {
+        let mut document = Document::new();
+        document.size = Size {
+            width: 4,
+            height: 2,
+        };
+        let bounds = Rect::new(0, 0, 4, 2);
+        let mut layer = Layer::new(bounds);
+        layer.image = Some(Image::color(&Color::YELLOW, bounds.size.into()));
+        document.layers = vec![layer];
+        document.preview_image = Some(Image::color(&Color::YELLOW, document.size));
+        document.file_data().unwrap()
+    }
+
+    #[test]
+    fn validate_valid_bytes() {
+        assert!(validate_bytes(&file_data()).is_ok());
+    }
+
+    #[test]
+    fn validate_corrupted_length() {
+        let mut data = file_data();
+        // The length of the image resources section, after the header
+        // and the colour mode data section.
+        let mut reader = Reader::new(&data);
+        reader.skip(HEADER_LENGTH).unwrap();
+        reader.read_section().unwrap();
+        let offset = data.len() - reader.remaining();
+        let length = reader.read_u32().unwrap();
+        data[offset..offset + 4].copy_from_slice(&length.wrapping_add(2).to_be_bytes());
+
+        let error = validate_bytes(&data).unwrap_err();
+        assert!(matches!(error, ValidationError::Overrun("image resource")));
+    }
+
+    #[test]
+    fn validate_large_document() {
+        let mut document = Document::from_image(Image::color(
+            &Color::YELLOW,
+            Size {
+                width: 4,
+                height: 2,
+            },
+        ));
+        let data = document.file_data_psb().unwrap();
+
+        let error = validate_bytes(&data).unwrap_err();
+        assert!(matches!(
+            error,
+            ValidationError::UnsupportedVersion(constants::LARGE_DOCUMENT_VERSION_NUMBER)
+        ));
+    }
+
+    #[test]
+    fn validate_invalid_signature() {
+        let mut data = file_data();
+        data[0] = 0;
+
+        let error = validate_bytes(&data).unwrap_err();
+        assert!(matches!(error, ValidationError::InvalidSignature));
+    }
+}
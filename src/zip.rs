@@ -0,0 +1,102 @@
+//! ZIP (zlib) compression, as used for the image data in Photoshop
+//! documents, with or without prediction.
+
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::error::ReadError;
+
+/// Returns the data compressed with zlib. With prediction, each byte is
+/// stored as the difference from the byte before it in the same row,
+/// which compresses gradients much better.
+pub(crate) fn encoded(
+    source: &[u8],
+    width: usize,
+    uses_prediction: bool,
+) -> anyhow::Result<Vec<u8>> {
+    let mut data = source.to_vec();
+    if uses_prediction {
+        for row in data.chunks_mut(width.max(1)) {
+            for index in (1..row.len()).rev() {
+                row[index] = row[index].wrapping_sub(row[index - 1]);
+            }
+        }
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Returns the data decompressed from zlib, undoing any prediction. The
+/// decompressed data has to be the expected length.
+pub(crate) fn decoded(
+    source: &[u8],
+    width: usize,
+    length: usize,
+    uses_prediction: bool,
+) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(length);
+    ZlibDecoder::new(source)
+        .read_to_end(&mut data)
+        .map_err(|_| ReadError::InvalidZipData)?;
+    if data.len() != length {
+        anyhow::bail!(ReadError::InvalidZipData);
+    }
+
+    if uses_prediction {
+        for row in data.chunks_mut(width.max(1)) {
+            for index in 1..row.len() {
+                row[index] = row[index].wrapping_add(row[index - 1]);
+            }
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ReadError;
+
+    #[test]
+    fn round_trip() {
+        // Two rows of a gradient.
+        let data = [0x10, 0x20, 0x30, 0x40, 0x05, 0x04, 0x03, 0xff];
+
+        for uses_prediction in [false, true] {
+            let encoded = super::encoded(&data, 4, uses_prediction).unwrap();
+            let decoded = super::decoded(&encoded, 4, data.len(), uses_prediction).unwrap();
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn prediction() {
+        let data = [0x10, 0x20, 0x30, 0x40, 0x05, 0x04, 0x03, 0xff];
+        let encoded = super::encoded(&data, 4, true).unwrap();
+
+        // Without undoing the prediction, each byte is the difference
+        // from the one before it in its row.
+        let decoded = super::decoded(&encoded, 4, data.len(), false).unwrap();
+        assert_eq!(decoded, [0x10, 0x10, 0x10, 0x10, 0x05, 0xff, 0xff, 0xfc]);
+    }
+
+    #[test]
+    fn invalid_data() {
+        let encoded = super::encoded(&[0x01, 0x02], 2, false).unwrap();
+
+        for result in [
+            super::decoded(&[0x01, 0x02, 0x03], 2, 2, false),
+            super::decoded(&encoded, 2, 4, false),
+        ] {
+            let error = result.unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<ReadError>(),
+                Some(ReadError::InvalidZipData)
+            ));
+        }
+    }
+}